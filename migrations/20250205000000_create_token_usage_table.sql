@@ -0,0 +1,18 @@
+-- Token usage ledger, one row per model request or streamed answer.
+-- conversation_id identifies a chat session or solver run; it isn't a foreign key
+-- because web chat sessions and solver runs aren't stored in conversations.
+CREATE TABLE token_usage (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id TEXT,
+    conversation_id UUID NOT NULL,
+    source TEXT NOT NULL,  -- 'chat', 'solver', ...
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    reasoning_tokens BIGINT NOT NULL DEFAULT 0,
+    prompt_cache_hit_tokens BIGINT NOT NULL DEFAULT 0,
+    prompt_cache_miss_tokens BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX token_usage_conversation_id_idx ON token_usage(conversation_id);
+CREATE INDEX token_usage_user_id_idx ON token_usage(user_id);
@@ -1,12 +1,21 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use clap::{Parser, Subcommand};
 use openagents::server::services::{
-    deepseek::{ChatMessage, DeepSeekService, ToolChoice},
+    agent::{AgentEvent, AgentLoop},
+    cassette::Cassette,
+    deepseek::ChatMessage,
     github_issue::GitHubService,
+    provider::{provider_from_env, ChatProvider, CHAT_MODEL_ROLE},
+};
+use openagents::server::tools::{
+    create_tools, mcp_tools_from_env, Approval, ApprovalRequest, ToolApprover, ToolContext,
+    ToolRegistry,
 };
-use serde_json::json;
 use std::io::{self, Write};
+use std::sync::Arc;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use tokio::sync::mpsc;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -38,41 +47,88 @@ fn print_colored(role: &str, content: &str) -> Result<()> {
     Ok(())
 }
 
+/// Asks on the terminal before side-effecting tool calls run.
+struct TerminalApprover;
+
+#[async_trait]
+impl ToolApprover for TerminalApprover {
+    async fn approve(&self, request: &ApprovalRequest) -> Approval {
+        let prompt = format!(
+            "{} {}\nAllow this? [y/N]",
+            request.description, request.arguments
+        );
+        if print_colored("system", &prompt).is_err() {
+            return Approval::Denied { reason: None };
+        }
+        let answer = tokio::task::spawn_blocking(|| {
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer).map(|_| answer)
+        })
+        .await;
+        match answer {
+            Ok(Ok(answer)) if answer.trim().eq_ignore_ascii_case("y") => Approval::Approved,
+            _ => Approval::Denied { reason: None },
+        }
+    }
+}
+
+/// Runs the agent loop on the conversation, printing each tool call as it
+/// starts, and appends the tool exchanges and the answer.
+async fn respond(
+    service: Arc<dyn ChatProvider>,
+    tools: Arc<ToolRegistry>,
+    messages: &mut Vec<ChatMessage>,
+) -> Result<()> {
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let agent = AgentLoop::new(service, tools).with_events(events_tx);
+    let history = messages.clone();
+    let context = ToolContext {
+        approver: Some(Arc::new(TerminalApprover)),
+        ..Default::default()
+    };
+    let run = async move { agent.run(history, &context).await };
+    let print = async {
+        while let Some(event) = events.recv().await {
+            if let AgentEvent::ToolCall { status, .. } = event {
+                print_colored("system", &status)?;
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let (run, printed) = tokio::join!(run, print);
+    printed?;
+    let run = run?;
+    print_colored("assistant", &run.content)?;
+
+    *messages = run.messages;
+    messages.push(ChatMessage {
+        role: "assistant".to_string(),
+        content: run.content,
+        tool_call_id: None,
+        tool_calls: None,
+    });
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize DeepSeek service
-    let deepseek_api_key = std::env::var("DEEPSEEK_API_KEY").expect("DEEPSEEK_API_KEY must be set");
-    let service = DeepSeekService::new(deepseek_api_key);
+    // Serve recorded API traffic instead of the network when OPENAGENTS_CASSETTE is set
+    let _cassette = Cassette::from_env().await?;
+
+    // Initialize the configured model provider
+    let service = provider_from_env(CHAT_MODEL_ROLE)?;
 
     // Initialize GitHub service
     let github_token = std::env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN must be set");
-    let github_service: GitHubService = GitHubService::new(Some(github_token))?;
-
-    // Create GitHub issue tool
-    let get_issue_tool = DeepSeekService::create_tool(
-        "get_github_issue".to_string(),
-        Some("Get a GitHub issue by number".to_string()),
-        json!({
-            "type": "object",
-            "properties": {
-                "owner": {
-                    "type": "string",
-                    "description": "The owner of the repository"
-                },
-                "repo": {
-                    "type": "string",
-                    "description": "The name of the repository"
-                },
-                "issue_number": {
-                    "type": "integer",
-                    "description": "The issue number"
-                }
-            },
-            "required": ["owner", "repo", "issue_number"]
-        }),
-    );
+    let github_service = GitHubService::new(Some(github_token))?;
+
+    // Create the tools the model can call, plus those of the MCP servers in MCP_CONFIG
+    let mut tools = create_tools(Arc::new(github_service));
+    tools.merge(mcp_tools_from_env().await);
+    let tools = Arc::new(tools);
 
     match &cli.command {
         Some(Commands::Chat { message }) => {
@@ -96,66 +152,7 @@ async fn main() -> Result<()> {
                     tool_calls: None,
                 });
 
-                let (response, _, tool_calls) = service
-                    .chat_with_tools_messages(
-                        messages.clone(),
-                        vec![get_issue_tool.clone()],
-                        Some(ToolChoice::Auto("auto".to_string())),
-                        false,
-                    )
-                    .await?;
-
-                print_colored("assistant", &response)?;
-
-                if let Some(tool_calls) = tool_calls {
-                    for tool_call in tool_calls {
-                        if tool_call.function.name == "get_github_issue" {
-                            let args: serde_json::Value =
-                                serde_json::from_str(&tool_call.function.arguments)?;
-                            let owner = args["owner"].as_str().unwrap_or("OpenAgentsInc");
-                            let repo = args["repo"].as_str().unwrap_or("openagents");
-                            let issue_number = args["issue_number"].as_i64().unwrap_or(0) as i32;
-
-                            print_colored(
-                                "system",
-                                &format!(
-                                    "Fetching GitHub issue #{} from {}/{}",
-                                    issue_number, owner, repo
-                                ),
-                            )?;
-
-                            let issue = github_service.get_issue(owner, repo, issue_number).await?;
-
-                            messages.push(ChatMessage {
-                                role: "assistant".to_string(),
-                                content: format!(
-                                    "Let me fetch GitHub issue #{} for you.",
-                                    issue_number
-                                ),
-                                tool_call_id: None,
-                                tool_calls: Some(vec![tool_call.clone()]),
-                            });
-
-                            messages.push(ChatMessage {
-                                role: "tool".to_string(),
-                                content: serde_json::to_string(&issue)?,
-                                tool_call_id: Some(tool_call.id),
-                                tool_calls: None,
-                            });
-
-                            let (response, _, _) = service
-                                .chat_with_tools_messages(
-                                    messages.clone(),
-                                    vec![get_issue_tool.clone()],
-                                    None,
-                                    false,
-                                )
-                                .await?;
-
-                            print_colored("assistant", &response)?;
-                        }
-                    }
-                }
+                respond(service.clone(), tools.clone(), &mut messages).await?;
             }
 
             // Interactive chat loop
@@ -182,66 +179,7 @@ async fn main() -> Result<()> {
                     tool_calls: None,
                 });
 
-                let (response, _, tool_calls) = service
-                    .chat_with_tools_messages(
-                        messages.clone(),
-                        vec![get_issue_tool.clone()],
-                        Some(ToolChoice::Auto("auto".to_string())),
-                        false,
-                    )
-                    .await?;
-
-                print_colored("assistant", &response)?;
-
-                if let Some(tool_calls) = tool_calls {
-                    for tool_call in tool_calls {
-                        if tool_call.function.name == "get_github_issue" {
-                            let args: serde_json::Value =
-                                serde_json::from_str(&tool_call.function.arguments)?;
-                            let owner = args["owner"].as_str().unwrap_or("OpenAgentsInc");
-                            let repo = args["repo"].as_str().unwrap_or("openagents");
-                            let issue_number = args["issue_number"].as_i64().unwrap_or(0) as i32;
-
-                            print_colored(
-                                "system",
-                                &format!(
-                                    "Fetching GitHub issue #{} from {}/{}",
-                                    issue_number, owner, repo
-                                ),
-                            )?;
-
-                            let issue = github_service.get_issue(owner, repo, issue_number).await?;
-
-                            messages.push(ChatMessage {
-                                role: "assistant".to_string(),
-                                content: format!(
-                                    "Let me fetch GitHub issue #{} for you.",
-                                    issue_number
-                                ),
-                                tool_call_id: None,
-                                tool_calls: Some(vec![tool_call.clone()]),
-                            });
-
-                            messages.push(ChatMessage {
-                                role: "tool".to_string(),
-                                content: serde_json::to_string(&issue)?,
-                                tool_call_id: Some(tool_call.id),
-                                tool_calls: None,
-                            });
-
-                            let (response, _, _) = service
-                                .chat_with_tools_messages(
-                                    messages.clone(),
-                                    vec![get_issue_tool.clone()],
-                                    None,
-                                    false,
-                                )
-                                .await?;
-
-                            print_colored("assistant", &response)?;
-                        }
-                    }
-                }
+                respond(service.clone(), tools.clone(), &mut messages).await?;
             }
         }
         None => {
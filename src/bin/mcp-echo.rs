@@ -0,0 +1,108 @@
+use anyhow::Result;
+use openagents::server::mcp::protocol::{
+    CallToolResult, Request, Response, RpcError, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND,
+    PARSE_ERROR, PROTOCOL_VERSION,
+};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// A minimal MCP server over stdio with an `echo` tool and a `fail` tool, for
+/// trying out and testing MCP clients without any real tools.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    let mut initialized = false;
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                if request.method == "notifications/initialized" {
+                    initialized = true;
+                }
+                let Some(id) = request.id.clone() else {
+                    continue;
+                };
+                match handle(&request, initialized) {
+                    Ok(result) => Response::success(id, result),
+                    Err(error) => Response::failure(id, error),
+                }
+            }
+            Err(e) => Response::failure(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        stdout.write_all(line.as_bytes()).await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
+
+fn handle(request: &Request, initialized: bool) -> Result<Value, RpcError> {
+    let params = request.params.clone().unwrap_or(Value::Null);
+    match request.method.as_str() {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {"tools": {}},
+            "serverInfo": {"name": "mcp-echo", "version": env!("CARGO_PKG_VERSION")}
+        })),
+        "ping" => Ok(json!({})),
+        _ if !initialized => Err(RpcError::new(
+            INVALID_REQUEST,
+            "Expected notifications/initialized first",
+        )),
+        // Two pages, so clients have to follow the cursor
+        "tools/list" => match params["cursor"].as_str() {
+            None => Ok(json!({
+                "tools": [{
+                    "name": "echo",
+                    "description": "Repeat the given text",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {"text": {"type": "string"}},
+                        "required": ["text"]
+                    },
+                    "annotations": {"readOnlyHint": true}
+                }],
+                "nextCursor": "2"
+            })),
+            Some("2") => Ok(json!({
+                "tools": [{
+                    "name": "fail",
+                    "description": "Always fails with the given message",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {"message": {"type": "string"}}
+                    }
+                }]
+            })),
+            Some(cursor) => Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown cursor {}", cursor),
+            )),
+        },
+        "tools/call" => {
+            let result = match params["name"].as_str() {
+                Some("echo") => {
+                    CallToolResult::text(params["arguments"]["text"].as_str().unwrap_or_default())
+                }
+                Some("fail") => CallToolResult::error(
+                    params["arguments"]["message"]
+                        .as_str()
+                        .unwrap_or("Failed as requested"),
+                ),
+                _ => {
+                    return Err(RpcError::new(
+                        INVALID_PARAMS,
+                        format!("Unknown tool {}", params["name"]),
+                    ))
+                }
+            };
+            Ok(serde_json::to_value(result).expect("results serialize"))
+        }
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )),
+    }
+}
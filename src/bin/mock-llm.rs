@@ -0,0 +1,54 @@
+use anyhow::Result;
+use clap::Parser;
+use openagents::server::services::mock_llm::{self, Scenario};
+use std::path::PathBuf;
+use tracing::info;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Serves DeepSeek-compatible chat completions from a scripted scenario, so the
+/// web app and CLIs can run end to end without network access or API spend.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// YAML or JSON scenario file (defaults to configuration/mock-llm.yaml)
+    #[arg(short, long)]
+    scenario: Option<PathBuf>,
+
+    /// Port to listen on
+    #[arg(short, long, default_value_t = 8787)]
+    port: u16,
+
+    /// Address to bind to
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "openagents=info".into()),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let scenario = match &cli.scenario {
+        Some(path) => Scenario::load(path)?,
+        None => Scenario::builtin(),
+    };
+    info!("Loaded scenario with {} rules", scenario.rules.len());
+
+    let listener = tokio::net::TcpListener::bind(format!("{}:{}", cli.host, cli.port)).await?;
+    let address = listener.local_addr()?;
+    info!("Mock LLM listening on http://{}", address);
+    info!(
+        "Point the app at it with DEEPSEEK_API_URL=http://{}",
+        address
+    );
+
+    axum::serve(listener, mock_llm::router(scenario)).await?;
+    Ok(())
+}
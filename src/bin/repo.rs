@@ -4,7 +4,13 @@ use dotenvy::dotenv;
 use openagents::{
     repo::{cleanup_temp_dir, clone_repository, run_cargo_tests, RepoContext},
     repomap::generate_repo_map,
-    server::services::{deepseek::DeepSeekService, github_issue::GitHubService, StreamUpdate},
+    server::services::{
+        cassette::Cassette,
+        deepseek::GenerationOptions,
+        github_issue::GitHubService,
+        provider::{provider_from_env, CHAT_MODEL_ROLE},
+        StreamUpdate,
+    },
 };
 use std::env;
 use std::io::{stdout, Write};
@@ -35,9 +41,12 @@ async fn main() -> Result<()> {
         bail!("Failed to load .env file: {}", e);
     }
 
-    // Get API keys immediately and fail if not present
-    let api_key = env::var("DEEPSEEK_API_KEY")
-        .map_err(|_| anyhow::anyhow!("DEEPSEEK_API_KEY not found in environment or .env file"))?;
+    // Serve recorded API traffic instead of the network when OPENAGENTS_CASSETTE is set
+    let _cassette = Cassette::from_env().await?;
+
+    // Resolve the model provider immediately and fail if it is not configured
+    let service = provider_from_env(CHAT_MODEL_ROLE)?;
+    let api_key = env::var("DEEPSEEK_API_KEY").unwrap_or_default();
     let github_token = env::var("GITHUB_TOKEN").ok();
 
     // Define the temporary directory path
@@ -63,28 +72,30 @@ async fn main() -> Result<()> {
     println!("{}", map);
 
     // Run cargo test
-    let test_output = run_cargo_tests(&ctx.temp_dir).await?;
-
-    // Initialize DeepSeek service
-    let service = DeepSeekService::new(ctx.api_key);
+    println!("\nRunning cargo test...");
+    let test_report = run_cargo_tests(&ctx.temp_dir).await?;
+    println!("{}", test_report.for_prompt());
 
     if cli.test {
         println!("\nAnalyzing test coverage and generating test suggestions...");
 
-        // First, analyze the test output to find uncovered modules/functions
+        // First, analyze the test results to find uncovered modules/functions
         let coverage_prompt = format!(
-            "You are a Rust testing expert. Analyze this test output and repository map to identify \
+            "You are a Rust testing expert. Analyze these test results and repository map to identify \
             specific functions or modules that lack test coverage. Focus only on test coverage analysis.\n\n\
-            Test output:\n{}\n\nRepository map:\n{}\n\n\
+            Test results:\n{}\n\nRepository map:\n{}\n\n\
             List the specific functions/modules that need test coverage, in order of importance. \
             For each one, explain why it needs testing and what scenarios should be tested.",
-            test_output, map
+            test_report.for_prompt(),
+            map
         );
 
         print_colored("\nTest Coverage Analysis Reasoning:\n", Color::Yellow)?;
         let mut coverage_analysis = String::new();
         let mut in_reasoning = true;
-        let mut stream = service.chat_stream(coverage_prompt, true).await;
+        let mut stream = service
+            .chat_stream(coverage_prompt, GenerationOptions::reasoner())
+            .await;
 
         while let Some(update) = stream.recv().await {
             match update {
@@ -101,6 +112,9 @@ async fn main() -> Result<()> {
                     coverage_analysis.push_str(&c);
                     stdout().flush()?;
                 }
+                StreamUpdate::Error(e) => {
+                    print_colored(&format!("\nDeepSeek API error: {}\n", e), Color::Red)?;
+                }
                 StreamUpdate::Done => break,
                 _ => {}
             }
@@ -121,7 +135,9 @@ async fn main() -> Result<()> {
         print_colored("\nTest Implementation Reasoning:\n", Color::Yellow)?;
         let mut test_code = String::new();
         let mut in_reasoning = true;
-        let mut stream = service.chat_stream(test_prompt, true).await;
+        let mut stream = service
+            .chat_stream(test_prompt, GenerationOptions::reasoner())
+            .await;
 
         while let Some(update) = stream.recv().await {
             match update {
@@ -138,6 +154,9 @@ async fn main() -> Result<()> {
                     test_code.push_str(&c);
                     stdout().flush()?;
                 }
+                StreamUpdate::Error(e) => {
+                    print_colored(&format!("\nDeepSeek API error: {}\n", e), Color::Red)?;
+                }
                 StreamUpdate::Done => break,
                 _ => {}
             }
@@ -2,15 +2,28 @@ use anyhow::{bail, Result};
 use clap::Parser;
 use dotenvy::dotenv;
 use openagents::{
-    repo::{cleanup_temp_dir, clone_repository, RepoContext},
+    repo::{
+        changed_files, cleanup_temp_dir, clone_repository_with_token, head_commit_sha,
+        implement_plan, plan_prompt, run_cargo_tests, RepoContext,
+    },
     repomap::generate_repo_map,
-    server::services::{deepseek::DeepSeekService, github_issue::GitHubService, StreamUpdate},
+    server::models::usage::CreateUsageRequest,
+    server::services::{
+        cassette::Cassette,
+        deepseek::{ChatCompletion, GenerationOptions, Usage},
+        github_issue::{GitHubApiError, GitHubService},
+        github_types::NewPullRequest,
+        provider::{provider_from_env, CHAT_MODEL_ROLE},
+        ChatDatabase, StreamUpdate,
+    },
 };
+use sqlx::PgPool;
 use std::env;
 use std::io::{stdout, Write};
 use std::time::Duration;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tokio::time::timeout;
+use uuid::Uuid;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -45,13 +58,16 @@ async fn main() -> Result<()> {
         bail!("Failed to load .env file: {}", e);
     }
 
+    // Serve recorded API traffic instead of the network when OPENAGENTS_CASSETTE is set
+    let _cassette = Cassette::from_env().await?;
+
     // Get API keys immediately and fail if not present
-    let api_key = env::var("DEEPSEEK_API_KEY")
-        .map_err(|_| anyhow::anyhow!("DEEPSEEK_API_KEY not found in environment or .env file"))?;
+    let api_key = env::var("DEEPSEEK_API_KEY").unwrap_or_default();
     let github_token = env::var("GITHUB_TOKEN")
         .map_err(|_| anyhow::anyhow!("GITHUB_TOKEN not found in environment or .env file"))?;
+    let model = provider_from_env(CHAT_MODEL_ROLE)?;
 
-    println!("DeepSeek API key length: {}", api_key.len());
+    println!("Using {} provider", model.name());
     println!("GitHub token length: {}", github_token.len());
 
     // Parse repo owner and name
@@ -63,18 +79,21 @@ async fn main() -> Result<()> {
 
     // Initialize services
     let github_service = GitHubService::new(Some(github_token.clone()))?;
-    let deepseek_service = DeepSeekService::new(api_key.clone());
 
     // Fetch issue details
     print_colored("\nFetching issue details...\n", Color::Blue)?;
-    let issue = github_service
-        .get_issue(owner, repo_name, cli.issue)
+    let thread = github_service
+        .get_issue_thread(owner, repo_name, cli.issue)
         .await?;
+    let issue = &thread.issue;
 
     println!("\nIssue #{}: {}", issue.number, issue.title);
     if let Some(body) = &issue.body {
         println!("Description:\n{}\n", body);
     }
+    if !thread.discussion.is_empty() {
+        println!("Comments: {}\n", thread.discussion.len());
+    }
 
     // Define the temporary directory path
     let temp_dir = env::temp_dir().join(format!("solver_{}", cli.issue));
@@ -90,45 +109,29 @@ async fn main() -> Result<()> {
     // Create context
     let ctx = RepoContext::new(temp_dir.clone(), api_key, Some(github_token));
 
-    // Clone the repository
+    // Clone the repository, with the token in case it's private
     let repo_url = format!("https://github.com/{}/{}", owner, repo_name);
-    let _repo = clone_repository(&repo_url, &ctx.temp_dir)?;
+    let _repo = clone_repository_with_token(&repo_url, &ctx.temp_dir, ctx.github_token.as_deref())?;
+    // The branch starts here rather than at the default branch's tip, which
+    // may move during the run, so the commit only carries the model's changes
+    let base_sha = head_commit_sha(&ctx.temp_dir)?;
 
     // Generate repository map
     print_colored("\nGenerating repository map...\n", Color::Blue)?;
     let map = generate_repo_map(&ctx.temp_dir);
     println!("\nRepository map generated ({} chars)", map.len());
 
-    // Create a new branch for the solution (if in live mode)
-    let branch_name = format!("solver/issue-{}", cli.issue);
-    if cli.live {
-        print_colored(
-            &format!("\nCreating branch '{}'...\n", branch_name),
-            Color::Blue,
-        )?;
-        // TODO: Implement branch creation
+    // Run the tests so the plan can account for what's already failing
+    print_colored("\nRunning cargo test...\n", Color::Blue)?;
+    let test_report = run_cargo_tests(&ctx.temp_dir).await?;
+    if test_report.success() {
+        println!("{}", test_report.summary());
     } else {
-        print_colored(
-            &format!("\n[DRY RUN] Would create branch '{}'\n", branch_name),
-            Color::Yellow,
-        )?;
+        print_colored(&format!("{}\n", test_report.for_prompt()), Color::Red)?;
     }
 
     // Analyze issue and generate implementation plan
-    let plan_prompt = format!(
-        "You are a Rust development expert. Analyze this GitHub issue and repository map to create an implementation plan.\n\n\
-        Issue #{}: {}\n{}\n\nRepository map:\n{}\n\n\
-        Create a detailed implementation plan including:\n\
-        1. Files that need to be created or modified\n\
-        2. Key functionality to implement\n\
-        3. Required dependencies or imports\n\
-        4. Testing strategy\n\
-        Be specific and focus on practical implementation details.",
-        issue.number,
-        issue.title,
-        issue.body.as_deref().unwrap_or("No description provided"),
-        map
-    );
+    let plan_prompt = plan_prompt(&thread, &map, Some(&test_report));
 
     print_colored("\nGenerating Implementation Plan:\n", Color::Yellow)?;
     println!(
@@ -136,10 +139,17 @@ async fn main() -> Result<()> {
         plan_prompt.len()
     );
 
+    let run_id = Uuid::new_v4();
+    let mut run_usage = Usage::default();
     let mut implementation_plan = String::new();
     let mut in_reasoning = true;
-    let mut stream = deepseek_service
-        .chat_stream(plan_prompt.clone(), true)
+    // Plans should stay close to the issue and repository map
+    let plan_options = GenerationOptions {
+        temperature: Some(0.3),
+        ..GenerationOptions::reasoner()
+    };
+    let mut stream = model
+        .chat_stream(plan_prompt.clone(), plan_options.clone())
         .await;
 
     println!("Waiting for DeepSeek response...");
@@ -163,6 +173,12 @@ async fn main() -> Result<()> {
                     implementation_plan.push_str(&c);
                     let _ = stdout().flush();
                 }
+                StreamUpdate::Usage(usage) => {
+                    run_usage += &usage;
+                }
+                StreamUpdate::Error(e) => {
+                    let _ = print_colored(&format!("\nDeepSeek API error: {}\n", e), Color::Red);
+                }
                 StreamUpdate::Done => {
                     println!("\nDeepSeek response complete");
                     break;
@@ -187,8 +203,16 @@ async fn main() -> Result<()> {
         print_colored("\nWARNING: No implementation plan generated!\n", Color::Red)?;
         print_colored("\nTrying non-streaming API...\n", Color::Yellow)?;
 
-        match deepseek_service.chat(plan_prompt, true).await {
-            Ok((content, reasoning)) => {
+        match model.chat(plan_prompt, plan_options).await {
+            Ok(ChatCompletion {
+                content,
+                reasoning,
+                usage,
+                ..
+            }) => {
+                if let Some(usage) = &usage {
+                    run_usage += usage;
+                }
                 if let Some(r) = reasoning {
                     print_colored("\nReasoning:\n", Color::Yellow)?;
                     println!("{}", r);
@@ -224,33 +248,139 @@ async fn main() -> Result<()> {
         println!("{}", implementation_plan);
     }
 
-    // TODO: Generate solution
     print_colored("\nGenerating solution...\n", Color::Blue)?;
-    // Implementation will go here
-
-    // TODO: Track file modifications
-    let modified_files: Vec<String> = Vec::new();
-
+    let solution = implement_plan(
+        model.clone(),
+        &thread.for_prompt(),
+        &implementation_plan,
+        &ctx.temp_dir,
+    )
+    .await?;
+    run_usage += &solution.usage;
+    println!("{}", solution.content);
+    let modified_files = changed_files(&ctx.temp_dir)?;
+
+    // The branch is only made once there is something to commit on it
+    let branch_name = format!("solver/issue-{}", cli.issue);
     if cli.live {
-        print_colored("\nCreating pull request...\n", Color::Blue)?;
-        // TODO: Implement PR creation
+        if modified_files.is_empty() {
+            print_colored(
+                "\nNo files were changed, so there is nothing to open a pull request for\n",
+                Color::Yellow,
+            )?;
+        } else {
+            print_colored(
+                &format!("\nCreating branch '{}'...\n", branch_name),
+                Color::Blue,
+            )?;
+            let base_branch = github_service
+                .get_repository(owner, repo_name)
+                .await?
+                .default_branch;
+            github_service
+                .set_branch(owner, repo_name, &branch_name, &base_sha)
+                .await?;
+
+            print_colored("\nCommitting changes...\n", Color::Blue)?;
+            let title = format!("Fix #{}: {}", issue.number, issue.title);
+            github_service
+                .commit_files(owner, repo_name, &branch_name, &title, &modified_files)
+                .await?;
+
+            print_colored("\nCreating pull request...\n", Color::Blue)?;
+            let created = github_service
+                .create_pull_request(
+                    owner,
+                    repo_name,
+                    &NewPullRequest {
+                        title,
+                        head: branch_name.clone(),
+                        base: base_branch,
+                        body: format!("Fixes #{}\n\n{}", issue.number, solution.content),
+                        draft: false,
+                    },
+                )
+                .await;
+            match created {
+                Ok(pull_request) => {
+                    print_colored(&format!("Opened {}\n", pull_request.html_url), Color::Green)?
+                }
+                // An earlier run's pull request now shows the new commit
+                Err(e)
+                    if e.downcast_ref::<GitHubApiError>().is_some_and(|e| {
+                        e.message
+                            .as_deref()
+                            .is_some_and(|message| message.contains("already exists"))
+                    }) =>
+                {
+                    print_colored(
+                        &format!("Updated the open pull request for '{}'\n", branch_name),
+                        Color::Green,
+                    )?
+                }
+                Err(e) => return Err(e),
+            }
+        }
     } else {
+        print_colored(
+            &format!(
+                "\n[DRY RUN] Would commit to branch '{}' from {}\n",
+                branch_name, base_sha
+            ),
+            Color::Yellow,
+        )?;
         print_colored(
             "\n[DRY RUN] Summary of changes that would be made:\n",
             Color::Yellow,
         )?;
         if modified_files.is_empty() {
-            println!("No files modified yet");
+            println!("No files modified");
         } else {
             for file in &modified_files {
-                println!("- Would modify: {}", file);
+                match file.content {
+                    Some(_) => println!("- Would modify: {}", file.path),
+                    None => println!("- Would delete: {}", file.path),
+                }
             }
         }
     }
 
+    print_colored("\nToken usage:\n", Color::Blue)?;
+    println!(
+        "Run {}: {} prompt ({} cached), {} completion, {} reasoning",
+        run_id,
+        run_usage.prompt_tokens,
+        run_usage.prompt_cache_hit_tokens,
+        run_usage.completion_tokens,
+        run_usage.reasoning_tokens()
+    );
+    if let Err(e) = record_run_usage(run_id, &run_usage).await {
+        print_colored(
+            &format!("Failed to record token usage: {}\n", e),
+            Color::Red,
+        )?;
+    }
+
     // Clean up at the end
     cleanup_temp_dir(&temp_dir);
     println!("Temporary directory removed.");
 
     Ok(())
 }
+
+/// Adds the run's usage to the token ledger when `DATABASE_URL` is set.
+async fn record_run_usage(run_id: Uuid, usage: &Usage) -> Result<()> {
+    let Ok(database_url) = env::var("DATABASE_URL") else {
+        return Ok(());
+    };
+
+    let chat_db = ChatDatabase::new(PgPool::connect(&database_url).await?);
+    chat_db
+        .record_usage(CreateUsageRequest {
+            user_id: None,
+            conversation_id: run_id,
+            source: "solver".to_string(),
+            usage: usage.clone(),
+        })
+        .await
+}
@@ -1,3 +1,4 @@
+use clap::{Parser, Subcommand};
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -9,20 +10,42 @@ pub mod repomap;
 pub mod routes;
 pub mod server;
 
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Serve the built-in tools to MCP clients over stdin and stdout
+    Mcp,
+}
+
 #[tokio::main]
 async fn main() {
     // Load .env file
     dotenvy::dotenv().ok();
+    let cli = Cli::parse();
 
-    // Initialize tracing
+    // Initialize tracing, on stderr since stdout carries the MCP protocol
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "openagents=debug,tower_http=debug".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
+    if let Some(Commands::Mcp) = cli.command {
+        if let Err(e) = server::config::configure_mcp_server().serve_stdio().await {
+            eprintln!("MCP server failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Get port from environment variable or use default
     let port = std::env::var("PORT")
         .ok()
@@ -32,8 +55,9 @@ async fn main() {
     let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
     let address = format!("{}:{}", host, port);
 
-    // Configure the application
-    let app = server::config::configure_app();
+    // Configure the application, with the tools of the MCP servers in MCP_CONFIG
+    let mcp_tools = server::tools::mcp_tools_from_env().await;
+    let app = server::config::configure_app_with_tools(mcp_tools);
 
     // Start the server
     let listener = tokio::net::TcpListener::bind(&address).await.unwrap();
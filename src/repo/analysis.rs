@@ -1,42 +1,71 @@
-use crate::server::services::deepseek::DeepSeekService;
+use super::TestReport;
+use crate::server::services::agent::{AgentLoop, AgentRun};
+use crate::server::services::deepseek::{ChatMessage, GenerationOptions};
+use crate::server::services::github_issue::IssueThread;
+use crate::server::services::provider::ChatProvider;
+use crate::server::tools::{create_workspace_tools, ToolContext, Workspace};
 use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(serde::Deserialize)]
-struct FileRequest {
-    path: String,
+/// Model turns the agent may take while implementing a plan.
+pub const IMPLEMENT_MAX_STEPS: usize = 30;
+/// How long the agent may spend implementing a plan.
+pub const IMPLEMENT_TIME_BUDGET: Duration = Duration::from_secs(10 * 60);
+
+/// Asks for an implementation plan for the issue in `thread`, taking the
+/// repository map and, when the tests were run, their results into account.
+pub fn plan_prompt(thread: &IssueThread, map: &str, test_report: Option<&TestReport>) -> String {
+    let test_results = test_report
+        .map(|report| format!("Current test results:\n{}\n\n", report.for_prompt()))
+        .unwrap_or_default();
+    format!(
+        "You are a Rust development expert. Analyze this GitHub issue and repository map to create an implementation plan.\n\n\
+        {}\n\nRepository map:\n{}\n\n{}\
+        Create a detailed implementation plan including:\n\
+        1. Files that need to be created or modified\n\
+        2. Key functionality to implement\n\
+        3. Required dependencies or imports\n\
+        4. Testing strategy\n\
+        Be specific and focus on practical implementation details.",
+        thread.for_prompt(),
+        map,
+        test_results
+    )
+}
+
+/// Asks which tests should cover the issue in `thread`.
+pub fn test_suggestion_prompt(thread: &IssueThread, map: &str) -> String {
+    format!(
+        "You are a Rust testing expert. Suggest tests for this GitHub issue.\n\n\
+        {}\n\nRepository map:\n{}\n\n\
+        For each test, say which file it belongs in, what it sets up, what it \
+        asserts and which edge case it covers. Follow the layout of the existing \
+        tests and include the test code.",
+        thread.for_prompt(),
+        map
+    )
 }
 
+/// Lets the model explore the cloned repository with the read-only workspace
+/// tools, then suggest changes for `issue`.
 pub async fn analyze_repository(
-    service: &DeepSeekService,
+    service: Arc<dyn ChatProvider>,
     map: &str,
-    test_output: &str,
+    test_report: &TestReport,
     issue: &crate::server::services::github_issue::GitHubIssue,
     repo_path: &std::path::Path,
 ) -> Result<String> {
-    // First, get the AI to identify which files we should look at
-    let prompt = format!(
-        "You are analyzing a Rust repository. Based on this repository map and GitHub issue, \
-        identify which files we should analyze in detail to help implement the requested changes.\n\n\
-        Repository Map:\n{}\n\nGitHub Issue:\nTitle: {}\nBody:\n{}\n\n\
-        Respond with a JSON object containing a 'path' field with the most relevant file path to analyze.",
-        map,
-        issue.title,
-        issue.body.as_deref().unwrap_or("No description provided")
-    );
-
-    let (file_response, _) = service.chat(prompt, false).await?;
-    let file_request: FileRequest = serde_json::from_str(file_response.trim())?;
+    let workspace = Arc::new(Workspace::new(repo_path)?);
+    let tools = Arc::new(create_workspace_tools(workspace, false));
 
-    // Read the identified file
-    let file_content = std::fs::read_to_string(repo_path.join(&file_request.path))?;
-
-    // Now analyze the file content
-    let analysis_prompt = format!(
+    let prompt = format!(
         "You are analyzing a Rust repository to implement changes requested in a GitHub issue.\n\n\
         Issue:\nTitle: {}\nBody:\n{}\n\n\
-        File content ({}):\n{}\n\n\
-        Test output:\n{}\n\n\
-        Analyze this code and suggest specific changes to implement the requested functionality. \
+        Repository Map:\n{}\n\n\
+        Test results:\n{}\n\n\
+        Use the list_dir, grep and read_file tools to read the code relevant to the issue. \
+        Then suggest specific changes to implement the requested functionality. \
         Consider:\n\
         1. Required modifications to existing functions\n\
         2. New functions or structs needed\n\
@@ -46,13 +75,58 @@ pub async fn analyze_repository(
         Be specific and provide code examples where appropriate.",
         issue.title,
         issue.body.as_deref().unwrap_or("No description provided"),
-        file_request.path,
-        file_content,
-        test_output
+        map,
+        test_report.for_prompt()
+    );
+
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        tool_call_id: None,
+        tool_calls: None,
+    }];
+    let run = AgentLoop::new(service, tools)
+        .with_options(GenerationOptions::deterministic())
+        .run(messages, &ToolContext::default())
+        .await?;
+    Ok(run.content)
+}
+
+/// Lets the model carry out `plan` in the cloned repository with the
+/// writable workspace tools. The run's answer summarizes what it changed;
+/// the changes are left in the working tree, see [`super::changed_files`].
+pub async fn implement_plan(
+    service: Arc<dyn ChatProvider>,
+    issue: &str,
+    plan: &str,
+    repo_path: &std::path::Path,
+) -> Result<AgentRun> {
+    let workspace = Arc::new(Workspace::new(repo_path)?);
+    let tools = Arc::new(create_workspace_tools(workspace, true));
+
+    let prompt = format!(
+        "You are implementing a fix for a GitHub issue in a Rust repository.\n\n\
+        {}\n\n\
+        Implementation plan:\n{}\n\n\
+        Use the list_dir, grep and read_file tools to read the code you need, \
+        then make the changes with write_file. Keep to the plan and to the style \
+        of the surrounding code. When you are done, reply with a short summary of \
+        the changes for the pull request description.",
+        issue, plan
     );
 
-    let (analysis, _) = service.chat(analysis_prompt, false).await?;
-    Ok(analysis)
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: prompt,
+        tool_call_id: None,
+        tool_calls: None,
+    }];
+    AgentLoop::new(service, tools)
+        .with_options(GenerationOptions::deterministic())
+        .with_max_steps(IMPLEMENT_MAX_STEPS)
+        .with_time_budget(IMPLEMENT_TIME_BUDGET)
+        .run(messages, &ToolContext::default())
+        .await
 }
 
 pub async fn post_analysis(
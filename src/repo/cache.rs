@@ -0,0 +1,240 @@
+use super::git::clone_repository;
+use anyhow::{anyhow, bail, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+/// How long a clone is reused before it is cloned again.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+/// Clones kept before the least recently used ones are removed.
+pub const DEFAULT_MAX_CLONES: usize = 20;
+/// Hosts `https://` URLs may be cloned from unless others are allowed.
+pub const DEFAULT_CLONE_HOSTS: [&str; 1] = ["github.com"];
+
+/// Clones of remote repositories, kept under one directory and reused until
+/// they are older than the TTL. Past `max_clones`, the least recently checked
+/// out clones are removed.
+#[derive(Debug)]
+pub struct RepoCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_clones: usize,
+    /// Hosts `https://` URLs may point at, in lowercase. Anything else could
+    /// have the server fetch from internal hosts or serve huge repositories.
+    clone_hosts: Vec<String>,
+    allow_file_urls: bool,
+    /// Repositories on this machine, by name, used as they are.
+    local_repos: BTreeMap<String, PathBuf>,
+    /// When each clone was made, behind a lock per repository so concurrent
+    /// requests for it clone once.
+    clones: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<Instant>>>>>,
+    /// When each clone on disk was last checked out.
+    used: Mutex<HashMap<String, Instant>>,
+}
+
+impl RepoCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: DEFAULT_CACHE_TTL,
+            max_clones: DEFAULT_MAX_CLONES,
+            clone_hosts: DEFAULT_CLONE_HOSTS.map(String::from).to_vec(),
+            allow_file_urls: false,
+            local_repos: BTreeMap::new(),
+            clones: Mutex::new(HashMap::new()),
+            used: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_max_clones(mut self, max_clones: usize) -> Self {
+        self.max_clones = max_clones.max(1);
+        self
+    }
+
+    /// Accepts `https://` URLs on `host` too, e.g. a GitHub Enterprise Server.
+    pub fn with_clone_host(mut self, host: impl Into<String>) -> Self {
+        self.clone_hosts
+            .push(host.into().trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Accepts `file://` URLs, which would otherwise let callers clone any
+    /// repository on the server's file system. Meant for tests.
+    pub fn allow_file_urls(mut self) -> Self {
+        self.allow_file_urls = true;
+        self
+    }
+
+    /// Makes the repository at `path` available as `name`, without cloning.
+    pub fn with_local_repo(mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.local_repos.insert(name.into(), path.into());
+        self
+    }
+
+    pub fn local_repos(&self) -> &BTreeMap<String, PathBuf> {
+        &self.local_repos
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// The clone URL for `repository`: an `https://` git URL on one of the
+    /// allowed hosts, or `owner/name` on GitHub.
+    pub fn clone_url(&self, repository: &str) -> Result<String> {
+        let repository = repository.trim();
+        if let Some(rest) = repository.strip_prefix("https://") {
+            let host = rest.split('/').next().unwrap_or_default();
+            if host.contains('@') || !self.clone_hosts.contains(&host.to_ascii_lowercase()) {
+                bail!(
+                    "Cloning from {} isn't allowed; use one of: {}",
+                    host,
+                    self.clone_hosts.join(", ")
+                );
+            }
+            return Ok(repository.trim_end_matches('/').to_string());
+        }
+        if self.allow_file_urls && repository.starts_with("file://") {
+            return Ok(repository.trim_end_matches('/').to_string());
+        }
+        match repository.split('/').collect::<Vec<_>>()[..] {
+            [owner, name]
+                if [owner, name].iter().all(|part| {
+                    !part.is_empty()
+                        && !part.starts_with('.')
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+                }) =>
+            {
+                Ok(format!("https://github.com/{}/{}", owner, name))
+            }
+            _ => bail!(
+                "Unsupported repository {:?}: use an https:// git URL or owner/name",
+                repository
+            ),
+        }
+    }
+
+    /// Returns the local repository named `repository`, or a fresh enough
+    /// clone of it, cloning it if needed.
+    pub async fn checkout(&self, repository: &str) -> Result<PathBuf> {
+        if let Some(path) = self.local_repos.get(repository) {
+            return Ok(path.clone());
+        }
+        let url = self.clone_url(repository)?;
+        let key = cache_key(&url);
+        let path = self.dir.join(&key);
+
+        let lock = self
+            .clones
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let mut cloned_at = lock.lock().await;
+        if cloned_at.is_some_and(|at| at.elapsed() < self.ttl) && path.is_dir() {
+            self.used.lock().unwrap().insert(key, Instant::now());
+            return Ok(path);
+        }
+
+        // Clone next to the old copy and swap, so a failed clone keeps nothing
+        // half-written
+        fs::create_dir_all(&self.dir)?;
+        let staging = Staging(Some(self.dir.join(format!(".{}-{}", key, Uuid::new_v4()))));
+        let clone_url = url.clone();
+        // The staging directory goes with the clone, which runs on even when
+        // this future is dropped, e.g. by a tool timeout, and is removed once
+        // it finishes unless it was kept
+        let mut staging = tokio::task::spawn_blocking(move || {
+            clone_repository(&clone_url, staging.path()).map(|_| staging)
+        })
+        .await
+        .map_err(|e| anyhow!("Clone of {} panicked: {}", url, e))??;
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::rename(staging.path(), &path)?;
+        staging.keep();
+        *cloned_at = Some(Instant::now());
+        drop(cloned_at);
+        self.used
+            .lock()
+            .unwrap()
+            .insert(key.clone(), Instant::now());
+        self.evict(&key);
+        Ok(path)
+    }
+
+    /// Removes the least recently used clones past `max_clones`, other than
+    /// `keep`. Clones being made or refreshed right now are left alone.
+    fn evict(&self, keep: &str) {
+        let mut used = self.used.lock().unwrap();
+        let mut candidates: Vec<(String, Instant)> = used
+            .iter()
+            .filter(|(key, _)| key.as_str() != keep)
+            .map(|(key, at)| (key.clone(), *at))
+            .collect();
+        candidates.sort_by_key(|(_, at)| *at);
+        for (key, _) in candidates {
+            if used.len() <= self.max_clones {
+                break;
+            }
+            let Some(lock) = self.clones.lock().unwrap().get(&key).cloned() else {
+                continue;
+            };
+            let Ok(mut cloned_at) = lock.try_lock() else {
+                continue;
+            };
+            if let Err(e) = fs::remove_dir_all(self.dir.join(&key)) {
+                warn!("Failed to remove the clone {}: {}", key, e);
+            }
+            *cloned_at = None;
+            used.remove(&key);
+        }
+    }
+}
+
+/// A directory a clone is made in, removed when dropped unless kept.
+struct Staging(Option<PathBuf>);
+
+impl Staging {
+    fn path(&self) -> &PathBuf {
+        self.0.as_ref().expect("staging directory already kept")
+    }
+
+    fn keep(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            let _ = fs::remove_dir_all(path);
+        }
+    }
+}
+
+/// Directory name for the clone of `url`.
+fn cache_key(url: &str) -> String {
+    let name = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .trim_end_matches(".git");
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
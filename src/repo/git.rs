@@ -1,7 +1,9 @@
+use crate::server::services::github_types::FileChange;
 use anyhow::Result;
-use git2::Repository;
+use git2::build::RepoBuilder;
+use git2::{Cred, FetchOptions, FileMode, RemoteCallbacks, Repository, Status, StatusOptions};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn cleanup_temp_dir(temp_dir: &PathBuf) {
     if temp_dir.exists() {
@@ -13,10 +15,91 @@ pub fn cleanup_temp_dir(temp_dir: &PathBuf) {
     }
 }
 
+/// Clones `url` into `temp_dir`, reporting progress on stderr so stdout stays
+/// free for output such as the MCP protocol.
 pub fn clone_repository(url: &str, temp_dir: &PathBuf) -> Result<Repository> {
-    println!("Cloning repository: {}", url);
-    let repo = Repository::clone(url, temp_dir)
+    clone_repository_with_token(url, temp_dir, None)
+}
+
+/// Like [`clone_repository`], answering the server's request for credentials
+/// with a GitHub `token`, so private repositories can be cloned. The token
+/// isn't stored in the clone.
+pub fn clone_repository_with_token(
+    url: &str,
+    temp_dir: &PathBuf,
+    token: Option<&str>,
+) -> Result<Repository> {
+    eprintln!("Cloning repository: {}", url);
+    let mut callbacks = RemoteCallbacks::new();
+    if let Some(token) = token {
+        callbacks.credentials(move |_, _, _| Cred::userpass_plaintext("x-access-token", token));
+    }
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    let repo = RepoBuilder::new()
+        .fetch_options(fetch_options)
+        .clone(url, temp_dir)
         .map_err(|e| anyhow::anyhow!("Failed to clone repository: {}", e))?;
-    println!("Repository cloned successfully into: {:?}", temp_dir);
+    eprintln!("Repository cloned successfully into: {:?}", temp_dir);
     Ok(repo)
 }
+
+/// The sha of the commit checked out in `repo_path`.
+pub fn head_commit_sha(repo_path: &Path) -> Result<String> {
+    let repo = Repository::open(repo_path)?;
+    let head = repo.head()?.peel_to_commit()?;
+    Ok(head.id().to_string())
+}
+
+/// The files added, modified or deleted in the working tree of `repo_path`
+/// since its last commit, leaving out ignored files, with their git file
+/// modes.
+pub fn changed_files(repo_path: &Path) -> Result<Vec<FileChange>> {
+    let repo = Repository::open(repo_path)?;
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+
+    let mut changes = Vec::new();
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let Some(path) = entry.path() else {
+            continue;
+        };
+        let deleted = entry
+            .status()
+            .intersects(Status::WT_DELETED | Status::INDEX_DELETED);
+        // The working tree side wins, as that is what gets committed
+        let delta = entry.index_to_workdir().or_else(|| entry.head_to_index());
+        let mode = delta
+            .map(|delta| {
+                let file = if deleted {
+                    delta.old_file()
+                } else {
+                    delta.new_file()
+                };
+                file.mode()
+            })
+            .unwrap_or(FileMode::Blob);
+        let full_path = repo_path.join(path);
+        let content = if deleted {
+            None
+        } else if mode == FileMode::Link {
+            Some(
+                fs::read_link(&full_path)?
+                    .into_os_string()
+                    .into_encoded_bytes(),
+            )
+        } else {
+            Some(fs::read(&full_path)?)
+        };
+        changes.push(FileChange {
+            path: path.to_string(),
+            mode: mode.into(),
+            content,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
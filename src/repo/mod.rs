@@ -1,9 +1,11 @@
 pub mod analysis;
+pub mod cache;
 pub mod git;
 pub mod test;
 pub mod types;
 
 pub use analysis::*;
+pub use cache::*;
 pub use git::*;
 pub use test::*;
 pub use types::*;
@@ -1,47 +1,360 @@
-use anyhow::Result;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::process::Command;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::time::{timeout_at, Instant as Deadline};
 
-pub async fn run_cargo_tests(repo_path: &Path) -> Result<String> {
-    println!("\nRunning cargo test...");
+/// How long building and running a repository's tests may take.
+pub const DEFAULT_TEST_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Longest failure message kept per test, so a noisy test can't fill a prompt.
+const MAX_FAILURE_CHARS: usize = 2_000;
 
-    let mut output = String::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+    /// Still running when the run timed out.
+    TimedOut,
+}
+
+impl fmt::Display for TestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            TestStatus::Passed => "passed",
+            TestStatus::Failed => "failed",
+            TestStatus::Ignored => "ignored",
+            TestStatus::TimedOut => "timed out",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    /// The test target the test is in, e.g. the library or an integration test file.
+    pub target: String,
+    pub name: String,
+    pub status: TestStatus,
+    pub duration: Option<Duration>,
+    /// What the test printed, including the panic message, when it failed.
+    pub failure: Option<String>,
+}
+
+/// A compiler message from building the tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// `error` or `warning`.
+    pub level: String,
+    /// The message as rustc prints it, with the source snippet.
+    pub rendered: String,
+}
+
+/// The outcome of building and running a repository's tests.
+#[derive(Debug, Clone, Default)]
+pub struct TestReport {
+    pub tests: Vec<TestCase>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Exit code of the build when it failed, otherwise of the first test
+    /// binary that failed, otherwise 0. `None` when the run was killed.
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration: Duration,
+}
+
+impl TestReport {
+    pub fn success(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
 
-    // Run cargo test and capture stdout
-    let mut cmd = Command::new("cargo")
-        .arg("test")
-        .current_dir(repo_path)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()?;
+    pub fn count(&self, status: TestStatus) -> usize {
+        self.tests
+            .iter()
+            .filter(|test| test.status == status)
+            .count()
+    }
+
+    /// Tests that failed or were cut off by the timeout.
+    pub fn failures(&self) -> impl Iterator<Item = &TestCase> {
+        self.tests
+            .iter()
+            .filter(|test| matches!(test.status, TestStatus::Failed | TestStatus::TimedOut))
+    }
 
-    // Handle stdout
-    if let Some(stdout) = cmd.stdout.take() {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines().map_while(Result::ok) {
-            println!("{}", line);
-            output.push_str(&line);
-            output.push('\n');
+    pub fn compile_errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.level == "error")
+    }
+
+    /// One line, e.g. `12 passed, 1 failed, 2 ignored in 3.2s`.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} passed, {} failed, {} ignored",
+            self.count(TestStatus::Passed),
+            self.count(TestStatus::Failed),
+            self.count(TestStatus::Ignored)
+        );
+        let errors = self.compile_errors().count();
+        if errors > 0 {
+            summary.push_str(&format!(", {} compile error(s)", errors));
+        }
+        summary.push_str(&format!(" in {:.1}s", self.duration.as_secs_f64()));
+        if self.timed_out {
+            summary.push_str(" (timed out)");
         }
+        summary
     }
 
-    // Handle stderr
-    if let Some(stderr) = cmd.stderr.take() {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines().map_while(Result::ok) {
-            eprintln!("{}", line);
-            output.push_str(&line);
-            output.push('\n');
+    /// What a model needs to know about the run: the summary, then only the
+    /// compile errors and failing tests.
+    pub fn for_prompt(&self) -> String {
+        let mut prompt = self.summary();
+        for error in self.compile_errors() {
+            prompt.push_str("\n\n");
+            prompt.push_str(error.rendered.trim_end());
         }
+        for test in self.failures() {
+            prompt.push_str(&format!(
+                "\n\n{} {} ({})",
+                test.status, test.name, test.target
+            ));
+            if let Some(failure) = &test.failure {
+                prompt.push('\n');
+                prompt.push_str(failure.trim());
+            }
+        }
+        prompt
     }
+}
+
+/// Builds and runs the tests of the crate or workspace at `repo_path`, giving
+/// up after [`DEFAULT_TEST_TIMEOUT`].
+pub async fn run_cargo_tests(repo_path: &Path) -> Result<TestReport> {
+    run_cargo_tests_with_timeout(repo_path, DEFAULT_TEST_TIMEOUT).await
+}
 
-    // Wait for the command to complete
-    let status = cmd.wait()?;
+/// Like [`run_cargo_tests`] with a custom timeout. Doc tests aren't run.
+///
+/// The tests are built with `cargo test --no-run`, then each test binary runs
+/// with libtest's JSON output for per-test results and timings.
+pub async fn run_cargo_tests_with_timeout(
+    repo_path: &Path,
+    timeout: Duration,
+) -> Result<TestReport> {
+    let started = Instant::now();
+    let deadline = Deadline::now() + timeout;
+    let mut report = TestReport::default();
 
-    if !status.success() {
-        println!("\nNote: Some tests failed, but continuing with analysis...");
+    let mut build = Command::new("cargo");
+    build
+        .args(["test", "--no-run", "--message-format", "json"])
+        .current_dir(repo_path);
+    let build = run(build, deadline).await?;
+    let mut binaries = Vec::new();
+    for line in &build.stdout {
+        let Ok(message) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        match message["reason"].as_str() {
+            Some("compiler-message") => {
+                if let Some(rendered) = message["message"]["rendered"].as_str() {
+                    report.diagnostics.push(Diagnostic {
+                        level: message["message"]["level"]
+                            .as_str()
+                            .unwrap_or("error")
+                            .to_string(),
+                        rendered: rendered.to_string(),
+                    });
+                }
+            }
+            Some("compiler-artifact") if message["profile"]["test"] == true => {
+                if let Some(executable) = message["executable"].as_str() {
+                    binaries.push(TestBinary {
+                        target: message["target"]["name"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        executable: PathBuf::from(executable),
+                        manifest_dir: message["manifest_path"]
+                            .as_str()
+                            .and_then(|path| Path::new(path).parent())
+                            .map_or_else(|| repo_path.to_path_buf(), Path::to_path_buf),
+                    });
+                }
+            }
+            _ => {}
+        }
     }
+    if build.timed_out || build.exit_code != Some(0) {
+        // Errors cargo reports itself, e.g. a broken manifest, only go to stderr
+        if report.compile_errors().next().is_none() && !build.timed_out {
+            report.diagnostics.push(Diagnostic {
+                level: "error".to_string(),
+                rendered: build.stderr.join("\n"),
+            });
+        }
+        report.exit_code = build.exit_code;
+        report.timed_out = build.timed_out;
+        report.duration = started.elapsed();
+        return Ok(report);
+    }
+
+    report.exit_code = Some(0);
+    for binary in binaries {
+        let mut command = Command::new(&binary.executable);
+        command
+            .args([
+                "-Z",
+                "unstable-options",
+                "--format",
+                "json",
+                "--report-time",
+            ])
+            // Lets stable libtest accept the JSON format
+            .env("RUSTC_BOOTSTRAP", "1")
+            .env("RUST_BACKTRACE", "0")
+            .env("CARGO_MANIFEST_DIR", &binary.manifest_dir)
+            .current_dir(&binary.manifest_dir);
+        let output = run(command, deadline).await?;
+        let parsed = parse_test_events(&binary.target, &output.stdout, output.timed_out);
+        report.tests.extend(parsed);
+        if report.exit_code == Some(0) {
+            report.exit_code = output.exit_code;
+        }
+        if output.timed_out {
+            report.timed_out = true;
+            break;
+        }
+    }
+    report.duration = started.elapsed();
+    Ok(report)
+}
 
-    Ok(output)
+struct TestBinary {
+    target: String,
+    executable: PathBuf,
+    manifest_dir: PathBuf,
+}
+
+/// Turns libtest's JSON events into test cases. Tests that started but didn't
+/// finish are marked timed out when the binary was killed.
+fn parse_test_events(target: &str, lines: &[String], timed_out: bool) -> Vec<TestCase> {
+    let mut tests = Vec::new();
+    let mut running = Vec::new();
+    let mut finished = HashSet::new();
+    for line in lines {
+        let Ok(event) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if event["type"] != "test" {
+            continue;
+        }
+        let Some(name) = event["name"].as_str() else {
+            continue;
+        };
+        let status = match event["event"].as_str() {
+            Some("started") => {
+                running.push(name.to_string());
+                continue;
+            }
+            Some("ok") => TestStatus::Passed,
+            Some("failed") => TestStatus::Failed,
+            Some("ignored") => TestStatus::Ignored,
+            _ => continue,
+        };
+        finished.insert(name.to_string());
+        tests.push(TestCase {
+            target: target.to_string(),
+            name: name.to_string(),
+            status,
+            duration: event["exec_time"].as_f64().map(Duration::from_secs_f64),
+            failure: (status == TestStatus::Failed).then(|| {
+                let output = event["stdout"].as_str().unwrap_or_default().trim();
+                truncate(output, MAX_FAILURE_CHARS)
+            }),
+        });
+    }
+    if timed_out {
+        for name in running {
+            if !finished.contains(&name) {
+                tests.push(TestCase {
+                    target: target.to_string(),
+                    name,
+                    status: TestStatus::TimedOut,
+                    duration: None,
+                    failure: None,
+                });
+            }
+        }
+    }
+    tests
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}\n[truncated]", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+struct CommandOutput {
+    stdout: Vec<String>,
+    stderr: Vec<String>,
+    exit_code: Option<i32>,
+    timed_out: bool,
+}
+
+/// Runs `command` until it exits or `deadline` passes, reading stdout and
+/// stderr at the same time so neither pipe can fill up and block it.
+async fn run(mut command: Command, deadline: Deadline) -> Result<CommandOutput> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to run {:?}: {}", command.as_std().get_program(), e))?;
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let (stdout_done, stderr_done) = tokio::join!(
+        timeout_at(
+            deadline,
+            read_lines(child.stdout.take().expect("stdout is piped"), &mut stdout)
+        ),
+        timeout_at(
+            deadline,
+            read_lines(child.stderr.take().expect("stderr is piped"), &mut stderr)
+        )
+    );
+    let status = match (stdout_done, stderr_done) {
+        (Ok(()), Ok(())) => timeout_at(deadline, child.wait()).await.ok(),
+        _ => None,
+    };
+    let timed_out = status.is_none();
+    if timed_out {
+        child.kill().await.ok();
+    }
+    Ok(CommandOutput {
+        stdout,
+        stderr,
+        exit_code: match status {
+            Some(status) => status?.code(),
+            None => None,
+        },
+        timed_out,
+    })
+}
+
+/// Appends lines to `collected` as they arrive, so they are kept if the read
+/// is cut off.
+async fn read_lines(pipe: impl AsyncRead + Unpin, collected: &mut Vec<String>) {
+    let mut lines = BufReader::new(pipe).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        collected.push(line);
+    }
 }
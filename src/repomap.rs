@@ -1,6 +1,6 @@
 use lazy_static::lazy_static;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tree_sitter::{Parser, Query, QueryCursor};
 
 lazy_static! {
@@ -12,6 +12,16 @@ pub fn generate_repo_map(repo_path: &Path) -> String {
 }
 
 pub fn generate_repo_map_with_blacklist(repo_path: &Path, blacklist: &[&str]) -> String {
+    generate_repo_map_for_paths(repo_path, &[repo_path.to_path_buf()], blacklist)
+}
+
+/// Maps only the files under `paths`, which are files or directories inside
+/// `repo_path`. Paths in the map stay relative to `repo_path`.
+pub fn generate_repo_map_for_paths(
+    repo_path: &Path,
+    paths: &[PathBuf],
+    blacklist: &[&str],
+) -> String {
     let mut parser = Parser::new();
     parser
         .set_language(*RUST_LANGUAGE)
@@ -34,7 +44,7 @@ pub fn generate_repo_map_with_blacklist(repo_path: &Path, blacklist: &[&str]) ->
 
     let mut cursor = QueryCursor::new();
 
-    walk_dir(repo_path, &mut |path| {
+    let mut visit = |path: &Path| {
         // Skip blacklisted paths
         if blacklist
             .iter()
@@ -162,17 +172,39 @@ pub fn generate_repo_map_with_blacklist(repo_path: &Path, blacklist: &[&str]) ->
             }
             _ => {}
         }
-    });
+    };
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(path, &mut visit);
+        } else {
+            visit(path);
+        }
+    }
 
     repo_map
 }
 
 fn walk_dir(dir: &Path, callback: &mut dyn FnMut(&Path)) {
     if dir.is_dir() {
-        for entry in fs::read_dir(dir).unwrap() {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            if path.is_dir() {
+        // Directories and entries that can't be read, e.g. for lack of
+        // permission or because they were removed meanwhile, are left out
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        // Sorted so the map is the same on every file system
+        let mut entries: Vec<_> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                Some((entry.path(), entry.file_type().ok()?))
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        for (path, file_type) in entries {
+            // Symlinks may lead out of the repository, or round in a loop
+            if file_type.is_symlink() {
+                continue;
+            }
+            if file_type.is_dir() {
                 walk_dir(&path, callback);
             } else {
                 callback(&path);
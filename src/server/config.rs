@@ -1,23 +1,38 @@
-use super::services::{deepseek::DeepSeekService, github_issue::GitHubService, RepomapService};
-use super::tools::create_tools;
+use super::handlers::{github_webhook, WebhookState};
+use super::mcp::McpServer;
+use super::services::{
+    github_issue::GitHubService,
+    github_webhook::{spawn_agent_worker, GitHubAgentRunner, WebhookConfig, JOB_QUEUE_CAPACITY},
+    provider::{provider_from_env, ChatProvider, CHAT_MODEL_ROLE, TOOL_MODEL_ROLE},
+    ChatDatabase, RepomapService,
+};
+use super::tools::{create_tools, Calculate, GetRepoMap, RunCargoTests, ToolLimits, ToolRegistry};
 use super::ws::transport::WebSocketState;
+use crate::repo::RepoCache;
 use crate::{routes, server};
 use axum::{
     routing::{get, post},
     Router,
 };
-use std::{env, sync::Arc};
+use sqlx::PgPool;
+use std::{env, path::PathBuf, sync::Arc};
+use tokio::sync::mpsc;
 use tower_http::services::ServeDir;
+use tracing::{info, warn};
 
 pub fn configure_app() -> Router {
+    configure_app_with_tools(ToolRegistry::new())
+}
+
+/// Like [`configure_app`], with `extra_tools` (e.g. from MCP servers) offered
+/// to the web chat alongside the built-in ones.
+pub fn configure_app_with_tools(extra_tools: ToolRegistry) -> Router {
     // Create shared services
-    let tool_model = Arc::new(DeepSeekService::new(
-        env::var("DEEPSEEK_API_KEY").expect("DEEPSEEK_API_KEY must be set"),
-    ));
+    let tool_model =
+        provider_from_env(TOOL_MODEL_ROLE).expect("Failed to configure the tool model");
 
-    let chat_model = Arc::new(DeepSeekService::new(
-        env::var("DEEPSEEK_API_KEY").expect("DEEPSEEK_API_KEY must be set"),
-    ));
+    let chat_model =
+        provider_from_env(CHAT_MODEL_ROLE).expect("Failed to configure the chat model");
 
     let github_service = Arc::new(
         GitHubService::new(Some(
@@ -26,11 +41,46 @@ pub fn configure_app() -> Router {
         .expect("Failed to create GitHub service"),
     );
 
+    let repos = Arc::new(repo_cache_from_env(RepoCache::new(repo_cache_dir())));
+
+    // Agents started by GitHub webhooks, when a webhook secret is configured.
+    // They get clones of their own: the repository map tool replaces stale
+    // clones, which would pull the checkout out from under a running job.
+    let webhooks = WebhookConfig::from_env().map(|config| {
+        webhook_routes(
+            config,
+            github_service.clone(),
+            chat_model.clone(),
+            Arc::new(repo_cache_from_env(RepoCache::new(
+                repo_cache_dir().join("webhooks"),
+            ))),
+        )
+    });
+
     // Create available tools
-    let tools = create_tools();
+    let mut tools = create_tools(github_service);
+    tools.register(GetRepoMap::new(repos));
+    for skipped in tools.merge(extra_tools) {
+        warn!("Tool {} is already defined", skipped);
+    }
 
-    // Create WebSocket state with services
-    let ws_state = WebSocketState::new(tool_model, chat_model, github_service.clone(), tools);
+    // Create WebSocket state with services, recording token usage when a database is configured
+    let ws_state = match env::var("DATABASE_URL")
+        .ok()
+        .map(|url| PgPool::connect_lazy(&url))
+    {
+        Some(Ok(pool)) => WebSocketState::with_chat_database(
+            tool_model,
+            chat_model,
+            tools,
+            Arc::new(ChatDatabase::new(pool)),
+        ),
+        Some(Err(e)) => {
+            warn!("Invalid DATABASE_URL, token usage won't be recorded: {}", e);
+            WebSocketState::new(tool_model, chat_model, tools)
+        }
+        None => WebSocketState::new(tool_model, chat_model, tools),
+    };
 
     // Initialize repomap service
     let aider_api_key = env::var("AIDER_API_KEY").unwrap_or_else(|_| "".to_string());
@@ -58,6 +108,13 @@ pub fn configure_app() -> Router {
     let app = app
         .route("/repomap/generate", post(routes::generate_repomap))
         .with_state(repomap_service);
+    let app = match webhooks {
+        Some(webhooks) => app.merge(webhooks),
+        None => {
+            info!("GITHUB_WEBHOOK_SECRET is not set, not serving /webhooks/github");
+            app
+        }
+    };
 
     // Static files
     app.nest_service("/assets", ServeDir::new("./assets").precompressed_gzip())
@@ -66,3 +123,81 @@ pub fn configure_app() -> Router {
             ServeDir::new("./templates").precompressed_gzip(),
         )
 }
+
+/// `POST /webhooks/github`, with a worker running the agent jobs that
+/// deliveries queue.
+fn webhook_routes(
+    config: WebhookConfig,
+    github_service: Arc<GitHubService>,
+    model: Arc<dyn ChatProvider>,
+    repos: Arc<RepoCache>,
+) -> Router {
+    let (jobs, queue) = mpsc::channel(JOB_QUEUE_CAPACITY);
+    spawn_agent_worker(
+        queue,
+        Arc::new(GitHubAgentRunner::new(github_service, model, repos)),
+    );
+    Router::new()
+        .route("/webhooks/github", post(github_webhook))
+        .with_state(WebhookState::new(config, jobs))
+}
+
+fn repo_cache_dir() -> PathBuf {
+    env::var("REPO_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir().join("openagents-repos"))
+}
+
+/// Adds the local repositories listed in `REPO_MAP_REPOS` to `cache`, keeps
+/// at most `REPO_CACHE_MAX_CLONES` clones and also clones from the hosts in
+/// `REPO_CLONE_HOSTS`.
+pub fn repo_cache_from_env(mut cache: RepoCache) -> RepoCache {
+    for host in env::var("REPO_CLONE_HOSTS").unwrap_or_default().split(',') {
+        if !host.trim().is_empty() {
+            cache = cache.with_clone_host(host);
+        }
+    }
+    if let Some(max_clones) = env::var("REPO_CACHE_MAX_CLONES")
+        .ok()
+        .and_then(|max| max.trim().parse().ok())
+    {
+        cache = cache.with_max_clones(max_clones);
+    }
+    for entry in env::var("REPO_MAP_REPOS").unwrap_or_default().split(',') {
+        match entry.trim().split_once('=') {
+            Some((name, path)) => cache = cache.with_local_repo(name.trim(), path.trim()),
+            None if entry.trim().is_empty() => {}
+            None => warn!("Ignoring REPO_MAP_REPOS entry without a path: {}", entry),
+        }
+    }
+    cache
+}
+
+/// The server behind `openagents mcp`: the GitHub tools when `GITHUB_TOKEN` is
+/// set, `calculate`, `get_repo_map` and `run_cargo_tests`, with the current
+/// directory available as the `workspace` repository.
+pub fn configure_mcp_server() -> McpServer {
+    let mut cache = RepoCache::new(repo_cache_dir());
+    match env::current_dir() {
+        Ok(dir) => cache = cache.with_local_repo("workspace", dir),
+        Err(e) => warn!("Not offering the current directory as a repository: {}", e),
+    }
+    let cache = Arc::new(repo_cache_from_env(cache));
+
+    let mut tools = match env::var("GITHUB_TOKEN") {
+        Ok(token) => create_tools(Arc::new(
+            GitHubService::new(Some(token)).expect("Failed to create GitHub service"),
+        )),
+        Err(_) => {
+            warn!("GITHUB_TOKEN is not set, leaving out the GitHub tools");
+            let mut tools = ToolRegistry::new();
+            tools.set_limits(ToolLimits::from_env()).register(Calculate);
+            tools
+        }
+    };
+    let repo_maps = GetRepoMap::new(cache.clone());
+    tools
+        .register(repo_maps.clone())
+        .register(RunCargoTests::new(cache));
+    McpServer::new(tools).with_repo_maps(repo_maps)
+}
@@ -1,4 +1,6 @@
 pub mod auth;
 pub mod user;
+pub mod webhook;
 
 pub use auth::{callback, login, logout, signup, AppState};
+pub use webhook::{github_webhook, WebhookState};
@@ -0,0 +1,110 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{info, warn};
+
+use crate::server::services::github_webhook::{
+    jobs_for, verify_signature, AgentJob, DeliveryLog, WebhookConfig, WebhookPayload,
+    DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER,
+};
+
+/// Events that can start agent jobs; `ping` and the rest are acknowledged
+/// and dropped.
+const HANDLED_EVENTS: [&str; 3] = ["issues", "issue_comment", "pull_request"];
+
+#[derive(Clone)]
+pub struct WebhookState {
+    config: Arc<WebhookConfig>,
+    deliveries: Arc<Mutex<DeliveryLog>>,
+    jobs: mpsc::Sender<AgentJob>,
+}
+
+impl WebhookState {
+    /// Queues the jobs deliveries ask for on `jobs`, for a worker such as
+    /// [`spawn_agent_worker`](crate::server::services::github_webhook::spawn_agent_worker).
+    pub fn new(config: WebhookConfig, jobs: mpsc::Sender<AgentJob>) -> Self {
+        Self {
+            config: Arc::new(config),
+            deliveries: Arc::new(Mutex::new(DeliveryLog::default())),
+            jobs,
+        }
+    }
+}
+
+/// `POST /webhooks/github`: checks the delivery's signature, drops
+/// redeliveries and queues the agent jobs the event asks for. Answers before
+/// the jobs run, since GitHub gives up on deliveries after ten seconds.
+pub async fn github_webhook(
+    State(state): State<WebhookState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let signed = header(SIGNATURE_HEADER).is_some_and(|signature| {
+        verify_signature(state.config.secret.as_bytes(), &body, signature)
+    });
+    if !signed {
+        warn!("Rejected a GitHub webhook delivery with a missing or invalid signature");
+        return error(StatusCode::UNAUTHORIZED, "Invalid signature");
+    }
+    let (Some(event), Some(delivery)) = (header(EVENT_HEADER), header(DELIVERY_HEADER)) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "Missing X-GitHub-Event or X-GitHub-Delivery header",
+        );
+    };
+    if !HANDLED_EVENTS.contains(&event) {
+        return (StatusCode::OK, Json(json!({"status": "ignored"})));
+    }
+    let payload: WebhookPayload = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("Invalid payload: {}", e)),
+    };
+
+    if !state.deliveries.lock().unwrap().insert(delivery) {
+        info!("Ignoring redelivery {}", delivery);
+        return (StatusCode::OK, Json(json!({"status": "duplicate"})));
+    }
+
+    let jobs = jobs_for(&state.config, event, delivery, &payload);
+    if jobs.is_empty() {
+        return (StatusCode::OK, Json(json!({"status": "ignored"})));
+    }
+    let queued = jobs.len();
+    // Room for every job or none, so a redelivery never repeats jobs that
+    // were already queued
+    let permits = match state.jobs.try_reserve_many(queued) {
+        Ok(permits) => permits,
+        Err(e) => {
+            // Let GitHub's redelivery try again
+            state.deliveries.lock().unwrap().remove(delivery);
+            return match e {
+                TrySendError::Full(_) => {
+                    error(StatusCode::SERVICE_UNAVAILABLE, "The job queue is full")
+                }
+                TrySendError::Closed(_) => {
+                    error(StatusCode::SERVICE_UNAVAILABLE, "The agent worker stopped")
+                }
+            };
+        }
+    };
+    for (permit, job) in permits.zip(jobs) {
+        permit.send(job);
+    }
+    info!("Queued {} agent jobs for delivery {}", queued, delivery);
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({"status": "queued", "jobs": queued})),
+    )
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, Json<Value>) {
+    (status, Json(json!({"error": message})))
+}
@@ -0,0 +1,338 @@
+use super::protocol::{
+    CallToolResult, Request, Response, RpcError, ToolInfo, METHOD_NOT_FOUND, PROTOCOL_VERSION,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+/// How long a request may wait for its response.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How to launch one MCP server, in the `mcpServers` format other MCP clients
+/// use.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct McpServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Lets the server's tools run without approval. Otherwise only tools the
+    /// server marks read-only do.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// MCP servers by name.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct McpConfig {
+    #[serde(rename = "mcpServers", default)]
+    pub servers: BTreeMap<String, McpServerConfig>,
+}
+
+impl McpConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read MCP config {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid MCP config {}", path.display()))
+    }
+}
+
+/// Why an MCP request failed.
+#[derive(Debug)]
+pub enum McpError {
+    /// The server answered with a JSON-RPC error.
+    Rpc {
+        server: String,
+        method: String,
+        error: RpcError,
+    },
+    Timeout {
+        server: String,
+        method: String,
+    },
+    /// The server exited or closed stdout.
+    Closed {
+        server: String,
+    },
+}
+
+impl fmt::Display for McpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            McpError::Rpc {
+                server,
+                method,
+                error,
+            } => write!(f, "MCP server {} failed {}: {}", server, method, error),
+            McpError::Timeout { server, method } => {
+                write!(f, "MCP server {} did not answer {} in time", server, method)
+            }
+            McpError::Closed { server } => write!(f, "MCP server {} has exited", server),
+        }
+    }
+}
+
+impl std::error::Error for McpError {}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Response>>>>;
+
+/// A connection to an MCP server running as a child process, speaking
+/// newline-delimited JSON-RPC over its stdin and stdout.
+pub struct McpClient {
+    name: String,
+    config: McpServerConfig,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    timeout: Duration,
+    server_info: Value,
+    /// Killed when the client is dropped.
+    _child: Mutex<Child>,
+}
+
+impl fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("McpClient")
+            .field("name", &self.name)
+            .field("server_info", &self.server_info)
+            .finish()
+    }
+}
+
+impl McpClient {
+    /// Launches the server and performs the `initialize` handshake.
+    pub async fn spawn(name: &str, config: &McpServerConfig) -> Result<Arc<Self>> {
+        Self::spawn_with_timeout(name, config, DEFAULT_REQUEST_TIMEOUT).await
+    }
+
+    pub async fn spawn_with_timeout(
+        name: &str,
+        config: &McpServerConfig,
+        timeout: Duration,
+    ) -> Result<Arc<Self>> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start MCP server {}", name))?;
+        let stdin = Arc::new(tokio::sync::Mutex::new(
+            child.stdin.take().expect("stdin is piped"),
+        ));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        tokio::spawn(read_messages(
+            name.to_string(),
+            BufReader::new(stdout),
+            stdin.clone(),
+            pending.clone(),
+        ));
+
+        let mut client = Self {
+            name: name.to_string(),
+            config: config.clone(),
+            stdin,
+            pending,
+            next_id: AtomicU64::new(1),
+            timeout,
+            server_info: Value::Null,
+            _child: Mutex::new(child),
+        };
+        let initialized = client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "openagents", "version": env!("CARGO_PKG_VERSION")}
+                }),
+            )
+            .await?;
+        if initialized["protocolVersion"] != PROTOCOL_VERSION {
+            warn!(
+                "MCP server {} speaks protocol {}, expected {}",
+                name, initialized["protocolVersion"], PROTOCOL_VERSION
+            );
+        }
+        client.server_info = initialized["serverInfo"].clone();
+        client
+            .send(&Request::notification("notifications/initialized", None))
+            .await?;
+        Ok(Arc::new(client))
+    }
+
+    /// The name the server is configured under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn config(&self) -> &McpServerConfig {
+        &self.config
+    }
+
+    /// `serverInfo` from the handshake.
+    pub fn server_info(&self) -> &Value {
+        &self.server_info
+    }
+
+    /// Sends a request and waits for its result.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let closed = || McpError::Closed {
+            server: self.name.clone(),
+        };
+        if self
+            .send(&Request::new(id, method, Some(params)))
+            .await
+            .is_err()
+        {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(closed().into());
+        }
+        let response = match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(closed().into()),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(McpError::Timeout {
+                    server: self.name.clone(),
+                    method: method.to_string(),
+                }
+                .into());
+            }
+        };
+        match response.error {
+            Some(error) => Err(McpError::Rpc {
+                server: self.name.clone(),
+                method: method.to_string(),
+                error,
+            }
+            .into()),
+            None => Ok(response.result.unwrap_or(Value::Null)),
+        }
+    }
+
+    /// Every tool the server offers, following `nextCursor` through the pages.
+    pub async fn list_tools(&self) -> Result<Vec<ToolInfo>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page = self.request("tools/list", params).await?;
+            let listed: Vec<ToolInfo> = serde_json::from_value(page["tools"].clone())
+                .map_err(|e| anyhow!("MCP server {} listed invalid tools: {}", self.name, e))?;
+            tools.extend(listed);
+            match page["nextCursor"].as_str() {
+                Some(next) if cursor.as_deref() != Some(next) => cursor = Some(next.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult> {
+        let result = self
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        serde_json::from_value(result).map_err(|e| {
+            anyhow!(
+                "MCP server {} returned an invalid {} result: {}",
+                self.name,
+                name,
+                e
+            )
+        })
+    }
+
+    async fn send(&self, request: &Request) -> Result<()> {
+        write_line(&self.stdin, &serde_json::to_string(request)?).await
+    }
+}
+
+async fn write_line(stdin: &tokio::sync::Mutex<ChildStdin>, line: &str) -> Result<()> {
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.write_all(b"\n").await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+/// Routes responses to their requests until the server closes stdout, then
+/// fails whatever is still waiting. Answers the server's own requests:
+/// `ping`, and method-not-found for anything else.
+async fn read_messages(
+    server: String,
+    stdout: BufReader<tokio::process::ChildStdout>,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+) {
+    let mut lines = stdout.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            warn!(
+                "Ignoring non-JSON output from MCP server {}: {}",
+                server, line
+            );
+            continue;
+        };
+        if let Some(method) = message["method"].as_str() {
+            let Some(id) = message.get("id").cloned() else {
+                debug!("Notification from MCP server {}: {}", server, method);
+                continue;
+            };
+            let response = match method {
+                "ping" => Response::success(id, json!({})),
+                _ => Response::failure(
+                    id,
+                    RpcError::new(METHOD_NOT_FOUND, format!("Unsupported method {}", method)),
+                ),
+            };
+            if let Ok(line) = serde_json::to_string(&response) {
+                let _ = write_line(&stdin, &line).await;
+            }
+            continue;
+        }
+        match serde_json::from_value::<Response>(message) {
+            Ok(response) => {
+                let sender = response
+                    .id
+                    .as_u64()
+                    .and_then(|id| pending.lock().unwrap().remove(&id));
+                match sender {
+                    Some(sender) => {
+                        let _ = sender.send(response);
+                    }
+                    None => warn!("Unexpected response from MCP server {}", server),
+                }
+            }
+            Err(e) => warn!("Invalid message from MCP server {}: {}", server, e),
+        }
+    }
+    // Dropping the senders fails the waiting requests
+    pending.lock().unwrap().clear();
+}
@@ -0,0 +1,7 @@
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+pub use client::{McpClient, McpConfig, McpError, McpServerConfig};
+pub use protocol::{CallToolResult, ToolAnnotations, ToolInfo};
+pub use server::McpServer;
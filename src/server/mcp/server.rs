@@ -0,0 +1,324 @@
+use super::protocol::{
+    CallToolResult, Request, Response, RpcError, ToolAnnotations, ToolInfo, INTERNAL_ERROR,
+    INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR, PROTOCOL_VERSION,
+};
+use crate::server::services::deepseek::{FunctionCallResponse, ToolCallResponse};
+use crate::server::tools::{
+    error_content, repo_map::MAX_MAP_CHARS, Approval, ApprovalRequest, GetRepoMap, RiskLevel,
+    ToolApprover, ToolContext, ToolRegistry,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// URI scheme of the repository map resources.
+pub const REPO_MAP_SCHEME: &str = "repomap://";
+
+/// Serves a [`ToolRegistry`] to MCP clients, plus repository maps as
+/// resources, over newline-delimited JSON-RPC.
+pub struct McpServer {
+    tools: ToolRegistry,
+    repo_maps: Option<GetRepoMap>,
+    context: ToolContext,
+    initialized: AtomicBool,
+    /// Cancels the `tools/call` requests still running, by request ID.
+    in_flight: Mutex<HashMap<String, CancellationToken>>,
+}
+
+/// MCP clients confirm tool calls with their user before sending them, so
+/// calls that arrive are already approved.
+struct ClientApproves;
+
+#[async_trait]
+impl ToolApprover for ClientApproves {
+    async fn approve(&self, _request: &ApprovalRequest) -> Approval {
+        Approval::Approved
+    }
+}
+
+impl McpServer {
+    pub fn new(tools: ToolRegistry) -> Self {
+        Self {
+            tools,
+            repo_maps: None,
+            context: ToolContext {
+                approver: Some(Arc::new(ClientApproves)),
+                ..Default::default()
+            },
+            initialized: AtomicBool::new(false),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Offers maps of `repo_maps`' local repositories as `repomap://name`
+    /// resources, and of GitHub repositories as `repomap://owner/repo`.
+    pub fn with_repo_maps(mut self, repo_maps: GetRepoMap) -> Self {
+        self.repo_maps = Some(repo_maps);
+        self
+    }
+
+    /// Answers one message. Notifications get no response.
+    pub async fn handle(&self, request: Request) -> Option<Response> {
+        let Some(id) = request.id.clone() else {
+            self.notify(&request);
+            return None;
+        };
+        let params = request.params.unwrap_or(Value::Null);
+        let result = match request.method.as_str() {
+            "initialize" => {
+                self.initialized.store(true, Ordering::SeqCst);
+                Ok(self.initialize_result())
+            }
+            "ping" => Ok(json!({})),
+            _ if !self.initialized.load(Ordering::SeqCst) => {
+                Err(RpcError::new(INVALID_REQUEST, "Expected initialize first"))
+            }
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => self.call_tool(&id, &params).await?,
+            "resources/list" => Ok(self.list_resources()),
+            "resources/templates/list" => Ok(self.list_resource_templates()),
+            "resources/read" => self.read_resource(&params).await,
+            method => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}", method),
+            )),
+        };
+        Some(match result {
+            Ok(result) => Response::success(id, result),
+            Err(error) => Response::failure(id, error),
+        })
+    }
+
+    /// Reads requests from `reader` until it closes and writes the responses
+    /// to `writer`. Requests run concurrently, so a slow tool doesn't hold up
+    /// the others or a cancellation.
+    pub async fn serve<R, W>(self: Arc<Self>, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Response>();
+        let write_responses = tokio::spawn(async move {
+            while let Some(response) = rx.recv().await {
+                let mut line = serde_json::to_string(&response)?;
+                line.push('\n');
+                writer.write_all(line.as_bytes()).await?;
+                writer.flush().await?;
+            }
+            anyhow::Ok(())
+        });
+
+        let mut requests = JoinSet::new();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    let _ = tx.send(Response::failure(
+                        Value::Null,
+                        RpcError::new(PARSE_ERROR, e.to_string()),
+                    ));
+                    continue;
+                }
+            };
+            // Notifications are handled in order, so a cancellation can't
+            // overtake the request it cancels
+            if request.id.is_none() {
+                self.notify(&request);
+                continue;
+            }
+            // The handshake is answered before anything after it runs
+            if request.method == "initialize" || !self.initialized.load(Ordering::SeqCst) {
+                if let Some(response) = self.handle(request).await {
+                    let _ = tx.send(response);
+                }
+                continue;
+            }
+            if request.method == "tools/call" {
+                self.track(request.id.as_ref().expect("requests have IDs"));
+            }
+            let server = self.clone();
+            let tx = tx.clone();
+            requests.spawn(async move {
+                if let Some(response) = server.handle(request).await {
+                    let _ = tx.send(response);
+                }
+            });
+            // Reap finished requests as we go
+            while requests.try_join_next().is_some() {}
+        }
+
+        // The client stopped sending, but may still read what's running
+        while requests.join_next().await.is_some() {}
+        drop(tx);
+        write_responses.await?
+    }
+
+    /// [`serve`](Self::serve) on stdin and stdout.
+    pub async fn serve_stdio(self) -> Result<()> {
+        Arc::new(self)
+            .serve(tokio::io::stdin(), tokio::io::stdout())
+            .await
+    }
+
+    fn notify(&self, notification: &Request) {
+        match notification.method.as_str() {
+            "notifications/cancelled" => {
+                let params = notification.params.clone().unwrap_or(Value::Null);
+                let cancelled = self
+                    .in_flight
+                    .lock()
+                    .unwrap()
+                    .remove(&params["requestId"].to_string());
+                if let Some(cancel) = cancelled {
+                    cancel.cancel();
+                }
+            }
+            method => debug!("MCP notification {}", method),
+        }
+    }
+
+    /// The token that cancels request `id`, made on first use.
+    fn track(&self, id: &Value) -> CancellationToken {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_insert_with(|| self.context.cancel.child_token())
+            .clone()
+    }
+
+    fn initialize_result(&self) -> Value {
+        let mut capabilities = json!({ "tools": {} });
+        if self.repo_maps.is_some() {
+            capabilities["resources"] = json!({});
+        }
+        json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": capabilities,
+            "serverInfo": {"name": "openagents", "version": env!("CARGO_PKG_VERSION")}
+        })
+    }
+
+    fn list_tools(&self) -> Value {
+        let tools: Vec<ToolInfo> = self
+            .tools
+            .names()
+            .into_iter()
+            .filter_map(|name| self.tools.get(name))
+            .map(|tool| ToolInfo {
+                name: tool.name().to_string(),
+                description: Some(tool.description().to_string()),
+                input_schema: tool.parameters(),
+                annotations: Some(ToolAnnotations {
+                    read_only_hint: Some(tool.risk() == RiskLevel::ReadOnly),
+                }),
+            })
+            .collect();
+        json!({ "tools": tools })
+    }
+
+    /// `None` when the call was cancelled, which gets no response.
+    async fn call_tool(&self, id: &Value, params: &Value) -> Option<Result<Value, RpcError>> {
+        let Some(name) = params["name"].as_str() else {
+            return Some(Err(RpcError::new(INVALID_PARAMS, "Missing tool name")));
+        };
+        if self.tools.get(name).is_none() {
+            return Some(Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown tool {}", name),
+            )));
+        }
+        let call = ToolCallResponse {
+            id: id.to_string(),
+            tool_type: "function".to_string(),
+            function: FunctionCallResponse {
+                name: name.to_string(),
+                arguments: match &params["arguments"] {
+                    Value::Null => String::new(),
+                    arguments => arguments.to_string(),
+                },
+            },
+        };
+
+        let cancel = self.track(id);
+        let context = ToolContext {
+            cancel: cancel.clone(),
+            ..self.context.clone()
+        };
+        let result = tokio::select! {
+            result = self.tools.execute(&call, &context) => Some(result),
+            _ = cancel.cancelled() => None,
+        };
+        self.in_flight.lock().unwrap().remove(&id.to_string());
+
+        let result = match result? {
+            Ok(content) => CallToolResult::text(content),
+            Err(e) => {
+                warn!("MCP call to {} failed: {:#}", name, e);
+                CallToolResult::error(error_content(&e))
+            }
+        };
+        Some(Ok(serde_json::to_value(result).expect("results serialize")))
+    }
+
+    fn list_resources(&self) -> Value {
+        let resources: Vec<Value> = self
+            .repo_maps
+            .iter()
+            .flat_map(|repo_maps| repo_maps.cache().local_repos().keys())
+            .map(|name| {
+                json!({
+                    "uri": format!("{}{}", REPO_MAP_SCHEME, name),
+                    "name": format!("{} repository map", name),
+                    "description": format!("Source files of {} with the functions, traits and impls in each", name),
+                    "mimeType": "text/plain"
+                })
+            })
+            .collect();
+        json!({ "resources": resources })
+    }
+
+    fn list_resource_templates(&self) -> Value {
+        let templates = match self.repo_maps {
+            Some(_) => vec![json!({
+                "uriTemplate": format!("{}{{owner}}/{{repo}}", REPO_MAP_SCHEME),
+                "name": "GitHub repository map",
+                "description": "Source files of a GitHub repository with the functions, traits and impls in each",
+                "mimeType": "text/plain"
+            })],
+            None => Vec::new(),
+        };
+        json!({ "resourceTemplates": templates })
+    }
+
+    async fn read_resource(&self, params: &Value) -> Result<Value, RpcError> {
+        let uri = params["uri"].as_str().unwrap_or_default();
+        let (Some(repo_maps), Some(repository)) =
+            (&self.repo_maps, uri.strip_prefix(REPO_MAP_SCHEME))
+        else {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("Unknown resource {:?}", uri),
+            ));
+        };
+        let map = repo_maps
+            .generate(repository, Vec::new(), MAX_MAP_CHARS)
+            .await
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, format!("{:#}", e)))?;
+        Ok(json!({
+            "contents": [{"uri": uri, "mimeType": "text/plain", "text": map.map}]
+        }))
+    }
+}
@@ -1,5 +1,6 @@
 pub mod config;
 pub mod handlers;
+pub mod mcp;
 pub mod models;
 pub mod services;
 pub mod tools;
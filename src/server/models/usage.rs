@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::server::services::deepseek::Usage;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateUsageRequest {
+    pub user_id: Option<String>,
+    pub conversation_id: Uuid,
+    pub source: String,
+    pub usage: Usage,
+}
+
+/// Summed token counts over a conversation or a user.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, sqlx::FromRow)]
+pub struct UsageTotals {
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub reasoning_tokens: i64,
+    pub prompt_cache_hit_tokens: i64,
+    pub prompt_cache_miss_tokens: i64,
+}
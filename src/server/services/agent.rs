@@ -0,0 +1,268 @@
+use anyhow::Result;
+use futures::future::join_all;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{timeout_at, Instant};
+use tracing::{info, warn};
+
+use super::deepseek::{
+    ChatCompletion, ChatMessage, GenerationOptions, ToolCallResponse, ToolChoice, Usage,
+};
+use super::provider::ChatProvider;
+use crate::server::tools::{error_content, ToolContext, ToolRegistry};
+
+/// Model turns allowed before the model is made to answer without tools.
+pub const DEFAULT_MAX_STEPS: usize = 8;
+/// Wall-clock time a run may take, tools included.
+pub const DEFAULT_TIME_BUDGET: Duration = Duration::from_secs(120);
+
+/// Progress reported while an [`AgentLoop`] runs.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// The model requested a call, which is about to run.
+    ToolCall {
+        call: ToolCallResponse,
+        status: String,
+    },
+    /// A call finished and its result was added to the conversation.
+    ToolResult(ChatMessage),
+}
+
+/// Why a run stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentStop {
+    /// The model answered without requesting more tools.
+    Finished,
+    /// The step budget ran out and the model answered with the results so far.
+    MaxSteps,
+}
+
+/// The outcome of an [`AgentLoop`] run.
+#[derive(Debug, Clone)]
+pub struct AgentRun {
+    /// The final answer.
+    pub content: String,
+    /// The conversation including every tool call and result, but not the
+    /// final answer.
+    pub messages: Vec<ChatMessage>,
+    /// Model turns taken, the final answer included.
+    pub steps: usize,
+    pub stop: AgentStop,
+    /// Tokens spent across all turns.
+    pub usage: Usage,
+}
+
+/// The run took longer than its wall-clock budget.
+#[derive(Debug, Clone)]
+pub struct TimeBudgetExceeded {
+    pub budget: Duration,
+    pub steps: usize,
+}
+
+impl fmt::Display for TimeBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Agent ran out of time after {} step(s) ({}s budget)",
+            self.steps,
+            self.budget.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for TimeBudgetExceeded {}
+
+/// Keeps calling the model with accumulated tool results until it stops
+/// requesting tools.
+///
+/// Calls requested in the same turn are independent, so they run concurrently
+/// and their results are added in the order the model asked for them. A call
+/// that fails is answered with [`error_content`] so the model can recover.
+pub struct AgentLoop {
+    model: Arc<dyn ChatProvider>,
+    tools: Arc<ToolRegistry>,
+    max_steps: usize,
+    time_budget: Duration,
+    options: GenerationOptions,
+    events: Option<mpsc::UnboundedSender<AgentEvent>>,
+}
+
+impl AgentLoop {
+    pub fn new(model: Arc<dyn ChatProvider>, tools: Arc<ToolRegistry>) -> Self {
+        Self {
+            model,
+            tools,
+            max_steps: DEFAULT_MAX_STEPS,
+            time_budget: DEFAULT_TIME_BUDGET,
+            options: GenerationOptions::default(),
+            events: None,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    pub fn with_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = time_budget;
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Reports progress on `events`. The channel closes when the loop is dropped.
+    pub fn with_events(mut self, events: mpsc::UnboundedSender<AgentEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Runs from a conversation ending in a user message.
+    pub async fn run(&self, messages: Vec<ChatMessage>, context: &ToolContext) -> Result<AgentRun> {
+        self.run_from(messages, None, context).await
+    }
+
+    /// Runs from a conversation whose last model turn already requested
+    /// `tool_calls`, e.g. from [`super::ModelRouter::route_message`]. That turn
+    /// counts as the first step.
+    pub async fn run_tool_calls(
+        &self,
+        messages: Vec<ChatMessage>,
+        tool_calls: Vec<ToolCallResponse>,
+        context: &ToolContext,
+    ) -> Result<AgentRun> {
+        let completion = ChatCompletion {
+            content: String::new(),
+            reasoning: None,
+            tool_calls: Some(tool_calls),
+            usage: None,
+        };
+        self.run_from(messages, Some(completion), context).await
+    }
+
+    async fn run_from(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        first: Option<ChatCompletion>,
+        context: &ToolContext,
+    ) -> Result<AgentRun> {
+        let deadline = Instant::now() + self.time_budget;
+        let out_of_time = |steps| TimeBudgetExceeded {
+            budget: self.time_budget,
+            steps,
+        };
+        let mut usage = Usage::default();
+        let mut steps = 0;
+        let mut next = first;
+
+        loop {
+            let completion = match next.take() {
+                Some(completion) => completion,
+                None => {
+                    let request = self.model.chat_with_tools_messages(
+                        messages.clone(),
+                        self.tools.definitions(),
+                        Some(ToolChoice::Auto("auto".to_string())),
+                        self.options.clone(),
+                    );
+                    timeout_at(deadline, request)
+                        .await
+                        .map_err(|_| out_of_time(steps))??
+                }
+            };
+            steps += 1;
+            if let Some(step_usage) = &completion.usage {
+                usage += step_usage;
+            }
+
+            let tool_calls = completion.tool_calls.unwrap_or_default();
+            if tool_calls.is_empty() {
+                return Ok(AgentRun {
+                    content: completion.content,
+                    messages,
+                    steps,
+                    stop: AgentStop::Finished,
+                    usage,
+                });
+            }
+
+            info!("Agent step {}: {} tool call(s)", steps, tool_calls.len());
+            messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: completion.content,
+                tool_call_id: None,
+                tool_calls: Some(tool_calls.clone()),
+            });
+            let results = timeout_at(deadline, self.execute_all(&tool_calls, context))
+                .await
+                .map_err(|_| out_of_time(steps))?;
+            messages.extend(results);
+
+            if steps >= self.max_steps {
+                warn!("Agent reached {} steps, asking for an answer", steps);
+                let request = self.model.chat_with_tools_messages(
+                    messages.clone(),
+                    self.tools.definitions(),
+                    Some(ToolChoice::Auto("none".to_string())),
+                    self.options.clone(),
+                );
+                let completion = timeout_at(deadline, request)
+                    .await
+                    .map_err(|_| out_of_time(steps))??;
+                if let Some(step_usage) = &completion.usage {
+                    usage += step_usage;
+                }
+                return Ok(AgentRun {
+                    content: completion.content,
+                    messages,
+                    steps: steps + 1,
+                    stop: AgentStop::MaxSteps,
+                    usage,
+                });
+            }
+        }
+    }
+
+    /// Runs one turn's calls concurrently and returns their tool messages in
+    /// call order.
+    async fn execute_all(
+        &self,
+        tool_calls: &[ToolCallResponse],
+        context: &ToolContext,
+    ) -> Vec<ChatMessage> {
+        let calls = tool_calls.iter().map(|call| async move {
+            self.send(AgentEvent::ToolCall {
+                call: call.clone(),
+                status: self.tools.status(call),
+            });
+            let content = match self.tools.execute(call, context).await {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Tool call {} failed: {}", call.function.name, e);
+                    error_content(&e)
+                }
+            };
+            let message = ChatMessage {
+                role: "tool".to_string(),
+                content,
+                tool_call_id: Some(call.id.clone()),
+                tool_calls: None,
+            };
+            self.send(AgentEvent::ToolResult(message.clone()));
+            message
+        });
+        join_all(calls).await
+    }
+
+    fn send(&self, event: AgentEvent) {
+        if let Some(events) = &self.events {
+            // The receiver may have stopped listening, which is fine
+            let _ = events.send(event);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use anyhow::{Context, Result};
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Upstream name for the DeepSeek API.
+pub const DEEPSEEK_UPSTREAM: &str = "deepseek";
+/// Upstream name for the GitHub REST API.
+pub const GITHUB_UPSTREAM: &str = "github";
+
+/// Response headers that describe the original connection rather than the content.
+const SKIPPED_RESPONSE_HEADERS: &[&str] = &[
+    "connection",
+    "content-encoding",
+    "content-length",
+    "date",
+    "set-cookie",
+    "transfer-encoding",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+impl FromStr for CassetteMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "record" => Ok(Self::Record),
+            "replay" => Ok(Self::Replay),
+            other => Err(anyhow::anyhow!(
+                "Unknown cassette mode '{}'; expected 'record' or 'replay'",
+                other
+            )),
+        }
+    }
+}
+
+/// A recorded request or response body. JSON bodies are stored as JSON so
+/// fixtures stay readable; anything else, such as an SSE stream, as text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordedBody {
+    Text(String),
+    Json(Value),
+}
+
+impl RecordedBody {
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        Some(match serde_json::from_slice(bytes) {
+            Ok(value) => Self::Json(value),
+            Err(_) => Self::Text(String::from_utf8_lossy(bytes).into_owned()),
+        })
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Text(text) => text.into_bytes(),
+            Self::Json(value) => value.to_string().into_bytes(),
+        }
+    }
+}
+
+/// The parts of a request used for matching. Headers are not recorded, so
+/// credentials never end up in fixtures.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub upstream: String,
+    /// Path and query relative to the upstream base URL.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RecordedBody>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RecordedBody>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+struct CassetteState {
+    path: PathBuf,
+    mode: CassetteMode,
+    upstreams: HashMap<String, String>,
+    client: reqwest::Client,
+    interactions: Mutex<Vec<Interaction>>,
+    /// Which interactions replay has already served, so repeated identical
+    /// requests get their recorded responses in order.
+    served: Mutex<Vec<bool>>,
+}
+
+/// Record/replay of upstream HTTP traffic.
+///
+/// A cassette is a small local server that services are pointed at through their
+/// base URLs. In [`CassetteMode::Record`] it forwards every request to the real
+/// upstream and saves the exchange, including SSE streams, to a JSON fixture. In
+/// [`CassetteMode::Replay`] it answers from that fixture without touching the
+/// network, so tests and the CLIs run offline and deterministically.
+pub struct Cassette {
+    url: String,
+    mode: CassetteMode,
+    state: Arc<CassetteState>,
+    server: JoinHandle<()>,
+}
+
+impl Cassette {
+    /// Starts a cassette server for the fixture at `path`.
+    ///
+    /// `upstreams` maps names such as [`DEEPSEEK_UPSTREAM`] to the real base
+    /// URLs that record mode forwards to. Recording starts a fresh fixture;
+    /// replay fails if the fixture can't be read.
+    pub async fn start(
+        path: impl Into<PathBuf>,
+        mode: CassetteMode,
+        upstreams: &[(&str, &str)],
+    ) -> Result<Self> {
+        let path = path.into();
+        let interactions = match mode {
+            CassetteMode::Record => Vec::new(),
+            CassetteMode::Replay => load(&path)?.interactions,
+        };
+
+        let state = Arc::new(CassetteState {
+            path,
+            mode,
+            upstreams: upstreams
+                .iter()
+                .map(|(name, url)| (name.to_string(), url.trim_end_matches('/').to_string()))
+                .collect(),
+            client: reqwest::Client::new(),
+            served: Mutex::new(vec![false; interactions.len()]),
+            interactions: Mutex::new(interactions),
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let server = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("Cassette server stopped: {}", e);
+            }
+        });
+
+        info!(
+            "Cassette {:?} for {} at {}",
+            mode,
+            state.path.display(),
+            url
+        );
+
+        Ok(Self {
+            url,
+            mode,
+            state,
+            server,
+        })
+    }
+
+    /// Starts a cassette when `OPENAGENTS_CASSETTE` names a fixture, for the
+    /// CLIs. `OPENAGENTS_CASSETTE_MODE` is `record` or `replay` (the default).
+    ///
+    /// Points `DEEPSEEK_API_URL` and `GITHUB_API_URL` at the cassette, and in
+    /// replay mode fills in placeholder credentials so no real keys are needed.
+    /// Call it at the start of `main`, before any services are created.
+    pub async fn from_env() -> Result<Option<Self>> {
+        let Ok(path) = std::env::var("OPENAGENTS_CASSETTE") else {
+            return Ok(None);
+        };
+        let mode = match std::env::var("OPENAGENTS_CASSETTE_MODE") {
+            Ok(mode) => mode.parse()?,
+            Err(_) => CassetteMode::Replay,
+        };
+
+        let deepseek_url = std::env::var("DEEPSEEK_API_URL")
+            .unwrap_or_else(|_| "https://api.deepseek.com/v1".to_string());
+        let github_url = std::env::var("GITHUB_API_URL")
+            .unwrap_or_else(|_| "https://api.github.com".to_string());
+
+        let cassette = Self::start(
+            path,
+            mode,
+            &[
+                (DEEPSEEK_UPSTREAM, &deepseek_url),
+                (GITHUB_UPSTREAM, &github_url),
+            ],
+        )
+        .await?;
+
+        std::env::set_var("DEEPSEEK_API_URL", cassette.url_for(DEEPSEEK_UPSTREAM));
+        std::env::set_var("GITHUB_API_URL", cassette.url_for(GITHUB_UPSTREAM));
+        if mode == CassetteMode::Replay {
+            for key in ["DEEPSEEK_API_KEY", "GITHUB_TOKEN"] {
+                if std::env::var(key).is_err() {
+                    std::env::set_var(key, "replay");
+                }
+            }
+        }
+
+        Ok(Some(cassette))
+    }
+
+    /// Base URL to give a service in place of the `upstream`'s real one.
+    pub fn url_for(&self, upstream: &str) -> String {
+        format!("{}/{}", self.url, upstream)
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Every exchange recorded or loaded so far.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.interactions.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn load(path: &Path) -> Result<CassetteFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse cassette {}", path.display()))
+}
+
+fn save(path: &Path, interactions: &[Interaction]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = CassetteFile {
+        interactions: interactions.to_vec(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&file)? + "\n")?;
+    Ok(())
+}
+
+/// Splits `/upstream/rest?query` into the upstream name and `/rest?query`,
+/// with query parameters sorted so their order doesn't affect matching.
+fn split_uri(uri: &Uri) -> Option<(String, String)> {
+    let path = uri.path().trim_start_matches('/');
+    let (upstream, rest) = path.split_once('/').unwrap_or((path, ""));
+    if upstream.is_empty() {
+        return None;
+    }
+
+    let mut relative = format!("/{}", rest);
+    if let Some(query) = uri.query() {
+        let mut params: Vec<&str> = query.split('&').filter(|p| !p.is_empty()).collect();
+        params.sort_unstable();
+        relative = format!("{}?{}", relative, params.join("&"));
+    }
+    Some((upstream.to_string(), relative))
+}
+
+async fn handle(
+    State(state): State<Arc<CassetteState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some((upstream, path)) = split_uri(&uri) else {
+        return error_response(StatusCode::NOT_FOUND, "No upstream in cassette URL");
+    };
+    let request = RecordedRequest {
+        method: method.to_string(),
+        upstream,
+        path,
+        body: RecordedBody::from_bytes(&body),
+    };
+
+    let result = match state.mode {
+        CassetteMode::Record => record(&state, request, &headers, body).await,
+        CassetteMode::Replay => replay(&state, &request),
+    };
+
+    match result {
+        Ok(response) => into_response(response),
+        Err(e) => {
+            warn!("Cassette error: {:#}", e);
+            error_response(StatusCode::NOT_FOUND, &e.to_string())
+        }
+    }
+}
+
+async fn record(
+    state: &CassetteState,
+    request: RecordedRequest,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Result<RecordedResponse> {
+    let base_url = state
+        .upstreams
+        .get(&request.upstream)
+        .with_context(|| format!("Unknown cassette upstream '{}'", request.upstream))?;
+    let url = format!("{}{}", base_url, request.path);
+
+    let mut upstream_request = state
+        .client
+        .request(
+            reqwest::Method::from_bytes(request.method.as_bytes())?,
+            &url,
+        )
+        .body(body.to_vec());
+    for (name, value) in headers {
+        if name != "host" && name != "content-length" {
+            upstream_request = upstream_request.header(name.as_str(), value.as_bytes());
+        }
+    }
+
+    let upstream_response = upstream_request.send().await?;
+    let status = upstream_response.status().as_u16();
+    let response_headers = upstream_response
+        .headers()
+        .iter()
+        .filter(|(name, _)| !SKIPPED_RESPONSE_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let response_body = upstream_response.bytes().await?;
+
+    let response = RecordedResponse {
+        status,
+        headers: response_headers,
+        body: RecordedBody::from_bytes(&response_body),
+    };
+
+    info!(
+        "Recorded {} {}{} -> {}",
+        request.method, request.upstream, request.path, status
+    );
+    let mut interactions = state.interactions.lock().unwrap();
+    interactions.push(Interaction {
+        request,
+        response: response.clone(),
+    });
+    save(&state.path, &interactions)?;
+
+    Ok(response)
+}
+
+/// Serves the first unused recording that matches `request`, or the last
+/// match again once they have all been used.
+fn replay(state: &CassetteState, request: &RecordedRequest) -> Result<RecordedResponse> {
+    let interactions = state.interactions.lock().unwrap();
+    let mut served = state.served.lock().unwrap();
+
+    let matches: Vec<usize> = interactions
+        .iter()
+        .enumerate()
+        .filter(|(_, interaction)| interaction.request == *request)
+        .map(|(index, _)| index)
+        .collect();
+
+    let index = matches
+        .iter()
+        .copied()
+        .find(|&index| !served[index])
+        .or_else(|| matches.last().copied())
+        .with_context(|| {
+            format!(
+                "Cassette {} has no recording for {} {}{}",
+                state.path.display(),
+                request.method,
+                request.upstream,
+                request.path
+            )
+        })?;
+
+    served[index] = true;
+    Ok(interactions[index].response.clone())
+}
+
+fn into_response(recorded: RecordedResponse) -> Response {
+    let mut builder = Response::builder().status(recorded.status);
+    for (name, value) in &recorded.headers {
+        builder = builder.header(name, value);
+    }
+    let body = recorded
+        .body
+        .map(RecordedBody::into_bytes)
+        .unwrap_or_default();
+    builder
+        .body(Body::from(body))
+        .unwrap_or_else(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        axum::Json(json!({ "error": { "message": message } })),
+    )
+        .into_response()
+}
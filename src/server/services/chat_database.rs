@@ -1,6 +1,7 @@
 use crate::server::models::chat::{
     Conversation, CreateConversationRequest, CreateMessageRequest, Message,
 };
+use crate::server::models::usage::{CreateUsageRequest, UsageTotals};
 use anyhow::{Context, Result};
 use sqlx::PgPool;
 use uuid::Uuid;
@@ -142,4 +143,75 @@ impl ChatDatabase {
 
         Ok(())
     }
+
+    pub async fn record_usage(&self, request: CreateUsageRequest) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO token_usage (
+                user_id, conversation_id, source, prompt_tokens, completion_tokens,
+                reasoning_tokens, prompt_cache_hit_tokens, prompt_cache_miss_tokens
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            request.user_id,
+            request.conversation_id,
+            request.source,
+            i64::from(request.usage.prompt_tokens),
+            i64::from(request.usage.completion_tokens),
+            i64::from(request.usage.reasoning_tokens()),
+            i64::from(request.usage.prompt_cache_hit_tokens),
+            i64::from(request.usage.prompt_cache_miss_tokens)
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to record token usage")?;
+
+        Ok(())
+    }
+
+    pub async fn get_conversation_usage(&self, conversation_id: Uuid) -> Result<UsageTotals> {
+        let totals = sqlx::query_as!(
+            UsageTotals,
+            r#"
+            SELECT
+                COUNT(*) AS "requests!",
+                COALESCE(SUM(prompt_tokens), 0)::BIGINT AS "prompt_tokens!",
+                COALESCE(SUM(completion_tokens), 0)::BIGINT AS "completion_tokens!",
+                COALESCE(SUM(reasoning_tokens), 0)::BIGINT AS "reasoning_tokens!",
+                COALESCE(SUM(prompt_cache_hit_tokens), 0)::BIGINT AS "prompt_cache_hit_tokens!",
+                COALESCE(SUM(prompt_cache_miss_tokens), 0)::BIGINT AS "prompt_cache_miss_tokens!"
+            FROM token_usage
+            WHERE conversation_id = $1
+            "#,
+            conversation_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to fetch conversation usage")?;
+
+        Ok(totals)
+    }
+
+    pub async fn get_user_usage(&self, user_id: &str) -> Result<UsageTotals> {
+        let totals = sqlx::query_as!(
+            UsageTotals,
+            r#"
+            SELECT
+                COUNT(*) AS "requests!",
+                COALESCE(SUM(prompt_tokens), 0)::BIGINT AS "prompt_tokens!",
+                COALESCE(SUM(completion_tokens), 0)::BIGINT AS "completion_tokens!",
+                COALESCE(SUM(reasoning_tokens), 0)::BIGINT AS "reasoning_tokens!",
+                COALESCE(SUM(prompt_cache_hit_tokens), 0)::BIGINT AS "prompt_cache_hit_tokens!",
+                COALESCE(SUM(prompt_cache_miss_tokens), 0)::BIGINT AS "prompt_cache_miss_tokens!"
+            FROM token_usage
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to fetch user usage")?;
+
+        Ok(totals)
+    }
 }
@@ -0,0 +1,140 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub enum DeepSeekError {
+    /// 429 Too Many Requests.
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// 401 or 403, usually a missing or invalid API key.
+    Auth {
+        status: u16,
+        message: String,
+    },
+    /// 5xx. A 503 may carry a `Retry-After`.
+    Server {
+        status: u16,
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// Any other non-success status, e.g. 400 for an invalid request body.
+    Api {
+        status: u16,
+        message: String,
+    },
+    Timeout,
+    Network(String),
+    /// The body couldn't be parsed as a chat completion.
+    Malformed(String),
+    EmptyResponse,
+    /// The request was rejected before being sent.
+    InvalidRequest(String),
+}
+
+impl DeepSeekError {
+    /// Builds the error for a non-success response from its status, headers and body.
+    pub(crate) fn from_response(status: StatusCode, headers: &HeaderMap, body: &str) -> Self {
+        let message = api_error_message(body);
+        let retry_after = parse_retry_after(headers);
+
+        match status.as_u16() {
+            429 => DeepSeekError::RateLimited {
+                retry_after,
+                message,
+            },
+            401 | 403 => DeepSeekError::Auth {
+                status: status.as_u16(),
+                message,
+            },
+            500..=599 => DeepSeekError::Server {
+                status: status.as_u16(),
+                retry_after,
+                message,
+            },
+            _ => DeepSeekError::Api {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+
+    /// Whether the same request may succeed if sent again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            DeepSeekError::RateLimited { .. }
+                | DeepSeekError::Server { .. }
+                | DeepSeekError::Timeout
+                | DeepSeekError::Network(_)
+        )
+    }
+
+    /// The delay the server asked for before retrying, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            DeepSeekError::RateLimited { retry_after, .. }
+            | DeepSeekError::Server { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DeepSeekError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeepSeekError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            DeepSeekError::Auth { status, message } => {
+                write!(f, "Authentication failed ({}): {}", status, message)
+            }
+            DeepSeekError::Server {
+                status, message, ..
+            } => write!(f, "Server error ({}): {}", status, message),
+            DeepSeekError::Api { status, message } => {
+                write!(f, "API request failed ({}): {}", status, message)
+            }
+            DeepSeekError::Timeout => write!(f, "Request timed out"),
+            DeepSeekError::Network(msg) => write!(f, "Network error: {}", msg),
+            DeepSeekError::Malformed(msg) => write!(f, "Malformed response: {}", msg),
+            DeepSeekError::EmptyResponse => write!(f, "No response from model"),
+            DeepSeekError::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DeepSeekError {}
+
+impl From<reqwest::Error> for DeepSeekError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            DeepSeekError::Timeout
+        } else if error.is_decode() {
+            DeepSeekError::Malformed(error.to_string())
+        } else {
+            DeepSeekError::Network(error.to_string())
+        }
+    }
+}
+
+/// Pulls `error.message` out of an OpenAI-style error body, falling back to the raw body.
+fn api_error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
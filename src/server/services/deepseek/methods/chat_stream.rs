@@ -3,116 +3,206 @@ use futures::StreamExt;
 use tokio::sync::mpsc;
 use tracing::info;
 
-use crate::server::services::deepseek::streaming::{StreamResponse, StreamUpdate};
-use crate::server::services::deepseek::types::{ChatMessage, ChatRequest};
+use crate::server::services::deepseek::streaming::{
+    StreamResponse, StreamUpdate, ToolCallAccumulator,
+};
+use crate::server::services::deepseek::types::{
+    ChatMessage, ChatRequest, GenerationOptions, Tool, ToolChoice,
+};
 use crate::server::services::deepseek::DeepSeekService;
 
 impl DeepSeekService {
     pub async fn chat_stream(
         &self,
         prompt: String,
-        use_reasoner: bool,
+        options: impl Into<GenerationOptions>,
     ) -> mpsc::Receiver<StreamUpdate> {
+        self.chat_stream_with_tools(prompt, Vec::new(), None, options)
+            .await
+    }
+
+    /// Streams a single-prompt completion while letting the model call `tools`.
+    ///
+    /// Tool calls arrive as one [`StreamUpdate::ToolCalls`] with complete arguments
+    /// once the model finishes its turn.
+    pub async fn chat_stream_with_tools(
+        &self,
+        prompt: String,
+        tools: Vec<Tool>,
+        tool_choice: Option<ToolChoice>,
+        options: impl Into<GenerationOptions>,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            tool_call_id: None,
+            tool_calls: None,
+        }];
+
+        self.chat_stream_messages(messages, Some(tools), tool_choice, options)
+            .await
+    }
+
+    /// Streams a completion over a full conversation, e.g. prior turns plus the
+    /// latest user message.
+    pub async fn chat_stream_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<ToolChoice>,
+        options: impl Into<GenerationOptions>,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        let options = options.into();
+
+        // The reasoner doesn't support function calling
+        let tools = tools.filter(|tools| !tools.is_empty() && !options.use_reasoner);
+
+        let mut request = self.build_request(messages, true, &options);
+        if tools.is_some() {
+            request.tools = tools;
+            request.tool_choice = tool_choice;
+        }
+
+        self.spawn_stream(request)
+    }
+
+    /// Runs the request on a background task. Dropping the receiver cancels the
+    /// task and closes the HTTP connection, so an abandoned generation stops
+    /// consuming tokens.
+    fn spawn_stream(&self, request: ChatRequest) -> mpsc::Receiver<StreamUpdate> {
         let (tx, rx) = mpsc::channel(100);
-        let client = self.client.clone();
-        let api_key = self.api_key.clone();
-        let base_url = self.base_url.clone();
+        let service = self.clone();
 
         tokio::spawn(async move {
-            let model = if use_reasoner {
-                "deepseek-reasoner"
-            } else {
-                "deepseek-chat"
-            };
+            tokio::select! {
+                _ = service.run_stream(&request, &tx) => {}
+                _ = tx.closed() => info!("Stream receiver dropped, cancelling request"),
+            }
+        });
 
-            let messages = vec![ChatMessage {
-                role: "user".to_string(),
-                content: prompt,
-                tool_call_id: None,
-                tool_calls: None,
-            }];
-
-            let request = ChatRequest {
-                model: model.to_string(),
-                messages,
-                stream: true,
-                temperature: 0.7,
-                max_tokens: None,
-                tools: None,
-                tool_choice: None,
-            };
+        rx
+    }
 
-            let url = format!("{}/chat/completions", base_url);
-            let response = client
-                .post(&url)
-                .header("Content-Type", "application/json")
-                .header("Authorization", format!("Bearer {}", api_key))
-                .json(&request)
-                .send()
-                .await;
+    async fn run_stream(&self, request: &ChatRequest, tx: &mpsc::Sender<StreamUpdate>) {
+        let mut parser = StreamParser::default();
 
-            match response {
-                Ok(response) => {
-                    let mut stream = response.bytes_stream();
-                    let mut buffer = String::new();
+        match self.send_request(request).await {
+            Ok(response) => {
+                let mut stream = response.bytes_stream();
 
-                    while let Some(chunk_result) = stream.next().await {
-                        match chunk_result {
-                            Ok(chunk) => {
-                                process_chunk(chunk, &mut buffer, &tx).await;
-                            }
-                            Err(e) => {
-                                info!("Stream error: {}", e);
+                while let Some(chunk_result) = stream.next().await {
+                    match chunk_result {
+                        Ok(chunk) => {
+                            if parser.process_chunk(chunk, tx).await {
                                 break;
                             }
                         }
+                        Err(e) => {
+                            info!("Stream error: {}", e);
+                            let _ = tx.send(StreamUpdate::Error(e.into())).await;
+                            break;
+                        }
                     }
                 }
-                Err(e) => {
-                    info!("Request error: {}", e);
-                }
             }
-        });
+            Err(e) => {
+                info!("Request error: {}", e);
+                let _ = tx.send(StreamUpdate::Error(e)).await;
+            }
+        }
 
-        rx
+        parser.finish(tx).await;
     }
 }
 
-async fn process_chunk(chunk: Bytes, buffer: &mut String, tx: &mpsc::Sender<StreamUpdate>) {
-    let chunk_str = String::from_utf8_lossy(&chunk);
-    buffer.push_str(&chunk_str);
+/// Incremental parser for the server-sent events of a chat completion.
+#[derive(Default)]
+struct StreamParser {
+    buffer: String,
+    tool_calls: ToolCallAccumulator,
+    done: bool,
+}
+
+impl StreamParser {
+    /// Feeds one network chunk through the parser. Returns `true` once the stream
+    /// is complete or the receiver has gone away.
+    async fn process_chunk(&mut self, chunk: Bytes, tx: &mpsc::Sender<StreamUpdate>) -> bool {
+        let chunk_str = String::from_utf8_lossy(&chunk);
+        self.buffer.push_str(&chunk_str);
 
-    // Process complete SSE messages
-    while let Some(pos) = buffer.find('\n') {
-        // Extract the line and update buffer without borrowing issues
-        let line = buffer[..pos].trim().to_string();
-        *buffer = buffer[pos + 1..].to_string();
+        // Process complete SSE messages
+        while let Some(pos) = self.buffer.find('\n') {
+            // Extract the line and update buffer without borrowing issues
+            let line = self.buffer[..pos].trim().to_string();
+            self.buffer = self.buffer[pos + 1..].to_string();
+
+            let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                continue;
+            };
 
-        if let Some(data) = line.strip_prefix("data: ") {
             if data == "[DONE]" {
-                let _ = tx.send(StreamUpdate::Done).await;
-                break;
+                self.finish(tx).await;
+                return true;
             }
 
-            if let Ok(response) = serde_json::from_str::<StreamResponse>(data) {
-                if let Some(choice) = response.choices.first() {
-                    if let Some(ref content) = choice.delta.content {
-                        let _ = tx.send(StreamUpdate::Content(content.to_string())).await;
-                    }
-                    if let Some(ref reasoning) = choice.delta.reasoning_content {
-                        let _ = tx
-                            .send(StreamUpdate::Reasoning(reasoning.to_string()))
-                            .await;
+            let Ok(response) = serde_json::from_str::<StreamResponse>(data) else {
+                continue;
+            };
+
+            if let Some(choice) = response.choices.first() {
+                if let Some(ref content) = choice.delta.content {
+                    if tx
+                        .send(StreamUpdate::Content(content.to_string()))
+                        .await
+                        .is_err()
+                    {
+                        return true;
                     }
-                    if let Some(tool_calls) = &choice.delta.tool_calls {
-                        let _ = tx.send(StreamUpdate::ToolCalls(tool_calls.clone())).await;
+                }
+                if let Some(ref reasoning) = choice.delta.reasoning_content {
+                    if tx
+                        .send(StreamUpdate::Reasoning(reasoning.to_string()))
+                        .await
+                        .is_err()
+                    {
+                        return true;
                     }
-                    if choice.finish_reason.is_some() {
-                        let _ = tx.send(StreamUpdate::Done).await;
-                        break;
+                }
+                if let Some(tool_calls) = &choice.delta.tool_calls {
+                    for delta in tool_calls {
+                        self.tool_calls.push(delta);
                     }
                 }
+                if choice.finish_reason.is_some() {
+                    self.flush_tool_calls(tx).await;
+                }
+            }
+
+            if let Some(usage) = response.usage {
+                if tx.send(StreamUpdate::Usage(usage)).await.is_err() {
+                    return true;
+                }
             }
         }
+
+        false
+    }
+
+    async fn flush_tool_calls(&mut self, tx: &mpsc::Sender<StreamUpdate>) {
+        if !self.tool_calls.is_empty() {
+            let _ = tx
+                .send(StreamUpdate::ToolCalls(self.tool_calls.finish()))
+                .await;
+        }
+    }
+
+    /// Emits anything still pending followed by a single `Done`.
+    async fn finish(&mut self, tx: &mpsc::Sender<StreamUpdate>) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+        self.flush_tool_calls(tx).await;
+        let _ = tx.send(StreamUpdate::Done).await;
     }
 }
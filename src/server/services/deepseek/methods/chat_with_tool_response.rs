@@ -1,8 +1,8 @@
-use anyhow::Result;
 use tracing::info;
 
+use crate::server::services::deepseek::error::DeepSeekError;
 use crate::server::services::deepseek::types::{
-    ChatMessage, ChatRequest, ChatResponse, Tool, ToolCallResponse, ToolChoice,
+    ChatCompletion, ChatMessage, GenerationOptions, Tool, ToolChoice,
 };
 use crate::server::services::deepseek::DeepSeekService;
 
@@ -12,13 +12,9 @@ impl DeepSeekService {
         messages: Vec<ChatMessage>,
         tool_response: ChatMessage,
         tools: Vec<Tool>,
-        use_reasoner: bool,
-    ) -> Result<(String, Option<String>, Option<Vec<ToolCallResponse>>)> {
-        let model = if use_reasoner {
-            "deepseek-reasoner"
-        } else {
-            "deepseek-chat"
-        };
+        options: impl Into<GenerationOptions>,
+    ) -> Result<ChatCompletion, DeepSeekError> {
+        let options = options.into();
 
         // Find the assistant message with tool calls
         let has_assistant_with_tools = messages
@@ -26,14 +22,16 @@ impl DeepSeekService {
             .any(|msg| msg.role == "assistant" && msg.tool_calls.is_some());
 
         if !has_assistant_with_tools {
-            return Err(anyhow::anyhow!(
-                "No assistant message with tool calls found"
+            return Err(DeepSeekError::InvalidRequest(
+                "No assistant message with tool calls found".to_string(),
             ));
         }
 
         // Make sure tool response has tool_call_id
         if tool_response.tool_call_id.is_none() {
-            return Err(anyhow::anyhow!("Tool response must have tool_call_id"));
+            return Err(DeepSeekError::InvalidRequest(
+                "Tool response must have tool_call_id".to_string(),
+            ));
         }
 
         // Create a new sequence of messages with the tool response
@@ -52,59 +50,12 @@ impl DeepSeekService {
             }
         }
 
-        let request = ChatRequest {
-            model: model.to_string(),
-            messages: all_messages,
-            stream: false,
-            temperature: 0.7,
-            max_tokens: None,
-            tools: Some(tools),
-            tool_choice: Some(ToolChoice::Auto("none".to_string())),
-        };
-
-        let url = format!("{}/chat/completions", self.base_url);
-        let response = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&request)
-            .send()
-            .await?;
-
-        // Add status code check
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await?;
-            return Err(anyhow::anyhow!(
-                "API request failed with status {}: {}",
-                status,
-                text
-            ));
-        }
-
-        // Get response text for debugging
-        let text = response.text().await?;
-        info!("API Response: {}", text);
-
-        if text.is_empty() {
-            return Err(anyhow::anyhow!("Empty response from API"));
-        }
+        let mut request = self.build_request(all_messages, false, &options);
+        request.tools = Some(tools);
+        request.tool_choice = Some(ToolChoice::Auto("none".to_string()));
 
-        // Parse the response
-        let chat_response: ChatResponse = serde_json::from_str(&text).map_err(|e| {
-            anyhow::anyhow!("Failed to parse response: {}\nResponse text: {}", e, text)
-        })?;
-
-        if let Some(choice) = chat_response.choices.first() {
-            info!("Response content: {}", choice.message.content);
-            Ok((
-                choice.message.content.clone(),
-                choice.message.reasoning_content.clone(),
-                choice.message.tool_calls.clone(),
-            ))
-        } else {
-            Err(anyhow::anyhow!("No response from model"))
-        }
+        let completion = self.complete(&request).await?;
+        info!("Response content: {}", completion.content);
+        Ok(completion)
     }
 }
@@ -2,3 +2,4 @@ mod chat;
 mod chat_stream;
 mod chat_with_tool_response;
 mod chat_with_tools;
+mod request;
@@ -0,0 +1,89 @@
+use reqwest::Response;
+use tracing::{error, info, warn};
+
+use crate::server::services::deepseek::error::DeepSeekError;
+use crate::server::services::deepseek::types::{ChatCompletion, ChatRequest, ChatResponse};
+use crate::server::services::deepseek::DeepSeekService;
+
+impl DeepSeekService {
+    /// Posts `request` to the chat completions endpoint, retrying transient
+    /// failures according to the service's [`RetryPolicy`](super::super::RetryPolicy).
+    pub(crate) async fn send_request(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<Response, DeepSeekError> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            let error = match self
+                .client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(request)
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let headers = response.headers().clone();
+                    let body = response.text().await.unwrap_or_default();
+                    DeepSeekError::from_response(status, &headers, &body)
+                }
+                Err(e) => DeepSeekError::from(e),
+            };
+
+            match self.retry_policy.next_delay(attempt, &error) {
+                Some(delay) => {
+                    warn!(
+                        "Request to {} failed (attempt {}/{}): {}. Retrying in {:?}",
+                        url, attempt, self.retry_policy.max_attempts, error, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    error!("Request to {} failed: {}", url, error);
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Sends a non-streaming request and returns the first choice with the usage.
+    pub(crate) async fn complete(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<ChatCompletion, DeepSeekError> {
+        let response = self.send_request(request).await?;
+
+        // Get response text for debugging
+        let text = response.text().await?;
+        info!("API Response: {}", text);
+
+        if text.trim().is_empty() {
+            return Err(DeepSeekError::EmptyResponse);
+        }
+
+        let chat_response: ChatResponse = serde_json::from_str(&text)
+            .map_err(|e| DeepSeekError::Malformed(format!("{}\nResponse text: {}", e, text)))?;
+
+        let usage = chat_response.usage;
+        let message = chat_response
+            .choices
+            .into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .ok_or(DeepSeekError::EmptyResponse)?;
+
+        Ok(ChatCompletion {
+            content: message.content,
+            reasoning: message.reasoning_content,
+            tool_calls: message.tool_calls,
+            usage,
+        })
+    }
+}
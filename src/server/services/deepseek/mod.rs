@@ -1,8 +1,12 @@
+mod error;
 mod methods;
+mod retry;
 mod service;
 mod streaming;
 mod types;
 
+pub use error::DeepSeekError;
+pub use retry::RetryPolicy;
 pub use service::DeepSeekService;
 pub use streaming::*;
 pub use types::*;
@@ -0,0 +1,72 @@
+use rand::Rng;
+use std::time::Duration;
+
+use super::error::DeepSeekError;
+
+/// How often and how patiently failed requests are retried.
+///
+/// Only retryable errors (rate limits, 5xx, timeouts and network failures) are
+/// retried. The delay doubles with each attempt, with jitter, unless the server
+/// sent a `Retry-After`; one longer than `max_delay` isn't retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The default policy with the number of retries taken from `DEEPSEEK_MAX_RETRIES`.
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Some(retries) = std::env::var("DEEPSEEK_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+        {
+            policy.max_attempts = retries + 1;
+        }
+        policy
+    }
+
+    /// The delay before the next attempt, or `None` when `error` shouldn't be retried.
+    /// `attempt` is the number of attempts made so far.
+    pub(crate) fn next_delay(&self, attempt: u32, error: &DeepSeekError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !error.is_retryable() {
+            return None;
+        }
+
+        // Retrying before the server asked would be refused again, so a wait
+        // past `max_delay` gives up with the error instead
+        if let Some(retry_after) = error.retry_after() {
+            return (retry_after <= self.max_delay).then_some(retry_after);
+        }
+
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        // Equal jitter: half the backoff plus a random share of the other half
+        let half = exponential / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        Some(half + Duration::from_millis(jitter))
+    }
+}
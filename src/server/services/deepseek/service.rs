@@ -2,13 +2,23 @@ use reqwest::{Client, ClientBuilder};
 use std::time::Duration;
 use tracing::info;
 
-use super::types::{FunctionDefinition, Tool};
+use super::retry::RetryPolicy;
+use super::types::{
+    ChatMessage, ChatRequest, FunctionDefinition, GenerationOptions, StreamOptions, Tool,
+};
+
+pub(crate) const DEFAULT_CHAT_MODEL: &str = "deepseek-chat";
+pub(crate) const DEFAULT_REASONER_MODEL: &str = "deepseek-reasoner";
+pub(crate) const DEFAULT_TEMPERATURE: f32 = 0.7;
 
 #[derive(Debug, Clone)]
 pub struct DeepSeekService {
     pub(crate) client: Client,
     pub(crate) api_key: String,
     pub(crate) base_url: String,
+    pub(crate) chat_model: String,
+    pub(crate) reasoner_model: String,
+    pub(crate) retry_policy: RetryPolicy,
 }
 
 impl DeepSeekService {
@@ -27,6 +37,9 @@ impl DeepSeekService {
             client,
             api_key,
             base_url,
+            chat_model: DEFAULT_CHAT_MODEL.to_string(),
+            reasoner_model: DEFAULT_REASONER_MODEL.to_string(),
+            retry_policy: RetryPolicy::from_env(),
         }
     }
 
@@ -42,6 +55,59 @@ impl DeepSeekService {
             client,
             api_key,
             base_url,
+            chat_model: DEFAULT_CHAT_MODEL.to_string(),
+            reasoner_model: DEFAULT_REASONER_MODEL.to_string(),
+            retry_policy: RetryPolicy::from_env(),
+        }
+    }
+
+    /// Overrides the model names sent for regular and reasoning requests.
+    pub fn with_models(mut self, chat_model: String, reasoner_model: String) -> Self {
+        self.chat_model = chat_model;
+        self.reasoner_model = reasoner_model;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub(crate) fn model_for(&self, use_reasoner: bool) -> &str {
+        if use_reasoner {
+            &self.reasoner_model
+        } else {
+            &self.chat_model
+        }
+    }
+
+    /// Builds a request without tools, applying `options` over the service defaults.
+    pub(crate) fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        stream: bool,
+        options: &GenerationOptions,
+    ) -> ChatRequest {
+        let model = options
+            .model
+            .clone()
+            .unwrap_or_else(|| self.model_for(options.use_reasoner).to_string());
+
+        ChatRequest {
+            model,
+            messages,
+            stream,
+            temperature: options.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            top_p: options.top_p,
+            max_tokens: options.max_tokens,
+            stop: options.stop.clone(),
+            seed: options.seed,
+            response_format: options.response_format.clone(),
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            tools: None,
+            tool_choice: None,
         }
     }
 
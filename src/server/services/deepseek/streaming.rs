@@ -1,5 +1,8 @@
-use crate::server::services::deepseek::types::ToolCallResponse;
+use crate::server::services::deepseek::error::DeepSeekError;
+use crate::server::services::deepseek::types::{FunctionCallResponse, ToolCallResponse, Usage};
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use tracing::warn;
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct StreamChoice {
@@ -12,19 +15,114 @@ pub(crate) struct StreamChoice {
 pub(crate) struct StreamDelta {
     pub content: Option<String>,
     pub reasoning_content: Option<String>,
-    pub tool_calls: Option<Vec<ToolCallResponse>>,
+    pub tool_calls: Option<Vec<StreamToolCallDelta>>,
     pub role: Option<String>,
 }
 
+/// One fragment of a tool call. Only the first fragment for an index carries the
+/// id and function name; later ones append to `function.arguments`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct StreamToolCallDelta {
+    #[serde(default)]
+    pub index: usize,
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub tool_type: Option<String>,
+    pub function: Option<StreamFunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct StreamFunctionDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct StreamResponse {
+    /// Empty on the final usage chunk.
+    #[serde(default)]
     pub choices: Vec<StreamChoice>,
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Clone)]
 pub enum StreamUpdate {
     Content(String),
     Reasoning(String),
+    /// Fully assembled tool calls, sent once the model finishes its turn.
     ToolCalls(Vec<ToolCallResponse>),
+    /// Token usage for the whole stream, sent just before `Done`.
+    Usage(Usage),
+    /// The request failed after retries, or the stream broke off. `Done` follows.
+    Error(DeepSeekError),
     Done,
 }
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: Option<String>,
+    tool_type: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// Collects streamed tool-call fragments by index until the turn is finished.
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallAccumulator {
+    calls: BTreeMap<usize, PartialToolCall>,
+}
+
+impl ToolCallAccumulator {
+    pub fn push(&mut self, delta: &StreamToolCallDelta) {
+        let call = self.calls.entry(delta.index).or_default();
+
+        if let Some(id) = &delta.id {
+            call.id = Some(id.clone());
+        }
+        if let Some(tool_type) = &delta.tool_type {
+            call.tool_type = Some(tool_type.clone());
+        }
+        if let Some(function) = &delta.function {
+            if let Some(name) = &function.name {
+                call.name.push_str(name);
+            }
+            if let Some(arguments) = &function.arguments {
+                call.arguments.push_str(arguments);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Drains the collected fragments into complete tool calls, ordered by index.
+    pub fn finish(&mut self) -> Vec<ToolCallResponse> {
+        std::mem::take(&mut self.calls)
+            .into_iter()
+            .map(|(index, call)| {
+                let arguments = if call.arguments.trim().is_empty() {
+                    "{}".to_string()
+                } else {
+                    call.arguments
+                };
+
+                if serde_json::from_str::<serde_json::Value>(&arguments).is_err() {
+                    warn!(
+                        "Streamed arguments for tool call {} ({}) are not valid JSON: {}",
+                        index, call.name, arguments
+                    );
+                }
+
+                ToolCallResponse {
+                    id: call.id.unwrap_or_else(|| format!("call_{}", index)),
+                    tool_type: call.tool_type.unwrap_or_else(|| "function".to_string()),
+                    function: FunctionCallResponse {
+                        name: call.name,
+                        arguments,
+                    },
+                }
+            })
+            .collect()
+    }
+}
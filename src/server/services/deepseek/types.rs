@@ -31,17 +31,141 @@ impl From<AssistantMessage> for ChatMessage {
     }
 }
 
+/// Per-request model and sampling settings. Unset fields fall back to the
+/// service defaults (the chat or reasoner model, temperature 0.7).
+#[derive(Debug, Clone, Default)]
+pub struct GenerationOptions {
+    pub use_reasoner: bool,
+    /// Overrides the model picked by `use_reasoner`.
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<i32>,
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+    pub response_format: Option<ResponseFormat>,
+}
+
+impl GenerationOptions {
+    pub fn reasoner() -> Self {
+        Self {
+            use_reasoner: true,
+            ..Default::default()
+        }
+    }
+
+    /// Temperature 0, for routing and other decisions that should be repeatable.
+    pub fn deterministic() -> Self {
+        Self {
+            temperature: Some(0.0),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<bool> for GenerationOptions {
+    fn from(use_reasoner: bool) -> Self {
+        Self {
+            use_reasoner,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    /// Structured output, supported by OpenAI-compatible servers but not DeepSeek.
+    JsonSchema {
+        json_schema: serde_json::Value,
+    },
+}
+
 #[derive(Debug, Serialize)]
 pub(crate) struct ChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub stream: bool,
     pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
     pub max_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
     pub tools: Option<Vec<Tool>>,
     pub tool_choice: Option<ToolChoice>,
 }
 
+#[derive(Debug, Serialize)]
+pub(crate) struct StreamOptions {
+    /// Ask for a final chunk carrying the usage of the whole stream.
+    pub include_usage: bool,
+}
+
+/// Token counts reported by the API for one completion.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+    #[serde(default)]
+    pub total_tokens: u32,
+    /// Prompt tokens served from DeepSeek's context cache.
+    #[serde(default)]
+    pub prompt_cache_hit_tokens: u32,
+    #[serde(default)]
+    pub prompt_cache_miss_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u32,
+}
+
+impl Usage {
+    pub fn reasoning_tokens(&self) -> u32 {
+        self.completion_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.reasoning_tokens)
+    }
+}
+
+impl std::ops::AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.prompt_cache_hit_tokens += other.prompt_cache_hit_tokens;
+        self.prompt_cache_miss_tokens += other.prompt_cache_miss_tokens;
+
+        let reasoning_tokens = self.reasoning_tokens() + other.reasoning_tokens();
+        if reasoning_tokens > 0 {
+            self.completion_tokens_details = Some(CompletionTokensDetails { reasoning_tokens });
+        }
+    }
+}
+
+/// The assistant's reply to a non-streaming request.
+#[derive(Debug, Clone, Default)]
+pub struct ChatCompletion {
+    pub content: String,
+    pub reasoning: Option<String>,
+    pub tool_calls: Option<Vec<ToolCallResponse>>,
+    pub usage: Option<Usage>,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct ChatChoice {
     pub message: ChatResponseMessage,
@@ -58,6 +182,7 @@ pub struct ChatResponseMessage {
 #[derive(Debug, Deserialize)]
 pub(crate) struct ChatResponse {
     pub choices: Vec<ChatChoice>,
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -1,20 +1,144 @@
+use super::github_rate_limit::{
+    rate_limit_resource, RateLimit, RateLimitExceeded, CORE_RESOURCE, DEFAULT_MAX_RATE_LIMIT_WAIT,
+};
+use super::github_types::{
+    FileChange, GitHubUser, IssueComment, Label, NewPullRequest, PullRequest, PullRequestFile,
+    Repository, TimelineEvent,
+};
 use anyhow::Result;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_NONE_MATCH},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
 
+/// Largest page GitHub serves.
+pub const MAX_PAGE_SIZE: usize = 100;
+/// Items listed when the caller doesn't say how many.
+pub const DEFAULT_LIST_LIMIT: usize = 30;
+/// Most items a listing collects across pages.
+pub const MAX_LIST_LIMIT: usize = 1000;
+/// GET responses kept for conditional requests.
+pub const MAX_CACHED_RESPONSES: usize = 256;
+
+/// Clones share the rate limits and the response cache.
 #[derive(Debug, Clone)]
 pub struct GitHubService {
     client: Client,
     token: String,
+    base_url: String,
+    max_rate_limit_wait: Duration,
+    /// By the resource they're for, e.g. `core` or `search`.
+    rate_limits: Arc<Mutex<HashMap<String, RateLimit>>>,
+    cache: Arc<Mutex<ResponseCache>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// An issue, or a pull request seen through the issues API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitHubIssue {
     pub number: i32,
     pub title: String,
     pub body: Option<String>,
     pub state: String,
     pub html_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<GitHubUser>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Label>,
+    /// How many comments there are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// Set when the issue is a pull request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull_request: Option<Value>,
+    /// How the author is related to the repository, e.g. `OWNER` or `NONE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_association: Option<String>,
+}
+
+impl GitHubIssue {
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+}
+
+/// An issue with its comments, oldest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct IssueThread {
+    #[serde(flatten)]
+    pub issue: GitHubIssue,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub discussion: Vec<IssueComment>,
+}
+
+impl IssueThread {
+    /// The issue and its discussion as plain text for a prompt.
+    pub fn for_prompt(&self) -> String {
+        let mut text = format!(
+            "Issue #{}: {}\n\n{}",
+            self.issue.number,
+            self.issue.title,
+            self.issue
+                .body
+                .as_deref()
+                .unwrap_or("No description provided")
+        );
+        if !self.discussion.is_empty() {
+            text.push_str("\n\nDiscussion:");
+            for comment in &self.discussion {
+                text.push_str(&format!(
+                    "\n\n@{} ({}):\n{}",
+                    comment.user.as_ref().map_or("ghost", |user| &user.login),
+                    comment.created_at,
+                    comment.body.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+        text
+    }
+}
+
+/// Which issues [`GitHubService::list_issues`] returns.
+#[derive(Debug, Clone, Default)]
+pub struct IssueFilter {
+    /// `open` (GitHub's default), `closed` or `all`.
+    pub state: Option<String>,
+    /// Issues with all of these labels.
+    pub labels: Vec<String>,
+    pub assignee: Option<String>,
+    pub creator: Option<String>,
+    /// Only issues updated at or after this ISO 8601 time.
+    pub since: Option<String>,
+    /// At most this many, [`DEFAULT_LIST_LIMIT`] when unset.
+    pub limit: Option<usize>,
+}
+
+/// Matches of [`GitHubService::search_issues`].
+#[derive(Debug, Clone, Serialize)]
+pub struct IssueSearch {
+    /// Matches in all, including ones past the limit.
+    pub total_count: u64,
+    /// GitHub gave up before finding every match.
+    pub incomplete_results: bool,
+    pub items: Vec<GitHubIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPage {
+    total_count: u64,
+    incomplete_results: bool,
+    items: Vec<GitHubIssue>,
 }
 
 #[derive(Debug, Serialize)]
@@ -23,12 +147,49 @@ struct CommentPayload {
 }
 
 impl GitHubService {
+    /// Talks to `GITHUB_API_URL`, e.g. `https://github.example.com/api/v3`
+    /// for GitHub Enterprise Server, or `https://api.github.com`. Waits up to
+    /// `GITHUB_RATE_LIMIT_WAIT_SECS` for an exhausted rate limit to reset.
     pub fn new(token: Option<String>) -> Result<Self> {
         let token = token.ok_or_else(|| anyhow::anyhow!("GitHub token is required"))?;
-        Ok(Self {
+        let base_url = std::env::var("GITHUB_API_URL")
+            .unwrap_or_else(|_| "https://api.github.com".to_string());
+        let mut service = Self::with_base_url(token, base_url);
+        if let Some(wait) = std::env::var("GITHUB_RATE_LIMIT_WAIT_SECS")
+            .ok()
+            .and_then(|wait| wait.trim().parse().ok())
+        {
+            service = service.with_max_rate_limit_wait(Duration::from_secs(wait));
+        }
+        Ok(service)
+    }
+
+    pub fn with_base_url(token: String, base_url: String) -> Self {
+        Self {
             client: Client::new(),
             token,
-        })
+            base_url: base_url.trim_end_matches('/').to_string(),
+            max_rate_limit_wait: DEFAULT_MAX_RATE_LIMIT_WAIT,
+            rate_limits: Arc::default(),
+            cache: Arc::default(),
+        }
+    }
+
+    /// Requests that would wait longer than `wait` for a rate limit to reset
+    /// fail with [`RateLimitExceeded`] instead.
+    pub fn with_max_rate_limit_wait(mut self, wait: Duration) -> Self {
+        self.max_rate_limit_wait = wait;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The quota left for `resource`, e.g. `core` or `search`, as of the
+    /// latest response that counted against it, if GitHub reported it.
+    pub fn rate_limit(&self, resource: &str) -> Option<RateLimit> {
+        self.rate_limits.lock().unwrap().get(resource).copied()
     }
 
     pub async fn get_issue(
@@ -38,27 +199,169 @@ impl GitHubService {
         issue_number: i32,
     ) -> Result<GitHubIssue> {
         let url = format!(
-            "https://api.github.com/repos/{}/{}/issues/{}",
-            owner, repo, issue_number
+            "{}/repos/{}/{}/issues/{}",
+            self.base_url, owner, repo, issue_number
         );
+        self.get_json(&url).await
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.token))
-            .header("User-Agent", "OpenAgents")
-            .send()
-            .await?;
+    /// The issue and every comment on it.
+    pub async fn get_issue_thread(
+        &self,
+        owner: &str,
+        repo: &str,
+        issue_number: i32,
+    ) -> Result<IssueThread> {
+        let issue = self.get_issue(owner, repo, issue_number).await?;
+        // The issue says how many comments there are, so skip the request
+        // when there are none
+        let discussion = if issue.comments.unwrap_or_default() == 0 {
+            Vec::new()
+        } else {
+            self.get_issue_comments(owner, repo, issue_number).await?
+        };
+        Ok(IssueThread { issue, discussion })
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "GitHub API request failed: {}",
-                response.status()
-            ));
+    /// Comments on an issue or pull request, oldest first.
+    pub async fn get_issue_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        issue_number: i32,
+    ) -> Result<Vec<IssueComment>> {
+        let url = format!(
+            "{}/repos/{}/{}/issues/{}/comments",
+            self.base_url, owner, repo, issue_number
+        );
+        self.get_list(&url, Vec::new(), MAX_LIST_LIMIT).await
+    }
+
+    /// Everything that happened to an issue: comments, labels, references,
+    /// closing and reopening.
+    pub async fn get_issue_timeline(
+        &self,
+        owner: &str,
+        repo: &str,
+        issue_number: i32,
+    ) -> Result<Vec<TimelineEvent>> {
+        let url = format!(
+            "{}/repos/{}/{}/issues/{}/timeline",
+            self.base_url, owner, repo, issue_number
+        );
+        self.get_list(&url, Vec::new(), MAX_LIST_LIMIT).await
+    }
+
+    /// Issues and pull requests matching `filter`, most recently created
+    /// first.
+    pub async fn list_issues(
+        &self,
+        owner: &str,
+        repo: &str,
+        filter: &IssueFilter,
+    ) -> Result<Vec<GitHubIssue>> {
+        let url = format!("{}/repos/{}/{}/issues", self.base_url, owner, repo);
+        let mut query = Vec::new();
+        if let Some(state) = &filter.state {
+            query.push(("state", state.clone()));
+        }
+        if !filter.labels.is_empty() {
+            query.push(("labels", filter.labels.join(",")));
         }
+        if let Some(assignee) = &filter.assignee {
+            query.push(("assignee", assignee.clone()));
+        }
+        if let Some(creator) = &filter.creator {
+            query.push(("creator", creator.clone()));
+        }
+        if let Some(since) = &filter.since {
+            query.push(("since", since.clone()));
+        }
+        let limit = filter.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+        self.get_list(&url, query, limit).await
+    }
+
+    /// Issues and pull requests matching a GitHub search query, e.g.
+    /// `repo:owner/name is:issue is:open panic`.
+    pub async fn search_issues(&self, query: &str, limit: usize) -> Result<IssueSearch> {
+        let limit = limit.clamp(1, MAX_LIST_LIMIT);
+        let mut url = format!("{}/search/issues", self.base_url);
+        let mut params = vec![
+            ("q", query.to_string()),
+            ("per_page", limit.min(MAX_PAGE_SIZE).to_string()),
+        ];
+        let mut search = IssueSearch {
+            total_count: 0,
+            incomplete_results: false,
+            items: Vec::new(),
+        };
+        loop {
+            let (page, next) = self.get_page::<SearchPage>(&url, &params).await?;
+            search.total_count = page.total_count;
+            search.incomplete_results |= page.incomplete_results;
+            search.items.extend(page.items);
+            match next {
+                Some(next) if search.items.len() < limit => {
+                    url = next;
+                    params.clear();
+                }
+                _ => break,
+            }
+        }
+        search.items.truncate(limit);
+        Ok(search)
+    }
+
+    pub async fn get_repository(&self, owner: &str, repo: &str) -> Result<Repository> {
+        let url = format!("{}/repos/{}/{}", self.base_url, owner, repo);
+        self.get_json(&url).await
+    }
+
+    pub async fn list_labels(&self, owner: &str, repo: &str) -> Result<Vec<Label>> {
+        let url = format!("{}/repos/{}/{}/labels", self.base_url, owner, repo);
+        self.get_list(&url, Vec::new(), MAX_LIST_LIMIT).await
+    }
 
-        let issue = response.json::<GitHubIssue>().await?;
-        Ok(issue)
+    pub async fn get_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        pull_number: i32,
+    ) -> Result<PullRequest> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}",
+            self.base_url, owner, repo, pull_number
+        );
+        self.get_json(&url).await
+    }
+
+    /// The pull request's changes as a unified diff.
+    pub async fn get_pull_request_diff(
+        &self,
+        owner: &str,
+        repo: &str,
+        pull_number: i32,
+    ) -> Result<String> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}",
+            self.base_url, owner, repo, pull_number
+        );
+        let (diff, _) = self.fetch(&url, &[], "application/vnd.github.diff").await?;
+        Ok(String::from_utf8_lossy(&diff).into_owned())
+    }
+
+    /// The files a pull request changes, with their patches.
+    pub async fn get_pull_request_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        pull_number: i32,
+    ) -> Result<Vec<PullRequestFile>> {
+        let url = format!(
+            "{}/repos/{}/{}/pulls/{}/files",
+            self.base_url, owner, repo, pull_number
+        );
+        self.get_list(&url, Vec::new(), MAX_LIST_LIMIT).await
     }
 
     pub async fn post_comment(
@@ -69,8 +372,8 @@ impl GitHubService {
         comment: &str,
     ) -> Result<()> {
         let url = format!(
-            "https://api.github.com/repos/{}/{}/issues/{}/comments",
-            owner, repo, issue_number
+            "{}/repos/{}/{}/issues/{}/comments",
+            self.base_url, owner, repo, issue_number
         );
 
         let payload = CommentPayload {
@@ -78,13 +381,14 @@ impl GitHubService {
         };
 
         let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.token))
-            .header("User-Agent", "OpenAgents")
-            .header("Accept", "application/vnd.github.v3+json")
-            .json(&payload)
-            .send()
+            .send(
+                self.client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", self.token))
+                    .header("User-Agent", "OpenAgents")
+                    .header("Accept", "application/vnd.github.v3+json")
+                    .json(&payload),
+            )
             .await?;
 
         if !response.status().is_success() {
@@ -96,6 +400,442 @@ impl GitHubService {
 
         Ok(())
     }
+
+    /// The commit a branch points at.
+    pub async fn get_branch_sha(&self, owner: &str, repo: &str, branch: &str) -> Result<String> {
+        let url = format!(
+            "{}/repos/{}/{}/git/ref/heads/{}",
+            self.base_url, owner, repo, branch
+        );
+        let reference: Value = self.get_json(&url).await?;
+        sha_of(&reference["object"])
+    }
+
+    /// Creates `branch` pointing at the commit `sha`.
+    pub async fn create_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        sha: &str,
+    ) -> Result<()> {
+        let url = format!("{}/repos/{}/{}/git/refs", self.base_url, owner, repo);
+        let payload = json!({ "ref": format!("refs/heads/{}", branch), "sha": sha });
+        self.send_json::<Value>(Method::POST, &url, &payload)
+            .await?;
+        Ok(())
+    }
+
+    /// Points `branch` at the commit `sha`, creating it, or moving it even if
+    /// that drops commits when it's left over from an earlier run.
+    pub async fn set_branch(&self, owner: &str, repo: &str, branch: &str, sha: &str) -> Result<()> {
+        match self.create_branch(owner, repo, branch, sha).await {
+            // GitHub refuses to create a ref that exists
+            Err(e)
+                if e.downcast_ref::<GitHubApiError>()
+                    .is_some_and(|e| e.status == StatusCode::UNPROCESSABLE_ENTITY) =>
+            {
+                let url = format!(
+                    "{}/repos/{}/{}/git/refs/heads/{}",
+                    self.base_url, owner, repo, branch
+                );
+                self.send_json::<Value>(Method::PATCH, &url, &json!({ "sha": sha, "force": true }))
+                    .await?;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Commits `changes` on top of `branch` with the git data API and moves
+    /// the branch to the new commit, whose sha is returned.
+    pub async fn commit_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        message: &str,
+        changes: &[FileChange],
+    ) -> Result<String> {
+        let repo_url = format!("{}/repos/{}/{}", self.base_url, owner, repo);
+        let parent = self.get_branch_sha(owner, repo, branch).await?;
+        let parent_commit: Value = self
+            .get_json(&format!("{}/git/commits/{}", repo_url, parent))
+            .await?;
+        let base_tree = sha_of(&parent_commit["tree"])?;
+
+        let mut entries = Vec::with_capacity(changes.len());
+        for change in changes {
+            let mode = format!("{:o}", change.mode);
+            let mut entry = json!({ "path": change.path, "mode": mode, "type": "blob" });
+            match &change.content {
+                // A null sha removes the path from the tree
+                None => entry["sha"] = Value::Null,
+                Some(content) => match std::str::from_utf8(content) {
+                    Ok(text) => entry["content"] = text.into(),
+                    // Inline content has to be UTF-8, so binary files go up as blobs
+                    Err(_) => {
+                        let blob: Value = self
+                            .send_json(
+                                Method::POST,
+                                &format!("{}/git/blobs", repo_url),
+                                &json!({ "content": STANDARD.encode(content), "encoding": "base64" }),
+                            )
+                            .await?;
+                        entry["sha"] = sha_of(&blob)?.into();
+                    }
+                },
+            }
+            entries.push(entry);
+        }
+
+        let tree: Value = self
+            .send_json(
+                Method::POST,
+                &format!("{}/git/trees", repo_url),
+                &json!({ "base_tree": base_tree, "tree": entries }),
+            )
+            .await?;
+        let commit: Value = self
+            .send_json(
+                Method::POST,
+                &format!("{}/git/commits", repo_url),
+                &json!({ "message": message, "tree": sha_of(&tree)?, "parents": [parent] }),
+            )
+            .await?;
+        let sha = sha_of(&commit)?;
+        self.send_json::<Value>(
+            Method::PATCH,
+            &format!("{}/git/refs/heads/{}", repo_url, branch),
+            &json!({ "sha": sha }),
+        )
+        .await?;
+        Ok(sha)
+    }
+
+    pub async fn create_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        pull_request: &NewPullRequest,
+    ) -> Result<PullRequest> {
+        let url = format!("{}/repos/{}/{}/pulls", self.base_url, owner, repo);
+        self.send_json(Method::POST, &url, pull_request).await
+    }
+
+    /// A GET asking for the `accept` media type, e.g. a diff instead of JSON.
+    fn get_as(&self, url: &str, accept: &str) -> RequestBuilder {
+        self.client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("User-Agent", "OpenAgents")
+            .header("Accept", accept)
+    }
+
+    /// Sends `payload` as JSON and parses the JSON response.
+    async fn send_json<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: &str,
+        payload: &(impl Serialize + ?Sized),
+    ) -> Result<T> {
+        let request = self
+            .client
+            .request(method, url)
+            .header("Authorization", format!("Bearer {}", self.token))
+            .header("User-Agent", "OpenAgents")
+            .header("Accept", "application/vnd.github+json")
+            .json(payload);
+        Ok(check(self.send(request).await?).await?.json().await?)
+    }
+
+    /// Sends `request`, keeping track of the rate limit of each resource.
+    /// When the quota the request counts against is used up, or a secondary
+    /// rate limit is hit, waits for it to reset and tries once more, unless
+    /// that would take longer than `max_rate_limit_wait`.
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let resource = request
+            .try_clone()
+            .and_then(|request| request.build().ok())
+            .and_then(|request| {
+                request
+                    .url()
+                    .as_str()
+                    .strip_prefix(&self.base_url)
+                    .map(rate_limit_resource)
+            })
+            .unwrap_or(CORE_RESOURCE);
+        if let Some(resets_in) = self
+            .rate_limit(resource)
+            .and_then(|limit| limit.exhausted_for())
+        {
+            self.wait_out(RateLimitExceeded {
+                secondary: false,
+                retry_after: resets_in,
+            })
+            .await?;
+        }
+
+        let mut waited = false;
+        loop {
+            let attempt = request
+                .try_clone()
+                .ok_or_else(|| anyhow::anyhow!("GitHub request can't be retried"))?;
+            let response = attempt.send().await?;
+            if let Some(limit) = RateLimit::from_headers(response.headers()) {
+                let reported = RateLimit::resource(response.headers()).unwrap_or(resource);
+                self.rate_limits
+                    .lock()
+                    .unwrap()
+                    .insert(reported.to_string(), limit);
+            }
+            let status = response.status();
+            if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            match RateLimitExceeded::from_response(status, &headers, &body) {
+                Some(exceeded) if !waited => {
+                    self.wait_out(exceeded).await?;
+                    waited = true;
+                }
+                Some(exceeded) => return Err(exceeded.into()),
+                None => return Err(GitHubApiError::from_body(status, &body).into()),
+            }
+        }
+    }
+
+    async fn wait_out(&self, exceeded: RateLimitExceeded) -> Result<()> {
+        if exceeded.retry_after > self.max_rate_limit_wait {
+            return Err(exceeded.into());
+        }
+        warn!("{}, waiting", exceeded);
+        tokio::time::sleep(exceeded.retry_after).await;
+        Ok(())
+    }
+
+    /// The body of a GET asking for `accept`, and the URL of the next page
+    /// from the `Link` header. Responses with an `ETag` are cached and
+    /// revalidated with `If-None-Match`; GitHub doesn't count a
+    /// `304 Not Modified` against the rate limit.
+    async fn fetch(
+        &self,
+        url: &str,
+        query: &[(&str, String)],
+        accept: &str,
+    ) -> Result<(Bytes, Option<String>)> {
+        let key = cache_key(url, query, accept);
+        let etag = self.cache.lock().unwrap().etag(&key);
+        let mut request = self.get_as(url, accept).query(query);
+        if let Some(etag) = &etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = check(self.send(request).await?).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+                return Ok((cached.body.clone(), cached.next.clone()));
+            }
+            anyhow::bail!(
+                "GitHub answered 304 Not Modified for {} without a cached copy",
+                url
+            );
+        }
+        let next = next_page(response.headers());
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await?;
+        if let Some(etag) = etag {
+            self.cache.lock().unwrap().insert(
+                key,
+                CachedResponse {
+                    etag,
+                    body: body.clone(),
+                    next: next.clone(),
+                },
+            );
+        }
+        Ok((body, next))
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        Ok(self.get_page(url, &[]).await?.0)
+    }
+
+    /// One page and the URL of the next, from the `Link` header.
+    async fn get_page<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, String)],
+    ) -> Result<(T, Option<String>)> {
+        let (body, next) = self
+            .fetch(url, query, "application/vnd.github+json")
+            .await?;
+        Ok((serde_json::from_slice(&body)?, next))
+    }
+
+    /// Up to `limit` items of a listing, following `Link` headers from page to
+    /// page.
+    async fn get_list<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        mut query: Vec<(&str, String)>,
+        limit: usize,
+    ) -> Result<Vec<T>> {
+        let limit = limit.clamp(1, MAX_LIST_LIMIT);
+        query.push(("per_page", limit.min(MAX_PAGE_SIZE).to_string()));
+        let mut url = url.to_string();
+        let mut items = Vec::new();
+        loop {
+            let (page, next) = self.get_page::<Vec<T>>(&url, &query).await?;
+            let empty = page.is_empty();
+            items.extend(page);
+            match next {
+                // The next URL carries the query along
+                Some(next) if items.len() < limit && !empty => {
+                    url = next;
+                    query.clear();
+                }
+                _ => break,
+            }
+        }
+        items.truncate(limit);
+        Ok(items)
+    }
+}
+
+async fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(GitHubApiError::from_body(status, &body).into())
+}
+
+/// A GitHub API request that failed for a reason other than a rate limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitHubApiError {
+    pub status: StatusCode,
+    /// GitHub's `message`, followed by the `errors` of a validation failure,
+    /// e.g. `Reference already exists`.
+    pub message: Option<String>,
+}
+
+impl GitHubApiError {
+    fn from_body(status: StatusCode, body: &str) -> Self {
+        let body: Value = serde_json::from_str(body).unwrap_or_default();
+        // Each error is a string, or an object with a message or a code
+        let errors: Vec<String> = body["errors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|error| {
+                let code = || match (error["field"].as_str(), error["code"].as_str()?) {
+                    (Some(field), code) => Some(format!("{} {}", field, code)),
+                    (None, code) => Some(code.to_string()),
+                };
+                error
+                    .as_str()
+                    .or_else(|| error["message"].as_str())
+                    .map(str::to_string)
+                    .or_else(code)
+            })
+            .collect();
+        let message = match (body["message"].as_str(), errors.is_empty()) {
+            (Some(message), true) => Some(message.to_string()),
+            (Some(message), false) => Some(format!("{} ({})", message, errors.join("; "))),
+            (None, false) => Some(errors.join("; ")),
+            (None, true) => None,
+        };
+        Self { status, message }
+    }
+}
+
+impl fmt::Display for GitHubApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GitHub API request failed: {}", self.status)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GitHubApiError {}
+
+#[derive(Debug)]
+struct CachedResponse {
+    etag: String,
+    body: Bytes,
+    next: Option<String>,
+}
+
+/// The latest GET responses with an `ETag`, oldest dropped first.
+#[derive(Debug, Default)]
+struct ResponseCache {
+    entries: HashMap<String, CachedResponse>,
+    order: VecDeque<String>,
+}
+
+impl ResponseCache {
+    fn etag(&self, key: &str) -> Option<String> {
+        self.entries.get(key).map(|cached| cached.etag.clone())
+    }
+
+    fn get(&self, key: &str) -> Option<&CachedResponse> {
+        self.entries.get(key)
+    }
+
+    fn insert(&mut self, key: String, response: CachedResponse) {
+        if self.entries.insert(key.clone(), response).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > MAX_CACHED_RESPONSES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn cache_key(url: &str, query: &[(&str, String)], accept: &str) -> String {
+    let query: Vec<String> = query
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    format!("{} {}?{}", accept, url, query.join("&"))
+}
+
+/// The `sha` field of a git object, reference target, tree or commit.
+fn sha_of(object: &Value) -> Result<String> {
+    object["sha"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("GitHub response is missing a sha"))
+}
+
+/// The `rel="next"` URL of a `Link` header, as in
+/// `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`.
+fn next_page(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get("link")?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.trim().split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
 }
 
 pub async fn post_github_comment(
@@ -0,0 +1,128 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a request waits for an exhausted rate limit to reset before
+/// failing with [`RateLimitExceeded`].
+pub const DEFAULT_MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+/// GitHub asks clients that hit a secondary rate limit without a
+/// `Retry-After` to wait at least a minute.
+const SECONDARY_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+/// The quota most of the REST API counts against.
+pub const CORE_RESOURCE: &str = "core";
+
+/// The quota a request to `path`, relative to the API base URL, counts
+/// against, as GitHub names it in `X-RateLimit-Resource`. Searches have
+/// quotas of their own, much smaller than the core one.
+pub fn rate_limit_resource(path: &str) -> &'static str {
+    let path = path.split('?').next().unwrap_or_default();
+    if path.starts_with("/search/code") {
+        "code_search"
+    } else if path.starts_with("/search/") {
+        "search"
+    } else if path.starts_with("/graphql") {
+        "graphql"
+    } else {
+        CORE_RESOURCE
+    }
+}
+
+/// A request quota, from the `X-RateLimit-*` headers of the latest response
+/// that counted against it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub remaining: u32,
+    /// When the quota resets, in seconds since the Unix epoch.
+    pub reset: u64,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse().ok();
+        Some(Self {
+            limit: header("x-ratelimit-limit")? as u32,
+            remaining: header("x-ratelimit-remaining")? as u32,
+            reset: header("x-ratelimit-reset")?,
+        })
+    }
+
+    /// The quota `headers` report on, from `X-RateLimit-Resource`.
+    pub fn resource(headers: &HeaderMap) -> Option<&str> {
+        headers.get("x-ratelimit-resource")?.to_str().ok()
+    }
+
+    pub fn resets_in(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Duration::from_secs(self.reset.saturating_sub(now))
+    }
+
+    /// The time left before requests are accepted again, when the quota is
+    /// used up.
+    pub fn exhausted_for(&self) -> Option<Duration> {
+        let resets_in = self.resets_in();
+        (self.remaining == 0 && !resets_in.is_zero()).then_some(resets_in)
+    }
+}
+
+/// GitHub refused a request, or would have, because a rate limit is used up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitExceeded {
+    /// Whether this is a secondary rate limit, e.g. for too many concurrent
+    /// requests or too much content created, rather than the hourly quota.
+    pub secondary: bool,
+    /// How long until requests are accepted again.
+    pub retry_after: Duration,
+}
+
+impl RateLimitExceeded {
+    /// Why a response failed, if it was a rate limit. GitHub answers with 403
+    /// or 429, and tells rate limits apart from missing permissions through
+    /// the headers or the error message.
+    pub(crate) fn from_response(
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Option<Self> {
+        if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+            return None;
+        }
+        // The hourly quota shows up in the headers; anything else is a
+        // secondary rate limit
+        let quota = RateLimit::from_headers(headers).filter(|limit| limit.remaining == 0);
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+            .map(Duration::from_secs);
+        let secondary = body.to_ascii_lowercase().contains("secondary rate limit")
+            || (quota.is_none()
+                && (retry_after.is_some() || status == StatusCode::TOO_MANY_REQUESTS));
+        if quota.is_none() && !secondary {
+            return None;
+        }
+        let retry_after = retry_after
+            .or_else(|| quota.filter(|_| !secondary).map(|limit| limit.resets_in()))
+            .unwrap_or(SECONDARY_RATE_LIMIT_WAIT);
+        Some(Self {
+            secondary,
+            retry_after,
+        })
+    }
+}
+
+impl fmt::Display for RateLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GitHub {}rate limit exceeded, retry in {}s",
+            if self.secondary { "secondary " } else { "" },
+            self.retry_after.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for RateLimitExceeded {}
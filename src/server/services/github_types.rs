@@ -1,16 +1,135 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Issue {
-    pub title: String,
-    pub body: String,
-    pub number: i32,
-    pub state: String,
+/// The author or actor of an issue, comment or event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitHubUser {
+    pub login: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Repository {
     pub name: String,
-    pub owner: String,
+    pub full_name: String,
+    pub owner: GitHubUser,
+    #[serde(default)]
     pub description: Option<String>,
+    pub default_branch: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssueComment {
+    pub id: u64,
+    #[serde(default)]
+    pub user: Option<GitHubUser>,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub created_at: String,
+    pub html_url: String,
+    /// How the author is related to the repository, e.g. `MEMBER` or `NONE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_association: Option<String>,
+}
+
+/// One entry of an issue's timeline: a comment, label change, reference,
+/// cross-reference and so on. Which fields are set depends on `event`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<GitHubUser>,
+    /// The author, for `commented` events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<GitHubUser>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_reason: Option<String>,
+}
+
+/// A branch end of a pull request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitRef {
+    #[serde(rename = "ref")]
+    pub branch: String,
+    pub sha: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: i32,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub state: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub user: Option<GitHubUser>,
+    pub head: GitRef,
+    pub base: GitRef,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub merged: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additions: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletions: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_files: Option<u32>,
+    /// How the author is related to the repository, e.g. `OWNER` or `NONE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_association: Option<String>,
+}
+
+/// A file changed by a pull request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PullRequestFile {
+    pub filename: String,
+    /// `added`, `removed`, `modified`, `renamed`, ...
+    pub status: String,
+    pub additions: u32,
+    pub deletions: u32,
+    /// The unified diff hunk, left out by GitHub for binary and huge files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+}
+
+/// A file to write, or delete when `content` is `None`, in a commit made with
+/// [`GitHubService::commit_files`](super::github_issue::GitHubService::commit_files).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    /// The git file mode: `0o100644` for a regular file, `0o100755` for an
+    /// executable and `0o120000` for a symlink, whose content is its target.
+    pub mode: u32,
+    pub content: Option<Vec<u8>>,
+}
+
+/// The fields of a pull request to open.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NewPullRequest {
+    pub title: String,
+    /// The branch holding the changes.
+    pub head: String,
+    /// The branch the changes should be merged into.
+    pub base: String,
+    pub body: String,
+    pub draft: bool,
 }
@@ -0,0 +1,475 @@
+use super::deepseek::GenerationOptions;
+use super::github_issue::{GitHubIssue, GitHubService};
+use super::github_types::{GitHubUser, IssueComment, Label, PullRequest, Repository};
+use super::provider::ChatProvider;
+use crate::repo::{
+    analyze_repository, plan_prompt, post_analysis, run_cargo_tests, test_suggestion_prompt,
+    RepoCache,
+};
+use crate::repomap::generate_repo_map;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+pub const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+pub const EVENT_HEADER: &str = "x-github-event";
+pub const DELIVERY_HEADER: &str = "x-github-delivery";
+/// Delivery IDs remembered to drop redeliveries.
+pub const MAX_REMEMBERED_DELIVERIES: usize = 10_000;
+/// Jobs waiting for the agent worker before deliveries are turned away.
+pub const JOB_QUEUE_CAPACITY: usize = 100;
+/// Author associations whose @-mentions start jobs: people with a say in the
+/// repository, rather than anyone who can comment on it.
+pub const TRUSTED_ASSOCIATIONS: [&str; 3] = ["OWNER", "MEMBER", "COLLABORATOR"];
+
+/// What an agent does for an issue or pull request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AgentAction {
+    /// Comment with an implementation plan.
+    Plan,
+    /// Run the tests, explore the code and comment with suggested changes.
+    Analysis,
+    /// Comment with tests that should cover the issue.
+    SuggestTests,
+}
+
+impl FromStr for AgentAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "plan" => Ok(Self::Plan),
+            "analysis" | "analyze" => Ok(Self::Analysis),
+            "tests" | "test" => Ok(Self::SuggestTests),
+            other => Err(anyhow!(
+                "Unknown agent action {:?}: use plan, analysis or tests",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AgentAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plan => write!(f, "plan"),
+            Self::Analysis => write!(f, "analysis"),
+            Self::SuggestTests => write!(f, "tests"),
+        }
+    }
+}
+
+/// Which webhook events start which agent actions.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// The secret GitHub signs deliveries with.
+    pub secret: String,
+    /// Labels that start an action when added to an issue or pull request.
+    pub label_actions: HashMap<String, AgentAction>,
+    /// The account whose @-mention starts an action, e.g. `@bot analyze`. A
+    /// mention without a known action asks for a plan.
+    pub bot_login: Option<String>,
+    /// Accounts whose mentions start actions even though they aren't owners,
+    /// members or collaborators, in lowercase.
+    pub trusted_users: HashSet<String>,
+}
+
+impl WebhookConfig {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            label_actions: HashMap::new(),
+            bot_login: None,
+            trusted_users: HashSet::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>, action: AgentAction) -> Self {
+        self.label_actions.insert(label.into(), action);
+        self
+    }
+
+    pub fn with_bot_login(mut self, login: impl Into<String>) -> Self {
+        self.bot_login = Some(login.into());
+        self
+    }
+
+    pub fn with_trusted_user(mut self, login: impl Into<String>) -> Self {
+        self.trusted_users
+            .insert(login.into().trim_start_matches('@').to_ascii_lowercase());
+        self
+    }
+
+    /// Whether an @-mention by `user`, related to the repository by
+    /// `association`, may start an action.
+    pub fn trusts(&self, user: Option<&GitHubUser>, association: Option<&str>) -> bool {
+        association.is_some_and(|association| TRUSTED_ASSOCIATIONS.contains(&association))
+            || user.is_some_and(|user| {
+                self.trusted_users
+                    .contains(&user.login.to_ascii_lowercase())
+            })
+    }
+
+    /// Reads `GITHUB_WEBHOOK_SECRET`, `GITHUB_WEBHOOK_LABELS` as
+    /// `label=action,label=action`, `GITHUB_BOT_LOGIN` and
+    /// `GITHUB_WEBHOOK_TRUSTED_USERS` as `login,login`. `None` when no secret
+    /// is set, since unsigned deliveries are never accepted.
+    pub fn from_env() -> Option<Self> {
+        let secret = std::env::var("GITHUB_WEBHOOK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())?;
+        let mut config = Self::new(secret);
+        for entry in std::env::var("GITHUB_WEBHOOK_LABELS")
+            .unwrap_or_default()
+            .split(',')
+        {
+            match entry.trim().rsplit_once('=') {
+                Some((label, action)) => match action.parse() {
+                    Ok(action) => config = config.with_label(label.trim(), action),
+                    Err(e) => warn!("Ignoring GITHUB_WEBHOOK_LABELS entry {}: {}", entry, e),
+                },
+                None if entry.trim().is_empty() => {}
+                None => warn!(
+                    "Ignoring GITHUB_WEBHOOK_LABELS entry without an action: {}",
+                    entry
+                ),
+            }
+        }
+        if let Ok(login) = std::env::var("GITHUB_BOT_LOGIN") {
+            if !login.trim().is_empty() {
+                config = config.with_bot_login(login.trim().trim_start_matches('@'));
+            }
+        }
+        for login in std::env::var("GITHUB_WEBHOOK_TRUSTED_USERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|login| !login.is_empty())
+        {
+            config = config.with_trusted_user(login);
+        }
+        Some(config)
+    }
+}
+
+/// Whether `signature`, the `X-Hub-Signature-256` header, is the
+/// `sha256=<hex>` HMAC of `body` under `secret`.
+pub fn verify_signature(secret: &[u8], body: &[u8], signature: &str) -> bool {
+    let Some(given) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret);
+    engine.input(body);
+    let expected = format!("{:x}", hmac::Hmac::<sha256::Hash>::from_engine(engine));
+    // Compare every byte so the time taken doesn't reveal the matching prefix
+    given.len() == expected.len()
+        && given
+            .to_ascii_lowercase()
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// The parts of an `issues`, `issue_comment` or `pull_request` delivery the
+/// agents look at.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookPayload {
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub repository: Option<Repository>,
+    #[serde(default)]
+    pub issue: Option<GitHubIssue>,
+    #[serde(default)]
+    pub pull_request: Option<PullRequest>,
+    #[serde(default)]
+    pub comment: Option<IssueComment>,
+    /// The label added or removed, for `labeled` and `unlabeled`.
+    #[serde(default)]
+    pub label: Option<Label>,
+    #[serde(default)]
+    pub sender: Option<GitHubUser>,
+}
+
+/// An action to run for an issue or pull request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentJob {
+    pub action: AgentAction,
+    pub owner: String,
+    pub repo: String,
+    /// The issue or pull request number.
+    pub number: i32,
+    /// The delivery that asked for the job.
+    pub delivery: String,
+}
+
+/// The jobs a delivery of `event` asks for: one per matching label added,
+/// and one per action @-mentioned in a new issue, pull request or comment by
+/// someone the config [trusts](WebhookConfig::trusts). Adding labels already
+/// takes triage access. The bot's own activity never starts a job.
+pub fn jobs_for(
+    config: &WebhookConfig,
+    event: &str,
+    delivery: &str,
+    payload: &WebhookPayload,
+) -> Vec<AgentJob> {
+    let Some(repository) = &payload.repository else {
+        return Vec::new();
+    };
+    if let (Some(sender), Some(bot)) = (&payload.sender, &config.bot_login) {
+        if sender.login.eq_ignore_ascii_case(bot) {
+            return Vec::new();
+        }
+    }
+
+    let number = match event {
+        "issues" | "issue_comment" => payload.issue.as_ref().map(|issue| issue.number),
+        "pull_request" => payload
+            .pull_request
+            .as_ref()
+            .map(|pull_request| pull_request.number),
+        _ => None,
+    };
+    let Some(number) = number else {
+        return Vec::new();
+    };
+
+    let actions = match (event, payload.action.as_deref()) {
+        ("issues" | "pull_request", Some("labeled")) => payload
+            .label
+            .as_ref()
+            .and_then(|label| config.label_actions.get(&label.name))
+            .map(|action| vec![*action])
+            .unwrap_or_default(),
+        ("issues", Some("opened")) => payload
+            .issue
+            .as_ref()
+            .filter(|issue| config.trusts(issue.user.as_ref(), issue.author_association.as_deref()))
+            .map(|issue| mentioned_actions(config, issue.body.as_deref()))
+            .unwrap_or_default(),
+        ("pull_request", Some("opened")) => payload
+            .pull_request
+            .as_ref()
+            .filter(|pull_request| {
+                config.trusts(
+                    pull_request.user.as_ref(),
+                    pull_request.author_association.as_deref(),
+                )
+            })
+            .map(|pull_request| mentioned_actions(config, pull_request.body.as_deref()))
+            .unwrap_or_default(),
+        ("issue_comment", Some("created")) => payload
+            .comment
+            .as_ref()
+            .filter(|comment| {
+                config.trusts(comment.user.as_ref(), comment.author_association.as_deref())
+            })
+            .map(|comment| mentioned_actions(config, comment.body.as_deref()))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+
+    actions
+        .into_iter()
+        .map(|action| AgentJob {
+            action,
+            owner: repository.owner.login.clone(),
+            repo: repository.name.clone(),
+            number,
+            delivery: delivery.to_string(),
+        })
+        .collect()
+}
+
+/// The actions asked for by @-mentions of the bot in `body`, each once, in
+/// the order they're asked for.
+fn mentioned_actions(config: &WebhookConfig, body: Option<&str>) -> Vec<AgentAction> {
+    let (Some(bot), Some(body)) = (config.bot_login.as_deref(), body) else {
+        return Vec::new();
+    };
+    let mention = format!("@{}", bot.to_ascii_lowercase());
+    let lowercase = body.to_ascii_lowercase();
+    let mut actions = Vec::new();
+    for (start, _) in lowercase.match_indices(&mention) {
+        let rest = &body[start + mention.len()..];
+        // `@bot` but not `@botany`
+        if rest
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            continue;
+        }
+        let action = rest
+            .split_whitespace()
+            .next()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+            .and_then(|word| word.parse().ok())
+            .unwrap_or(AgentAction::Plan);
+        if !actions.contains(&action) {
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+/// The most recent delivery IDs, to drop the redeliveries GitHub makes when
+/// a response is slow or fails.
+#[derive(Debug)]
+pub struct DeliveryLog {
+    seen: HashSet<String>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl DeliveryLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Remembers `delivery`, returning false if it was already seen.
+    pub fn insert(&mut self, delivery: &str) -> bool {
+        if !self.seen.insert(delivery.to_string()) {
+            return false;
+        }
+        self.order.push_back(delivery.to_string());
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Forgets `delivery`, so a redelivery is handled again.
+    pub fn remove(&mut self, delivery: &str) {
+        if self.seen.remove(delivery) {
+            self.order.retain(|seen| seen != delivery);
+        }
+    }
+}
+
+impl Default for DeliveryLog {
+    fn default() -> Self {
+        Self::new(MAX_REMEMBERED_DELIVERIES)
+    }
+}
+
+#[async_trait]
+pub trait AgentJobRunner: Send + Sync {
+    async fn run(&self, job: &AgentJob) -> Result<()>;
+}
+
+/// Runs jobs against GitHub: reads the issue, checks out the repository and
+/// comments with the model's answer.
+pub struct GitHubAgentRunner {
+    github: Arc<GitHubService>,
+    model: Arc<dyn ChatProvider>,
+    repos: Arc<RepoCache>,
+}
+
+impl GitHubAgentRunner {
+    pub fn new(
+        github: Arc<GitHubService>,
+        model: Arc<dyn ChatProvider>,
+        repos: Arc<RepoCache>,
+    ) -> Self {
+        Self {
+            github,
+            model,
+            repos,
+        }
+    }
+}
+
+#[async_trait]
+impl AgentJobRunner for GitHubAgentRunner {
+    async fn run(&self, job: &AgentJob) -> Result<()> {
+        let thread = self
+            .github
+            .get_issue_thread(&job.owner, &job.repo, job.number)
+            .await?;
+        let repo_path = self
+            .repos
+            .checkout(&format!("{}/{}", job.owner, job.repo))
+            .await?;
+        let map_path = repo_path.clone();
+        let map = tokio::task::spawn_blocking(move || generate_repo_map(&map_path)).await?;
+
+        let comment = match job.action {
+            AgentAction::Plan => {
+                let options = GenerationOptions {
+                    temperature: Some(0.3),
+                    ..GenerationOptions::reasoner()
+                };
+                let plan = self
+                    .model
+                    .chat(plan_prompt(&thread, &map, None), options)
+                    .await?;
+                format!("# Implementation Plan\n\n{}", plan.content)
+            }
+            AgentAction::Analysis => {
+                let test_report = run_cargo_tests(&repo_path).await?;
+                let analysis = analyze_repository(
+                    self.model.clone(),
+                    &map,
+                    &test_report,
+                    &thread.issue,
+                    &repo_path,
+                )
+                .await?;
+                return post_analysis(&self.github, &analysis, job.number, &job.owner, &job.repo)
+                    .await;
+            }
+            AgentAction::SuggestTests => {
+                let tests = self
+                    .model
+                    .chat(
+                        test_suggestion_prompt(&thread, &map),
+                        GenerationOptions::default(),
+                    )
+                    .await?;
+                format!("# Suggested Tests\n\n{}", tests.content)
+            }
+        };
+        self.github
+            .post_comment(&job.owner, &job.repo, job.number, &comment)
+            .await
+    }
+}
+
+/// Runs queued jobs one at a time until every sender is dropped. A job works
+/// in its checkout for minutes, so the runner's [`RepoCache`] must not be
+/// shared with anything else that could replace a clone in the meantime.
+pub fn spawn_agent_worker(
+    mut jobs: mpsc::Receiver<AgentJob>,
+    runner: Arc<dyn AgentJobRunner>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(job) = jobs.recv().await {
+            info!(
+                "Running {} for {}/{}#{} (delivery {})",
+                job.action, job.owner, job.repo, job.number, job.delivery
+            );
+            if let Err(e) = runner.run(&job).await {
+                error!(
+                    "The {} for {}/{}#{} failed: {}",
+                    job.action, job.owner, job.repo, job.number, e
+                );
+            }
+        }
+    })
+}
@@ -0,0 +1,401 @@
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use futures::{stream, StreamExt};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+/// Scenario used when `mock-llm` is started without one.
+const DEFAULT_SCENARIO: &str = include_str!("../../../configuration/mock-llm.yaml");
+
+/// Scripted behaviour for the mock chat completions server.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Scenario {
+    /// Tried in order; the first matching rule answers.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Answer when no rule matches.
+    #[serde(default)]
+    pub default: Reply,
+    /// Pause between streamed chunks, so streaming is visible in the UI.
+    #[serde(default)]
+    pub chunk_delay_ms: u64,
+}
+
+/// A reply and the conditions under which it is sent. Unset conditions always hold.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// Regex checked against the latest message from `role`.
+    #[serde(default, rename = "match")]
+    pub pattern: Option<String>,
+    #[serde(default = "default_role")]
+    pub role: String,
+    /// Role of the final message, e.g. `tool` once a tool result is in.
+    #[serde(default)]
+    pub last_role: Option<String>,
+    /// Whether the request asked for JSON output.
+    #[serde(default)]
+    pub json: Option<bool>,
+    /// Whether the request sent any tools.
+    #[serde(default)]
+    pub tools: Option<bool>,
+    /// Substring of the requested model name.
+    #[serde(default)]
+    pub model: Option<String>,
+    pub reply: Reply,
+    #[serde(skip)]
+    regex: Option<Regex>,
+}
+
+fn default_role() -> String {
+    "user".to_string()
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Reply {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub reasoning: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ScriptedToolCall>,
+    /// Fails the request instead of answering.
+    #[serde(default)]
+    pub error: Option<ScriptedError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedToolCall {
+    pub name: String,
+    /// A JSON object, or a string template that expands to one.
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedError {
+    pub status: u16,
+    pub message: String,
+    /// Seconds sent in a `Retry-After` header.
+    #[serde(default)]
+    pub retry_after: Option<u64>,
+}
+
+impl Scenario {
+    /// Loads a scenario from a YAML or JSON file, picked by extension.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_source(config::File::from(path))
+            .with_context(|| format!("Failed to load scenario {}", path.display()))
+    }
+
+    /// The scenario in `configuration/mock-llm.yaml`, which walks the web chat
+    /// through routing, a GitHub issue tool call and the final answer.
+    pub fn builtin() -> Self {
+        Self::from_source(config::File::from_str(
+            DEFAULT_SCENARIO,
+            config::FileFormat::Yaml,
+        ))
+        .expect("Built-in scenario should be valid")
+    }
+
+    fn from_source<S>(source: S) -> Result<Self>
+    where
+        S: config::Source + Send + Sync + 'static,
+    {
+        let scenario: Self = config::Config::builder()
+            .add_source(source)
+            .build()?
+            .try_deserialize()?;
+        scenario.compiled()
+    }
+
+    fn compiled(mut self) -> Result<Self> {
+        for rule in &mut self.rules {
+            if let Some(pattern) = &rule.pattern {
+                rule.regex = Some(
+                    Regex::new(pattern)
+                        .with_context(|| format!("Invalid scenario pattern '{}'", pattern))?,
+                );
+            }
+        }
+        Ok(self)
+    }
+
+    /// Picks the reply for a chat completions request, with captures expanded.
+    pub fn respond(&self, request: &Value) -> Reply {
+        for rule in &self.rules {
+            if let Some(captures) = rule.matches(request) {
+                return rule.reply.expand(captures.as_ref());
+            }
+        }
+        self.default.clone()
+    }
+}
+
+impl Rule {
+    /// Returns `None` if the rule doesn't apply, otherwise the pattern's captures
+    /// if it has one.
+    fn matches<'a>(&self, request: &'a Value) -> Option<Option<Captures<'a>>> {
+        let messages = request["messages"].as_array()?;
+
+        if let Some(last_role) = &self.last_role {
+            if messages.last()?["role"].as_str()? != last_role {
+                return None;
+            }
+        }
+        if let Some(json) = self.json {
+            let format = request["response_format"]["type"]
+                .as_str()
+                .unwrap_or("text");
+            if json != (format == "json_object" || format == "json_schema") {
+                return None;
+            }
+        }
+        if let Some(tools) = self.tools {
+            let has_tools = request["tools"].as_array().is_some_and(|t| !t.is_empty());
+            if tools != has_tools {
+                return None;
+            }
+        }
+        if let Some(model) = &self.model {
+            if !request["model"].as_str()?.contains(model.as_str()) {
+                return None;
+            }
+        }
+
+        match &self.regex {
+            Some(regex) => {
+                let content = messages
+                    .iter()
+                    .rev()
+                    .find(|message| message["role"] == self.role.as_str())?["content"]
+                    .as_str()?;
+                Some(Some(regex.captures(content)?))
+            }
+            None => Some(None),
+        }
+    }
+}
+
+impl Reply {
+    fn expand(&self, captures: Option<&Captures>) -> Reply {
+        let Some(captures) = captures else {
+            return self.clone();
+        };
+        let expand = |template: &str| {
+            let mut expanded = String::new();
+            captures.expand(template, &mut expanded);
+            expanded
+        };
+
+        Reply {
+            content: expand(&self.content),
+            reasoning: self.reasoning.as_deref().map(expand),
+            tool_calls: self
+                .tool_calls
+                .iter()
+                .map(|call| ScriptedToolCall {
+                    name: call.name.clone(),
+                    arguments: match &call.arguments {
+                        Value::String(template) => Value::String(expand(template)),
+                        other => other.clone(),
+                    },
+                })
+                .collect(),
+            error: self.error.clone(),
+        }
+    }
+}
+
+impl ScriptedToolCall {
+    fn arguments(&self) -> String {
+        match &self.arguments {
+            Value::String(arguments) => arguments.clone(),
+            Value::Null => "{}".to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Serves `/chat/completions` (and `/v1/chat/completions`) in DeepSeek's wire
+/// format, answering from `scenario`.
+pub fn router(scenario: Scenario) -> Router {
+    Router::new()
+        .route("/chat/completions", post(chat_completions))
+        .route("/v1/chat/completions", post(chat_completions))
+        .with_state(Arc::new(scenario))
+}
+
+async fn chat_completions(
+    State(scenario): State<Arc<Scenario>>,
+    Json(request): Json<Value>,
+) -> Response {
+    let reply = scenario.respond(&request);
+    let model = request["model"].as_str().unwrap_or("mock").to_string();
+    info!(
+        "{} request for {} -> {} tool calls, {} chars",
+        if request["stream"] == true {
+            "Streaming"
+        } else {
+            "Chat"
+        },
+        model,
+        reply.tool_calls.len(),
+        reply.content.len()
+    );
+
+    if let Some(error) = &reply.error {
+        let status = StatusCode::from_u16(error.status).unwrap_or(StatusCode::BAD_REQUEST);
+        let body = Json(json!({ "error": { "message": error.message } }));
+        return match error.retry_after {
+            Some(seconds) => {
+                (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response()
+            }
+            None => (status, body).into_response(),
+        };
+    }
+
+    let tool_calls: Vec<(String, &ScriptedToolCall)> = reply
+        .tool_calls
+        .iter()
+        .map(|call| (format!("call_{}", Uuid::new_v4().simple()), call))
+        .collect();
+    let finish_reason = if tool_calls.is_empty() {
+        "stop"
+    } else {
+        "tool_calls"
+    };
+    let usage = usage(&request, &reply);
+
+    if request["stream"] != true {
+        let mut message = json!({ "role": "assistant", "content": reply.content });
+        if let Some(reasoning) = &reply.reasoning {
+            message["reasoning_content"] = json!(reasoning);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = tool_calls
+                .iter()
+                .map(|(id, call)| {
+                    json!({
+                        "id": id,
+                        "type": "function",
+                        "function": { "name": call.name, "arguments": call.arguments() }
+                    })
+                })
+                .collect();
+        }
+
+        return Json(json!({
+            "id": format!("mock-{}", Uuid::new_v4().simple()),
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{ "index": 0, "message": message, "finish_reason": finish_reason }],
+            "usage": usage
+        }))
+        .into_response();
+    }
+
+    let mut events = Vec::new();
+    let delta = |delta: Value, finish_reason: Option<&str>| {
+        json!({
+            "object": "chat.completion.chunk",
+            "model": model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }]
+        })
+    };
+
+    for piece in reply.reasoning.iter().flat_map(|r| r.split_inclusive(' ')) {
+        events.push(delta(json!({ "reasoning_content": piece }), None));
+    }
+    for piece in reply.content.split_inclusive(' ') {
+        events.push(delta(json!({ "content": piece }), None));
+    }
+    // Tool calls arrive as an opening fragment followed by argument fragments
+    for (index, (id, call)) in tool_calls.iter().enumerate() {
+        events.push(delta(
+            json!({ "tool_calls": [{
+                "index": index,
+                "id": id,
+                "type": "function",
+                "function": { "name": call.name, "arguments": "" }
+            }]}),
+            None,
+        ));
+        let arguments = call.arguments();
+        let mid = (0..=arguments.len() / 2)
+            .rev()
+            .find(|&i| arguments.is_char_boundary(i))
+            .unwrap_or(0);
+        for part in [&arguments[..mid], &arguments[mid..]] {
+            events.push(delta(
+                json!({ "tool_calls": [{ "index": index, "function": { "arguments": part } }] }),
+                None,
+            ));
+        }
+    }
+    events.push(delta(json!({}), Some(finish_reason)));
+    if request["stream_options"]["include_usage"] == true {
+        events.push(json!({ "object": "chat.completion.chunk", "choices": [], "usage": usage }));
+    }
+
+    let delay = Duration::from_millis(scenario.chunk_delay_ms);
+    let lines = events
+        .into_iter()
+        .map(|event| format!("data: {}\n\n", event))
+        .chain(std::iter::once("data: [DONE]\n\n".to_string()));
+    let body = stream::iter(lines).then(move |line| async move {
+        tokio::time::sleep(delay).await;
+        Ok::<_, Infallible>(line)
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .body(Body::from_stream(body))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// Rough token counts at four characters per token.
+fn usage(request: &Value, reply: &Reply) -> Value {
+    let tokens = |chars: usize| (chars / 4).max(1);
+
+    let prompt_chars: usize = request["messages"]
+        .as_array()
+        .map(|messages| {
+            messages
+                .iter()
+                .map(|m| m["content"].as_str().map_or(0, str::len))
+                .sum()
+        })
+        .unwrap_or(0);
+    let reasoning_tokens = reply.reasoning.as_deref().map_or(0, |r| tokens(r.len()));
+    let completion_chars = reply.content.len()
+        + reply
+            .tool_calls
+            .iter()
+            .map(|call| call.arguments().len())
+            .sum::<usize>();
+
+    let prompt_tokens = tokens(prompt_chars);
+    let completion_tokens = tokens(completion_chars) + reasoning_tokens;
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens,
+        "prompt_cache_hit_tokens": 0,
+        "prompt_cache_miss_tokens": prompt_tokens,
+        "completion_tokens_details": { "reasoning_tokens": reasoning_tokens }
+    })
+}
@@ -1,14 +1,26 @@
+pub mod agent;
 pub mod auth;
+pub mod cassette;
 pub mod chat_database;
 pub mod deepseek;
 pub mod github_issue;
+pub mod github_rate_limit;
 pub mod github_types;
+pub mod github_webhook;
+pub mod mock_llm;
 pub mod model_router;
+pub mod openai_compat;
+pub mod provider;
 pub mod repomap;
+pub mod structured;
 
+pub use agent::{AgentLoop, AgentRun};
 pub use auth::OIDCConfig;
 pub use chat_database::ChatDatabase;
 pub use deepseek::{DeepSeekService, StreamUpdate};
 pub use github_issue::GitHubService;
 pub use model_router::ModelRouter;
+pub use openai_compat::OpenAICompatService;
+pub use provider::{provider_from_env, ChatProvider};
 pub use repomap::RepomapService;
+pub use structured::ChatJson;
@@ -2,11 +2,16 @@ use anyhow::Result;
 use serde::Deserialize;
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tracing::info;
+use tracing::{info, warn};
 
+use super::agent::AgentLoop;
 use super::deepseek::{
-    ChatMessage, DeepSeekService, StreamUpdate, Tool, ToolCallResponse, ToolChoice,
+    ChatCompletion, ChatMessage, GenerationOptions, StreamUpdate, Tool, ToolCallResponse,
+    ToolChoice, Usage,
 };
+use super::provider::ChatProvider;
+use super::structured::{ChatJson, JsonOutputError};
+use crate::server::tools::ToolRegistry;
 
 #[derive(Debug, Deserialize)]
 pub struct RoutingDecision {
@@ -16,15 +21,15 @@ pub struct RoutingDecision {
 }
 
 pub struct ModelRouter {
-    tool_model: Arc<DeepSeekService>,
-    chat_model: Arc<DeepSeekService>,
+    tool_model: Arc<dyn ChatProvider>,
+    chat_model: Arc<dyn ChatProvider>,
     available_tools: Vec<Tool>,
 }
 
 impl ModelRouter {
     pub fn new(
-        tool_model: Arc<DeepSeekService>,
-        chat_model: Arc<DeepSeekService>,
+        tool_model: Arc<dyn ChatProvider>,
+        chat_model: Arc<dyn ChatProvider>,
         available_tools: Vec<Tool>,
     ) -> Self {
         Self {
@@ -34,39 +39,55 @@ impl ModelRouter {
         }
     }
 
+    /// One line per available tool, for the routing prompt.
+    fn tool_list(&self) -> String {
+        self.available_tools
+            .iter()
+            .map(|tool| match &tool.function.description {
+                Some(description) => format!("- {}: {}", tool.function.name, description),
+                None => format!("- {}", tool.function.name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Decides whether `message` needs a tool and, if so, asks the tool model for the
+    /// calls. Also returns the tokens spent on both requests.
     pub async fn route_message(
         &self,
         message: String,
-    ) -> Result<(RoutingDecision, Option<Vec<ToolCallResponse>>)> {
+    ) -> Result<(RoutingDecision, Option<Vec<ToolCallResponse>>, Usage)> {
         // Create system prompt for routing
         let system_message = ChatMessage {
             role: "system".to_string(),
-            content: r#"You are a routing assistant that determines whether a user message requires tool usage.
+            content: format!(
+                r#"You are a routing assistant that determines whether a user message requires tool usage.
 DO NOT USE ANY TOOLS DIRECTLY. Instead, analyze the user's message and respond with a JSON object containing:
 1. "needs_tool": boolean - whether any tools are needed
 2. "reasoning": string - brief explanation of your decision (use "requesting a calculation" for math queries)
 3. "suggested_tool": string | null - name of suggested tool if applicable
 
 Available tools:
-- read_github_issue: Read GitHub issues by number
-- calculate: Perform mathematical calculations
+{}
 
 IMPORTANT: Your response must be a valid JSON object and nothing else.
 
 Example responses:
-{
+{{
     "needs_tool": true,
     "reasoning": "User is requesting to view a GitHub issue",
     "suggested_tool": "read_github_issue"
-}
+}}
 
-{
+{{
     "needs_tool": false,
     "reasoning": "General chat message that doesn't require tools",
     "suggested_tool": null
-}
+}}
 
-Remember: Only respond with a JSON object, do not use any tools, and do not add any additional text."#.to_string(),
+Remember: Only respond with a JSON object, do not use any tools, and do not add any additional text."#,
+                self.tool_list()
+            ),
             tool_call_id: None,
             tool_calls: None,
         };
@@ -79,32 +100,33 @@ Remember: Only respond with a JSON object, do not use any tools, and do not add
             tool_calls: None,
         };
 
-        // Get routing decision
-        let (response, _, _) = self
+        // Get routing decision, asking the model to repair replies that aren't valid JSON
+        let (decision, mut usage) = match self
             .tool_model
-            .chat_with_tools_messages(
+            .chat_json::<RoutingDecision>(
                 vec![system_message, user_message],
-                self.available_tools.clone(),
-                None, // Don't allow tool usage during routing
-                false,
+                GenerationOptions::deterministic(),
             )
-            .await?;
-
-        info!("Routing decision: {}", response);
-
-        // Try to parse routing decision
-        let decision = match serde_json::from_str::<RoutingDecision>(&response) {
-            Ok(d) => d,
-            Err(_) => {
-                // If parsing fails, treat it as a non-tool message
-                RoutingDecision {
-                    needs_tool: false,
-                    reasoning: "General chat message".to_string(),
-                    suggested_tool: None,
+            .await
+        {
+            Ok(routing) => (routing.value, routing.usage),
+            Err(e) => match e.downcast::<JsonOutputError>() {
+                // Still unparseable after repairs, so treat it as a non-tool message
+                Ok(invalid) => {
+                    warn!("Falling back to general chat: {}", invalid);
+                    let decision = RoutingDecision {
+                        needs_tool: false,
+                        reasoning: "General chat message".to_string(),
+                        suggested_tool: None,
+                    };
+                    (decision, invalid.usage)
                 }
-            }
+                Err(e) => return Err(e),
+            },
         };
 
+        info!("Routing decision: {:?}", decision);
+
         // If tools are needed, try to execute the suggested tool
         if decision.needs_tool {
             if let Some(suggested_tool_name) = &decision.suggested_tool {
@@ -116,29 +138,29 @@ Remember: Only respond with a JSON object, do not use any tools, and do not add
 
                 if let Some(tool) = tool {
                     // Try to use the tool
-                    let (_, _, tool_calls) = self
+                    let completion = self
                         .tool_model
                         .chat_with_tools(
                             message,
                             vec![tool.clone()],
                             Some(ToolChoice::Auto("auto".to_string())),
-                            false,
+                            GenerationOptions::deterministic(),
                         )
                         .await?;
 
-                    return Ok((decision, tool_calls));
+                    if let Some(tool_usage) = &completion.usage {
+                        usage += tool_usage;
+                    }
+
+                    return Ok((decision, completion.tool_calls, usage));
                 }
             }
         }
 
-        Ok((decision, None))
+        Ok((decision, None, usage))
     }
 
-    pub async fn execute_tool_call(
-        &self,
-        message: String,
-        tool: Tool,
-    ) -> Result<(String, Option<String>, Option<Vec<ToolCallResponse>>)> {
+    pub async fn execute_tool_call(&self, message: String, tool: Tool) -> Result<ChatCompletion> {
         // Create a system message to guide tool usage
         let system_message = ChatMessage {
             role: "system".to_string(),
@@ -178,7 +200,7 @@ Here's what I found: [Explain results]""#,
                 vec![system_message, user_message],
                 vec![tool],
                 Some(ToolChoice::Auto("auto".to_string())),
-                false,
+                GenerationOptions::deterministic(),
             )
             .await
     }
@@ -186,26 +208,47 @@ Here's what I found: [Explain results]""#,
     pub async fn chat(
         &self,
         message: String,
-        use_reasoning: bool,
-    ) -> Result<(String, Option<String>)> {
+        options: impl Into<GenerationOptions>,
+    ) -> Result<ChatCompletion> {
         // Use basic chat without any tools or messages
-        self.chat_model.chat(message, use_reasoning).await
+        self.chat_model.chat(message, options.into()).await
     }
 
-    pub async fn chat_stream(&self, message: String) -> mpsc::Receiver<StreamUpdate> {
-        self.chat_model.chat_stream(message, true).await
+    /// Streams the chat model's answer to the last message in `messages`, with
+    /// the earlier messages as conversation context.
+    pub async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        options: impl Into<GenerationOptions>,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        self.chat_model
+            .chat_stream_messages(messages, None, None, options.into())
+            .await
     }
 
+    /// Sends one tool result back to the tool model. For anything beyond a
+    /// single round trip use [`ModelRouter::agent`].
     pub async fn handle_tool_response(
         &self,
         messages: Vec<ChatMessage>,
         tool_message: ChatMessage,
-    ) -> Result<(String, Option<String>, Option<Vec<ToolCallResponse>>)> {
+    ) -> Result<ChatCompletion> {
         let mut all_messages = messages;
         all_messages.push(tool_message);
 
         self.tool_model
-            .chat_with_tools_messages(all_messages, self.available_tools.clone(), None, false)
+            .chat_with_tools_messages(
+                all_messages,
+                self.available_tools.clone(),
+                None,
+                GenerationOptions::default(),
+            )
             .await
     }
+
+    /// An agent loop on the tool model that runs `tools` until the model has
+    /// what it needs to answer.
+    pub fn agent(&self, tools: Arc<ToolRegistry>) -> AgentLoop {
+        AgentLoop::new(self.tool_model.clone(), tools)
+    }
 }
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use super::deepseek::{
+    ChatCompletion, ChatMessage, DeepSeekService, GenerationOptions, StreamUpdate, Tool, ToolChoice,
+};
+use super::provider::ChatProvider;
+
+/// Provider for servers exposing the OpenAI chat-completions API, such as
+/// vLLM, the llama.cpp server or Ollama.
+///
+/// They share DeepSeek's wire format, so requests go through a [`DeepSeekService`]
+/// pointed at the server's URL and model names. Without a dedicated reasoning
+/// model, reasoning requests fall back to the regular model.
+#[derive(Debug, Clone)]
+pub struct OpenAICompatService {
+    inner: DeepSeekService,
+    has_reasoner: bool,
+}
+
+impl OpenAICompatService {
+    pub fn new(
+        base_url: String,
+        api_key: Option<String>,
+        model: String,
+        reasoner_model: Option<String>,
+    ) -> Self {
+        let has_reasoner = reasoner_model.is_some();
+        let reasoner_model = reasoner_model.unwrap_or_else(|| model.clone());
+        let inner = DeepSeekService::with_base_url(api_key.unwrap_or_default(), base_url)
+            .with_models(model, reasoner_model);
+
+        Self {
+            inner,
+            has_reasoner,
+        }
+    }
+
+    fn options(&self, options: GenerationOptions) -> GenerationOptions {
+        GenerationOptions {
+            use_reasoner: options.use_reasoner && self.has_reasoner,
+            ..options
+        }
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OpenAICompatService {
+    fn name(&self) -> &str {
+        "openai-compatible"
+    }
+
+    async fn chat(&self, prompt: String, options: GenerationOptions) -> Result<ChatCompletion> {
+        Ok(self.inner.chat(prompt, self.options(options)).await?)
+    }
+
+    async fn chat_stream_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        self.inner
+            .chat_stream_messages(messages, tools, tool_choice, self.options(options))
+            .await
+    }
+
+    async fn chat_with_tools_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Vec<Tool>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion> {
+        Ok(self
+            .inner
+            .chat_with_tools_messages(messages, tools, tool_choice, self.options(options))
+            .await?)
+    }
+
+    async fn chat_with_tool_response(
+        &self,
+        messages: Vec<ChatMessage>,
+        tool_response: ChatMessage,
+        tools: Vec<Tool>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion> {
+        Ok(self
+            .inner
+            .chat_with_tool_response(messages, tool_response, tools, self.options(options))
+            .await?)
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::env;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use super::deepseek::{
+    ChatCompletion, ChatMessage, DeepSeekService, GenerationOptions, StreamUpdate, Tool, ToolChoice,
+};
+use super::openai_compat::OpenAICompatService;
+
+/// Environment prefix for the model that makes routing and tool-calling decisions.
+pub const TOOL_MODEL_ROLE: &str = "TOOL_MODEL";
+/// Environment prefix for the model that writes conversational answers.
+pub const CHAT_MODEL_ROLE: &str = "CHAT_MODEL";
+
+/// A chat-completions backend the router, the chat handler and the CLIs can talk to.
+///
+/// All messages and tool definitions use the OpenAI wire format, which DeepSeek
+/// and most local inference servers speak.
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    /// Short identifier used in logs, e.g. `deepseek`.
+    fn name(&self) -> &str;
+
+    async fn chat(&self, prompt: String, options: GenerationOptions) -> Result<ChatCompletion>;
+
+    /// Streams a completion over a full conversation. When `tools` are given the
+    /// stream may end in [`StreamUpdate::ToolCalls`]. Dropping the receiver
+    /// cancels the request.
+    async fn chat_stream_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> mpsc::Receiver<StreamUpdate>;
+
+    async fn chat_with_tools_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Vec<Tool>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion>;
+
+    async fn chat_with_tool_response(
+        &self,
+        messages: Vec<ChatMessage>,
+        tool_response: ChatMessage,
+        tools: Vec<Tool>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion>;
+
+    async fn chat_with_tools(
+        &self,
+        prompt: String,
+        tools: Vec<Tool>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion> {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            tool_call_id: None,
+            tool_calls: None,
+        }];
+
+        self.chat_with_tools_messages(messages, tools, tool_choice, options)
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        prompt: String,
+        options: GenerationOptions,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        self.chat_stream_with_tools(prompt, Vec::new(), None, options)
+            .await
+    }
+
+    /// Streams a single-prompt completion that may end in [`StreamUpdate::ToolCalls`].
+    async fn chat_stream_with_tools(
+        &self,
+        prompt: String,
+        tools: Vec<Tool>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: prompt,
+            tool_call_id: None,
+            tool_calls: None,
+        }];
+
+        self.chat_stream_messages(messages, Some(tools), tool_choice, options)
+            .await
+    }
+}
+
+#[async_trait]
+impl ChatProvider for DeepSeekService {
+    fn name(&self) -> &str {
+        "deepseek"
+    }
+
+    async fn chat(&self, prompt: String, options: GenerationOptions) -> Result<ChatCompletion> {
+        Ok(DeepSeekService::chat(self, prompt, options).await?)
+    }
+
+    async fn chat_stream_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<Tool>>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> mpsc::Receiver<StreamUpdate> {
+        DeepSeekService::chat_stream_messages(self, messages, tools, tool_choice, options).await
+    }
+
+    async fn chat_with_tools_messages(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Vec<Tool>,
+        tool_choice: Option<ToolChoice>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion> {
+        Ok(
+            DeepSeekService::chat_with_tools_messages(self, messages, tools, tool_choice, options)
+                .await?,
+        )
+    }
+
+    async fn chat_with_tool_response(
+        &self,
+        messages: Vec<ChatMessage>,
+        tool_response: ChatMessage,
+        tools: Vec<Tool>,
+        options: GenerationOptions,
+    ) -> Result<ChatCompletion> {
+        Ok(
+            DeepSeekService::chat_with_tool_response(self, messages, tool_response, tools, options)
+                .await?,
+        )
+    }
+}
+
+/// Builds the provider configured for `role` (see [`TOOL_MODEL_ROLE`] and [`CHAT_MODEL_ROLE`]).
+///
+/// `{ROLE}_PROVIDER` (falling back to `LLM_PROVIDER`) selects `deepseek` (the default)
+/// or `openai` for any OpenAI-compatible server such as vLLM, llama.cpp or Ollama.
+/// The OpenAI-compatible provider reads `{ROLE}_BASE_URL`, `{ROLE}_API_KEY`,
+/// `{ROLE}_NAME` and `{ROLE}_REASONER_NAME`, falling back to the shared
+/// `OPENAI_COMPAT_BASE_URL`, `OPENAI_COMPAT_API_KEY`, `OPENAI_COMPAT_MODEL` and
+/// `OPENAI_COMPAT_REASONER_MODEL`.
+pub fn provider_from_env(role: &str) -> Result<Arc<dyn ChatProvider>> {
+    let kind = role_var(role, "PROVIDER")
+        .or_else(|| non_empty_var("LLM_PROVIDER"))
+        .unwrap_or_else(|| "deepseek".to_string());
+
+    match kind.to_lowercase().as_str() {
+        "deepseek" => {
+            let api_key = env::var("DEEPSEEK_API_KEY")
+                .map_err(|_| anyhow::anyhow!("DEEPSEEK_API_KEY must be set for {}", role))?;
+            Ok(Arc::new(DeepSeekService::new(api_key)))
+        }
+        "openai" | "openai_compat" | "openai-compatible" => {
+            let base_url = compat_var(role, "BASE_URL", "OPENAI_COMPAT_BASE_URL")
+                .unwrap_or_else(|| "http://localhost:11434/v1".to_string());
+            let model = compat_var(role, "NAME", "OPENAI_COMPAT_MODEL").ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}_NAME or OPENAI_COMPAT_MODEL must be set for the OpenAI-compatible provider",
+                    role
+                )
+            })?;
+            let api_key = compat_var(role, "API_KEY", "OPENAI_COMPAT_API_KEY");
+            let reasoner_model = compat_var(role, "REASONER_NAME", "OPENAI_COMPAT_REASONER_MODEL");
+
+            info!(
+                "Using OpenAI-compatible provider for {} at {} (model {})",
+                role, base_url, model
+            );
+
+            Ok(Arc::new(OpenAICompatService::new(
+                base_url,
+                api_key,
+                model,
+                reasoner_model,
+            )))
+        }
+        other => Err(anyhow::anyhow!(
+            "Unknown LLM provider '{}' for {}; expected 'deepseek' or 'openai'",
+            other,
+            role
+        )),
+    }
+}
+
+fn role_var(role: &str, key: &str) -> Option<String> {
+    non_empty_var(&format!("{}_{}", role, key))
+}
+
+fn compat_var(role: &str, key: &str, shared: &str) -> Option<String> {
+    role_var(role, key).or_else(|| non_empty_var(shared))
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.is_empty())
+}
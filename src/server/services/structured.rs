@@ -0,0 +1,150 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use tracing::warn;
+
+use super::deepseek::{ChatMessage, GenerationOptions, ResponseFormat, Usage};
+use super::provider::ChatProvider;
+
+/// How many times a reply that doesn't deserialize is sent back to the model
+/// with the parse error before giving up.
+pub const JSON_REPAIR_ATTEMPTS: usize = 2;
+
+/// A reply deserialized into `T`, with the tokens spent across all attempts.
+#[derive(Debug, Clone)]
+pub struct JsonCompletion<T> {
+    pub value: T,
+    pub usage: Usage,
+}
+
+/// The model kept answering with something that doesn't deserialize into the
+/// requested type.
+#[derive(Debug, Clone)]
+pub struct JsonOutputError {
+    pub attempts: usize,
+    /// The serde error for the last reply.
+    pub message: String,
+    /// The last reply as the model sent it.
+    pub content: String,
+    pub usage: Usage,
+}
+
+impl fmt::Display for JsonOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Model returned invalid JSON after {} attempts: {}",
+            self.attempts, self.message
+        )
+    }
+}
+
+impl std::error::Error for JsonOutputError {}
+
+/// Structured output on top of any [`ChatProvider`].
+#[async_trait]
+pub trait ChatJson {
+    /// Asks for a JSON reply and deserializes it into `T`.
+    ///
+    /// Requests `json_object` output unless `options` already set a response
+    /// format, and strips markdown fences before parsing. A reply that still
+    /// doesn't deserialize is sent back to the model with the error, up to
+    /// [`JSON_REPAIR_ATTEMPTS`] times, before failing with [`JsonOutputError`].
+    /// Prompts must mention JSON, as DeepSeek rejects `json_object` otherwise.
+    async fn chat_json<T>(
+        &self,
+        messages: Vec<ChatMessage>,
+        options: GenerationOptions,
+    ) -> Result<JsonCompletion<T>>
+    where
+        T: DeserializeOwned + Send;
+}
+
+#[async_trait]
+impl<P: ChatProvider + ?Sized> ChatJson for P {
+    async fn chat_json<T>(
+        &self,
+        mut messages: Vec<ChatMessage>,
+        mut options: GenerationOptions,
+    ) -> Result<JsonCompletion<T>>
+    where
+        T: DeserializeOwned + Send,
+    {
+        if options.response_format.is_none() {
+            options.response_format = Some(ResponseFormat::JsonObject);
+        }
+
+        let mut usage = Usage::default();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let completion = self
+                .chat_with_tools_messages(messages.clone(), Vec::new(), None, options.clone())
+                .await?;
+            if let Some(completion_usage) = &completion.usage {
+                usage += completion_usage;
+            }
+
+            let error = match serde_json::from_str::<T>(extract_json(&completion.content)) {
+                Ok(value) => return Ok(JsonCompletion { value, usage }),
+                Err(e) => e.to_string(),
+            };
+
+            if attempt > JSON_REPAIR_ATTEMPTS {
+                return Err(JsonOutputError {
+                    attempts: attempt,
+                    message: error,
+                    content: completion.content,
+                    usage,
+                }
+                .into());
+            }
+
+            warn!(
+                "{} reply didn't match the expected JSON ({}), asking it to repair",
+                self.name(),
+                error
+            );
+            messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: completion.content,
+                tool_call_id: None,
+                tool_calls: None,
+            });
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: format!(
+                    "Your reply could not be parsed: {}. Respond again with only the corrected JSON object, without markdown or commentary.",
+                    error
+                ),
+                tool_call_id: None,
+                tool_calls: None,
+            });
+        }
+    }
+}
+
+/// Returns the JSON document inside a model reply, dropping markdown code
+/// fences and any prose around the outermost object or array.
+pub fn extract_json(content: &str) -> &str {
+    let mut content = content.trim();
+
+    if let Some(start) = content.find("```") {
+        let fenced = &content[start + 3..];
+        // Skip the info string, e.g. ```json
+        let body = fenced.find('\n').map_or(fenced, |i| &fenced[i + 1..]);
+        content = body.find("```").map_or(body, |end| &body[..end]).trim();
+    }
+
+    if content.starts_with('{') || content.starts_with('[') {
+        return content;
+    }
+
+    let start = content.find(['{', '[']);
+    let end = content.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => content,
+    }
+}
@@ -1,45 +0,0 @@
-use crate::server::services::deepseek::{DeepSeekService, Tool};
-use serde_json::json;
-
-pub fn create_tools() -> Vec<Tool> {
-    vec![
-        // GitHub issue tool
-        DeepSeekService::create_tool(
-            "read_github_issue".to_string(),
-            Some("Read a GitHub issue by number".to_string()),
-            json!({
-                "type": "object",
-                "properties": {
-                    "owner": {
-                        "type": "string",
-                        "description": "The owner of the repository"
-                    },
-                    "repo": {
-                        "type": "string",
-                        "description": "The name of the repository"
-                    },
-                    "issue_number": {
-                        "type": "integer",
-                        "description": "The issue number"
-                    }
-                },
-                "required": ["owner", "repo", "issue_number"]
-            }),
-        ),
-        // Calculator tool
-        DeepSeekService::create_tool(
-            "calculate".to_string(),
-            Some("Perform a calculation".to_string()),
-            json!({
-                "type": "object",
-                "properties": {
-                    "expression": {
-                        "type": "string",
-                        "description": "The mathematical expression to evaluate"
-                    }
-                },
-                "required": ["expression"]
-            }),
-        ),
-    ]
-}
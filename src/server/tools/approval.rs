@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// What a tool can do beyond reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    /// Only reads, so it runs without asking.
+    #[default]
+    ReadOnly,
+    /// Changes something outside the conversation, e.g. posts a comment. Only
+    /// runs once the user approves the call.
+    SideEffecting,
+}
+
+/// A side-effecting call waiting for the user.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApprovalRequest {
+    pub tool: String,
+    pub arguments: Value,
+    /// The tool's progress message, describing what the call will do.
+    pub description: String,
+    pub risk: RiskLevel,
+}
+
+/// The user's answer to an [`ApprovalRequest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Approval {
+    Approved,
+    Denied {
+        reason: Option<String>,
+    },
+    /// Nobody answered in time.
+    TimedOut,
+}
+
+/// Asks the user whether a side-effecting call may run.
+#[async_trait]
+pub trait ToolApprover: Send + Sync {
+    async fn approve(&self, request: &ApprovalRequest) -> Approval;
+}
+
+/// A side-effecting call was not approved, so it didn't run. Sent back to the
+/// model as the tool result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallNotApproved {
+    pub tool: String,
+    /// [`Approval::Denied`] or [`Approval::TimedOut`].
+    pub approval: Approval,
+}
+
+impl CallNotApproved {
+    pub fn kind(&self) -> &'static str {
+        match self.approval {
+            Approval::TimedOut => "approval_timeout",
+            _ => "denied",
+        }
+    }
+}
+
+impl fmt::Display for CallNotApproved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.approval {
+            Approval::Denied {
+                reason: Some(reason),
+            } => write!(f, "The user denied the {} call: {}", self.tool, reason),
+            Approval::TimedOut => {
+                write!(f, "The user did not approve the {} call in time", self.tool)
+            }
+            _ => write!(f, "The user denied the {} call", self.tool),
+        }
+    }
+}
+
+impl std::error::Error for CallNotApproved {}
@@ -0,0 +1,1083 @@
+use super::{InvalidArguments, SchemaViolation, ToolContext, ToolExecutor};
+use anyhow::Result;
+use async_trait::async_trait;
+use bigdecimal::{
+    num_bigint::{BigInt, Sign},
+    BigDecimal, Context, One, RoundingMode, Signed, ToPrimitive, Zero,
+};
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+use std::fmt;
+use std::num::NonZeroU64;
+
+/// Significant digits kept by functions that can't be computed exactly.
+const WORKING_PRECISION: u64 = 60;
+/// Extra digits carried inside series so rounding doesn't reach the result.
+const GUARD_DIGITS: u64 = 10;
+/// Significant digits shown for results that aren't exact integers.
+const DISPLAY_PRECISION: u64 = 40;
+/// Significant digits kept by exact arithmetic before it starts rounding.
+const MAX_DIGITS: u64 = 1000;
+/// Largest power of ten a result may reach: results stay below
+/// 10^(MAX_MAGNITUDE + 1).
+const MAX_MAGNITUDE: i64 = 100_000;
+const MAX_FACTORIAL: u64 = 10_000;
+const MAX_EXPRESSION_LENGTH: usize = 2000;
+const MAX_NESTING: usize = 100;
+/// Differences this many digits below their rounded operands are treated as 0.
+const CANCELLATION_DIGITS: i64 = (WORKING_PRECISION - GUARD_DIGITS) as i64;
+
+lazy_static! {
+    static ref LN2: BigDecimal = atanh_series(&(BigDecimal::one() / 3u32)).double();
+    static ref LN10: BigDecimal =
+        precise(&*LN2 * BigDecimal::from(3) + atanh_series(&(BigDecimal::one() / 9u32)).double());
+    static ref PI: BigDecimal = precise(
+        atan_series(&BigDecimal::new(2.into(), 1)) * BigDecimal::from(16)
+            - atan_series(&(BigDecimal::one() / 239u32)) * BigDecimal::from(4)
+    );
+    static ref E: BigDecimal = exp_series(&BigDecimal::one());
+}
+
+/// Why an expression couldn't be evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalcError {
+    /// The expression doesn't parse. `position` is a character offset.
+    Syntax {
+        position: usize,
+        message: String,
+    },
+    UnknownIdentifier {
+        position: usize,
+        name: String,
+    },
+    WrongArity {
+        function: String,
+        expected: &'static str,
+        found: usize,
+    },
+    DivisionByZero,
+    /// An argument outside the function's domain, e.g. `sqrt(-1)`.
+    Domain {
+        function: String,
+        message: String,
+    },
+    /// The result is too large to represent.
+    Overflow,
+}
+
+impl CalcError {
+    /// Stable identifier sent to the model alongside the message.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CalcError::Syntax { .. } => "syntax",
+            CalcError::UnknownIdentifier { .. } => "unknown_identifier",
+            CalcError::WrongArity { .. } => "wrong_arity",
+            CalcError::DivisionByZero => "division_by_zero",
+            CalcError::Domain { .. } => "domain",
+            CalcError::Overflow => "overflow",
+        }
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            CalcError::Syntax { position, .. } | CalcError::UnknownIdentifier { position, .. } => {
+                Some(*position)
+            }
+            _ => None,
+        }
+    }
+
+    fn syntax(position: usize, message: impl Into<String>) -> Self {
+        CalcError::Syntax {
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn domain(function: &str, message: impl Into<String>) -> Self {
+        CalcError::Domain {
+            function: function.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::Syntax { position, message } => {
+                write!(f, "{} at position {}", message, position)
+            }
+            CalcError::UnknownIdentifier { position, name } => {
+                write!(
+                    f,
+                    "Unknown function or constant '{}' at position {}",
+                    name, position
+                )
+            }
+            CalcError::WrongArity {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} argument(s) but got {}",
+                function, expected, found
+            ),
+            CalcError::DivisionByZero => write!(f, "Division by zero"),
+            CalcError::Domain { function, message } => write!(f, "{}: {}", function, message),
+            CalcError::Overflow => write!(f, "Result is too large"),
+        }
+    }
+}
+
+impl std::error::Error for CalcError {}
+
+/// Evaluates an arithmetic expression with decimal arithmetic.
+///
+/// Supports `+ - * / % ^` (`**` also works), unary minus, postfix `!`,
+/// parentheses, the constants `pi`, `e` and `tau`, and the functions `sqrt`,
+/// `cbrt`, `abs`, `floor`, `ceil`, `trunc`, `round`, `exp`, `ln`, `log`, `log2`,
+/// `log10`, `sin`, `cos`, `tan`, `asin`, `acos`, `atan`, `min`, `max` and `pow`.
+/// Sums, differences, products and integer powers are exact; division and the
+/// other functions are rounded to at least 60 significant digits.
+pub fn evaluate(expression: &str) -> Result<BigDecimal, CalcError> {
+    if expression.chars().count() > MAX_EXPRESSION_LENGTH {
+        return Err(CalcError::syntax(
+            MAX_EXPRESSION_LENGTH,
+            "Expression is too long",
+        ));
+    }
+
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        next: 0,
+        depth: 0,
+        end: expression.chars().count(),
+    };
+    let result = parser.expression(0)?;
+    match parser.peek() {
+        Some(token) => Err(CalcError::syntax(
+            token.position,
+            format!("Unexpected '{}'", token.kind),
+        )),
+        None => Ok(result.value),
+    }
+}
+
+/// Formats a result for display: integers of up to 1000 digits in full,
+/// anything else rounded to 40 significant digits, with scientific notation
+/// for extreme magnitudes.
+pub fn format_number(value: &BigDecimal) -> String {
+    if value.is_zero() {
+        return "0".to_string();
+    }
+    let value = if value.is_integer() {
+        value.normalized()
+    } else {
+        round_to(value, DISPLAY_PRECISION).normalized()
+    };
+
+    let magnitude = magnitude(&value);
+    if value.is_integer() && magnitude <= DISPLAY_PRECISION as i64 * 25 {
+        value.with_scale(0).to_string()
+    } else if (-20..=DISPLAY_PRECISION as i64).contains(&magnitude) {
+        value.to_plain_string()
+    } else {
+        round_to(&value, DISPLAY_PRECISION)
+            .normalized()
+            .to_scientific_notation()
+    }
+}
+
+/// Evaluates expressions for the model.
+pub struct Calculate;
+
+#[async_trait]
+impl ToolExecutor for Calculate {
+    fn name(&self) -> &str {
+        "calculate"
+    }
+
+    fn description(&self) -> &str {
+        "Evaluate a mathematical expression with arbitrary precision"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "expression": {
+                    "type": "string",
+                    "description": "The mathematical expression to evaluate, e.g. \"2^64 - 1\" or \"sqrt(2) * sin(pi / 4)\". Supports + - * / % ^ !, parentheses, the constants pi, e and tau, and the functions sqrt, cbrt, abs, floor, ceil, trunc, round, exp, ln, log, log2, log10, sin, cos, tan, asin, acos, atan, min, max and pow. Angles are in radians."
+                }
+            },
+            "required": ["expression"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["expression"].as_str() {
+            Some(expression) => format!("Calculating {}", expression),
+            None => "Calculating".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let Some(expression) = arguments["expression"].as_str() else {
+            return Err(InvalidArguments {
+                tool: self.name().to_string(),
+                violations: vec![SchemaViolation {
+                    path: String::new(),
+                    message: "missing required property 'expression'".to_string(),
+                }],
+            }
+            .into());
+        };
+
+        // Arbitrary precision can take a while, so keep it off the async workers
+        let owned = expression.to_string();
+        let evaluated = tokio::task::spawn_blocking(move || {
+            evaluate(&owned).map(|value| format_number(&value))
+        })
+        .await??;
+        Ok(json!({
+            "expression": expression,
+            "result": evaluated
+        })
+        .to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(BigDecimal),
+    Identifier(String),
+    Operator(char),
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "{}", n),
+            TokenKind::Identifier(name) => write!(f, "{}", name),
+            TokenKind::Operator(op) => write!(f, "{}", op),
+            TokenKind::LeftParen => write!(f, "("),
+            TokenKind::RightParen => write!(f, ")"),
+            TokenKind::Comma => write!(f, ","),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, CalcError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i;
+        let kind = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // Exponent, but only when digits follow so `2e` stays 2 times e
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && matches!(chars[j], '+' | '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let number = literal.parse::<BigDecimal>().map_err(|_| {
+                    CalcError::syntax(start, format!("Invalid number '{}'", literal))
+                })?;
+                if magnitude(&number) > MAX_MAGNITUDE + 1 {
+                    return Err(CalcError::Overflow);
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number(number),
+                    position,
+                });
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Identifier(chars[start..i].iter().collect()),
+                    position,
+                });
+                continue;
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                TokenKind::Operator('^')
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' | '!' => TokenKind::Operator(c),
+            '−' => TokenKind::Operator('-'),
+            '×' => TokenKind::Operator('*'),
+            '÷' => TokenKind::Operator('/'),
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            ',' => TokenKind::Comma,
+            other => {
+                return Err(CalcError::syntax(
+                    position,
+                    format!("Unexpected character '{}'", other),
+                ))
+            }
+        };
+        tokens.push(Token { kind, position });
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Binding power of prefix `-` and `+`, between products and powers so that
+/// `-2^2` is -4.
+const PREFIX_POWER: u8 = 5;
+const POSTFIX_POWER: u8 = 8;
+
+/// Left and right binding powers of infix operators.
+fn infix_power(op: char) -> Option<(u8, u8)> {
+    match op {
+        '+' | '-' => Some((1, 2)),
+        '*' | '/' | '%' => Some((3, 4)),
+        // Right associative: 2^3^2 is 2^9
+        '^' => Some((7, 6)),
+        _ => None,
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+    depth: usize,
+    /// Length of the expression, reported for errors at the end.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), CalcError> {
+        match self.advance() {
+            Some(token) if token.kind == kind => Ok(()),
+            Some(token) => Err(CalcError::syntax(
+                token.position,
+                format!("Expected '{}' but found '{}'", kind, token.kind),
+            )),
+            None => Err(CalcError::syntax(
+                self.end,
+                format!("Expected '{}' but the expression ended", kind),
+            )),
+        }
+    }
+
+    fn expression(&mut self, min_power: u8) -> Result<Number, CalcError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            let position = self.peek().map_or(self.end, |t| t.position);
+            return Err(CalcError::syntax(
+                position,
+                "Expression is nested too deeply",
+            ));
+        }
+
+        let mut lhs = self.prefix()?;
+        while let Some(&Token {
+            kind: TokenKind::Operator(op),
+            ..
+        }) = self.peek()
+        {
+            if op == '!' {
+                if POSTFIX_POWER < min_power {
+                    break;
+                }
+                self.advance();
+                lhs = factorial(&lhs)?;
+                continue;
+            }
+
+            let Some((left_power, right_power)) = infix_power(op) else {
+                break;
+            };
+            if left_power < min_power {
+                break;
+            }
+            self.advance();
+            let rhs = self.expression(right_power)?;
+            lhs = apply(op, lhs, rhs)?;
+        }
+
+        self.depth -= 1;
+        Ok(lhs)
+    }
+
+    fn prefix(&mut self) -> Result<Number, CalcError> {
+        let Some(token) = self.advance() else {
+            return Err(CalcError::syntax(self.end, "Unexpected end of expression"));
+        };
+
+        match token.kind {
+            TokenKind::Number(value) => Ok(Number::exact(value)),
+            TokenKind::Operator('-') => {
+                let operand = self.expression(PREFIX_POWER)?;
+                Ok(Number {
+                    value: -operand.value,
+                    exact: operand.exact,
+                })
+            }
+            TokenKind::Operator('+') => self.expression(PREFIX_POWER),
+            TokenKind::LeftParen => {
+                let value = self.expression(0)?;
+                self.expect(TokenKind::RightParen)?;
+                Ok(value)
+            }
+            TokenKind::Identifier(name) => {
+                if matches!(self.peek(), Some(t) if t.kind == TokenKind::LeftParen) {
+                    self.advance();
+                    let args = self.arguments()?;
+                    call(&name, token.position, &args)
+                } else {
+                    constant(&name)
+                        .map(Number::approximate)
+                        .ok_or(CalcError::UnknownIdentifier {
+                            position: token.position,
+                            name,
+                        })
+                }
+            }
+            other => Err(CalcError::syntax(
+                token.position,
+                format!("Unexpected '{}'", other),
+            )),
+        }
+    }
+
+    /// Parses call arguments after the opening parenthesis.
+    fn arguments(&mut self) -> Result<Vec<Number>, CalcError> {
+        let mut args = Vec::new();
+        if matches!(self.peek(), Some(t) if t.kind == TokenKind::RightParen) {
+            self.advance();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression(0)?);
+            match self.advance() {
+                Some(Token {
+                    kind: TokenKind::Comma,
+                    ..
+                }) => continue,
+                Some(Token {
+                    kind: TokenKind::RightParen,
+                    ..
+                }) => return Ok(args),
+                Some(token) => {
+                    return Err(CalcError::syntax(
+                        token.position,
+                        format!("Expected ',' or ')' but found '{}'", token.kind),
+                    ))
+                }
+                None => {
+                    return Err(CalcError::syntax(
+                        self.end,
+                        "Expected ')' but the expression ended",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// A value and whether it was computed without rounding.
+#[derive(Debug, Clone)]
+struct Number {
+    value: BigDecimal,
+    exact: bool,
+}
+
+impl Number {
+    fn exact(value: BigDecimal) -> Self {
+        Self { value, exact: true }
+    }
+
+    fn approximate(value: BigDecimal) -> Self {
+        Self {
+            value,
+            exact: false,
+        }
+    }
+
+    /// Checks the size of the value, rounding it past `MAX_DIGITS`.
+    fn limited(self) -> Result<Self, CalcError> {
+        let rounded = self.value.digits() > MAX_DIGITS || magnitude(&self.value) < -MAX_MAGNITUDE;
+        Ok(Self {
+            value: limit(self.value)?,
+            exact: self.exact && !rounded,
+        })
+    }
+}
+
+fn apply(op: char, lhs: Number, rhs: Number) -> Result<Number, CalcError> {
+    let exact = lhs.exact && rhs.exact;
+    let (lhs, rhs) = (lhs.value, rhs.value);
+
+    let result = match op {
+        '+' | '-' => {
+            let largest = magnitude(&lhs).max(magnitude(&rhs));
+            let value = if op == '+' { lhs + rhs } else { lhs - rhs };
+            // Rounded operands that cancel leave only rounding noise, as in exp(1) - e
+            if !exact && !value.is_zero() && magnitude(&value) < largest - CANCELLATION_DIGITS {
+                Number::approximate(BigDecimal::zero())
+            } else {
+                Number { value, exact }
+            }
+        }
+        '*' => Number {
+            value: lhs * rhs,
+            exact,
+        },
+        '/' => {
+            if rhs.is_zero() {
+                return Err(CalcError::DivisionByZero);
+            }
+            let value = &lhs / &rhs;
+            let exact = exact && &value * &rhs == lhs;
+            Number { value, exact }
+        }
+        '%' => {
+            if rhs.is_zero() {
+                return Err(CalcError::DivisionByZero);
+            }
+            Number {
+                value: lhs % rhs,
+                exact,
+            }
+        }
+        '^' => {
+            let result = power(&lhs, &rhs)?;
+            Number {
+                value: result.value,
+                exact: exact && result.exact,
+            }
+        }
+        _ => unreachable!("infix_power only accepts known operators"),
+    };
+    result.limited()
+}
+
+fn constant(name: &str) -> Option<BigDecimal> {
+    match name.to_lowercase().as_str() {
+        "pi" | "π" => Some(PI.clone()),
+        "tau" | "τ" => Some(PI.double()),
+        "e" => Some(E.clone()),
+        _ => None,
+    }
+}
+
+fn call(name: &str, position: usize, args: &[Number]) -> Result<Number, CalcError> {
+    let function = name.to_lowercase();
+    let arity = |expected: &'static str, ok: bool| {
+        if ok {
+            Ok(())
+        } else {
+            Err(CalcError::WrongArity {
+                function: function.clone(),
+                expected,
+                found: args.len(),
+            })
+        }
+    };
+    let exact = args.iter().all(|arg| arg.exact);
+
+    let result = match function.as_str() {
+        "min" | "max" => {
+            arity("at least 1", !args.is_empty())?;
+            let pick = args.iter().skip(1).fold(&args[0], |best, x| {
+                if (function == "min") == (x.value < best.value) {
+                    x
+                } else {
+                    best
+                }
+            });
+            pick.clone()
+        }
+        "round" => {
+            arity("1 or 2", matches!(args.len(), 1 | 2))?;
+            let digits = match args.get(1) {
+                Some(digits) => integer(&digits.value, "round")?,
+                None => 0,
+            };
+            Number {
+                value: args[0].value.with_scale_round(digits, RoundingMode::HalfUp),
+                exact,
+            }
+        }
+        "log" => {
+            arity("1 or 2", matches!(args.len(), 1 | 2))?;
+            let value = match args.get(1) {
+                Some(base) => {
+                    if !base.value.is_positive() || base.value.is_one() {
+                        return Err(CalcError::domain("log", "base must be positive and not 1"));
+                    }
+                    ln(&args[0].value, "log")? / ln(&base.value, "log")?
+                }
+                None => ln(&args[0].value, "log")? / &*LN10,
+            };
+            Number::approximate(value)
+        }
+        "pow" => {
+            arity("2", args.len() == 2)?;
+            let result = power(&args[0].value, &args[1].value)?;
+            Number {
+                value: result.value,
+                exact: exact && result.exact,
+            }
+        }
+        "sqrt" => {
+            arity("1", args.len() == 1)?;
+            let x = &args[0].value;
+            let value = precise(x.clone())
+                .sqrt_with_context(&context(WORKING_PRECISION))
+                .ok_or_else(|| CalcError::domain("sqrt", "argument must not be negative"))?;
+            let exact = exact && &value.square() == x;
+            Number { value, exact }
+        }
+        "cbrt" => {
+            arity("1", args.len() == 1)?;
+            let x = &args[0].value;
+            let value = x.cbrt_with_context(&context(WORKING_PRECISION));
+            let exact = exact && &value.cube() == x;
+            Number { value, exact }
+        }
+        "abs" | "floor" | "ceil" | "trunc" => {
+            arity("1", args.len() == 1)?;
+            let x = &args[0].value;
+            let value = match function.as_str() {
+                "abs" => x.abs(),
+                "floor" => x.with_scale_round(0, RoundingMode::Floor),
+                "ceil" => x.with_scale_round(0, RoundingMode::Ceiling),
+                _ => x.with_scale_round(0, RoundingMode::Down),
+            };
+            Number { value, exact }
+        }
+        _ => {
+            let unary: fn(&BigDecimal) -> Result<BigDecimal, CalcError> = match function.as_str() {
+                "exp" => exp,
+                "ln" => |x| ln(x, "ln"),
+                "log10" => |x| Ok(ln(x, "log10")? / &*LN10),
+                "log2" => |x| Ok(ln(x, "log2")? / &*LN2),
+                "sin" => |x| Ok(sin_cos(x, "sin")?.0),
+                "cos" => |x| Ok(sin_cos(x, "cos")?.1),
+                "tan" => tan,
+                "asin" => asin,
+                "acos" => |x| Ok(PI.half() - asin(x)?),
+                "atan" => |x| Ok(atan(x)),
+                _ => {
+                    return Err(CalcError::UnknownIdentifier {
+                        position,
+                        name: name.to_string(),
+                    })
+                }
+            };
+            arity("1", args.len() == 1)?;
+            Number::approximate(unary(&args[0].value)?)
+        }
+    };
+    result.limited()
+}
+
+fn context(precision: u64) -> Context {
+    Context::default().with_precision(NonZeroU64::new(precision).expect("precision is non-zero"))
+}
+
+/// Rounds to `digits` significant digits, half to even. Unlike
+/// `BigDecimal::with_prec` this rounds negative numbers too.
+fn round_to(x: &BigDecimal, digits: u64) -> BigDecimal {
+    if x.digits() <= digits {
+        return x.clone();
+    }
+    x.with_precision_round(
+        NonZeroU64::new(digits).expect("precision is non-zero"),
+        RoundingMode::HalfEven,
+    )
+}
+
+/// Rounds to the precision carried inside series.
+fn precise(x: BigDecimal) -> BigDecimal {
+    round_to(&x, WORKING_PRECISION + GUARD_DIGITS)
+}
+
+/// Smallest term worth adding to a series.
+fn epsilon() -> BigDecimal {
+    BigDecimal::new(BigInt::one(), (WORKING_PRECISION + GUARD_DIGITS) as i64)
+}
+
+/// Number of digits before the decimal point, i.e. `x` lies in
+/// [10^(m-1), 10^m). Negative for numbers below 0.1.
+fn magnitude(x: &BigDecimal) -> i64 {
+    let (_, scale) = x.as_bigint_and_exponent();
+    x.digits() as i64 - scale
+}
+
+/// Keeps results within the supported size and precision.
+fn limit(x: BigDecimal) -> Result<BigDecimal, CalcError> {
+    if x.is_zero() {
+        return Ok(x);
+    }
+    let magnitude = magnitude(&x);
+    // 10^MAX_MAGNITUDE itself has MAX_MAGNITUDE + 1 digits
+    if magnitude > MAX_MAGNITUDE + 1 {
+        return Err(CalcError::Overflow);
+    }
+    if magnitude < -MAX_MAGNITUDE {
+        return Ok(BigDecimal::zero());
+    }
+    if x.digits() > MAX_DIGITS {
+        return Ok(round_to(&x, MAX_DIGITS));
+    }
+    Ok(x)
+}
+
+fn integer(x: &BigDecimal, function: &str) -> Result<i64, CalcError> {
+    if !x.is_integer() {
+        return Err(CalcError::domain(function, "argument must be an integer"));
+    }
+    x.to_i64().ok_or(CalcError::Overflow)
+}
+
+/// Approximate log10 of |x|, for overflow checks before expensive work.
+fn log10_estimate(x: &BigDecimal) -> f64 {
+    let magnitude = magnitude(x);
+    let (digits, scale) = x.abs().into_bigint_and_exponent();
+    let mantissa = BigDecimal::new(digits, scale + magnitude)
+        .to_f64()
+        .unwrap_or(1.0);
+    magnitude as f64 + mantissa.log10()
+}
+
+fn factorial(n: &Number) -> Result<Number, CalcError> {
+    if n.value.is_negative() || !n.value.is_integer() {
+        return Err(CalcError::domain(
+            "factorial",
+            "argument must be a non-negative integer",
+        ));
+    }
+    let count = integer(&n.value, "factorial")? as u64;
+    if count > MAX_FACTORIAL {
+        return Err(CalcError::Overflow);
+    }
+    let product = (2..=count).fold(BigInt::one(), |acc, i| acc * i);
+    Number {
+        value: BigDecimal::from(product),
+        exact: n.exact,
+    }
+    .limited()
+}
+
+/// Raises `base` to `exponent`. The result is exact for integer exponents
+/// unless it had to be rounded.
+fn power(base: &BigDecimal, exponent: &BigDecimal) -> Result<Number, CalcError> {
+    if base.is_zero() {
+        return match exponent.sign() {
+            Sign::Plus => Ok(Number::exact(BigDecimal::zero())),
+            Sign::NoSign => Ok(Number::exact(BigDecimal::one())),
+            Sign::Minus => Err(CalcError::DivisionByZero),
+        };
+    }
+    if exponent.is_integer() && base.abs().is_one() {
+        let even = (exponent % BigDecimal::from(2)).is_zero();
+        let value = if base.is_negative() && !even {
+            -BigDecimal::one()
+        } else {
+            BigDecimal::one()
+        };
+        return Ok(Number::exact(value));
+    }
+
+    // Only roughly, so results near the limit are left to `limited`
+    let estimate = log10_estimate(base) * exponent.to_f64().unwrap_or(f64::MAX);
+    if estimate > (MAX_MAGNITUDE + 1) as f64 {
+        return Err(CalcError::Overflow);
+    }
+    if estimate < -(MAX_MAGNITUDE as f64) {
+        return Ok(Number::approximate(BigDecimal::zero()));
+    }
+
+    if exponent.is_integer() {
+        let n = integer(exponent, "pow")?;
+        let mut result = Number::exact(BigDecimal::one());
+        let mut square = Number::exact(base.clone());
+        let mut remaining = n.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = Number {
+                    value: &result.value * &square.value,
+                    exact: result.exact && square.exact,
+                }
+                .limited()?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                square = Number {
+                    value: square.value.square(),
+                    exact: square.exact,
+                }
+                .limited()?;
+            }
+        }
+        if n < 0 {
+            return apply('/', Number::exact(BigDecimal::one()), result);
+        }
+        return Ok(result);
+    }
+
+    if base.is_negative() {
+        return Err(CalcError::domain(
+            "pow",
+            "a negative number has no real fractional power",
+        ));
+    }
+    Ok(Number::approximate(exp(&precise(
+        ln(base, "pow")? * exponent,
+    ))?))
+}
+
+fn exp(x: &BigDecimal) -> Result<BigDecimal, CalcError> {
+    let bound = BigDecimal::from(MAX_MAGNITUDE) * &*LN10;
+    if x > &(&bound + &*LN10) {
+        return Err(CalcError::Overflow);
+    }
+    if x < &-bound {
+        return Ok(BigDecimal::zero());
+    }
+
+    // e^x = e^n * e^r with n the integer part and |r| < 1
+    let n = x.with_scale_round(0, RoundingMode::Down);
+    let r = x - &n;
+    let whole = power(&E, &n)?.value;
+    Ok(round_to(&(whole * exp_series(&r)), WORKING_PRECISION))
+}
+
+/// Taylor series for e^x, for |x| <= 1.
+fn exp_series(x: &BigDecimal) -> BigDecimal {
+    let epsilon = epsilon();
+    let mut sum = BigDecimal::one();
+    let mut term = BigDecimal::one();
+    for n in 1u32.. {
+        term = precise(term * x / n);
+        if term.abs() < epsilon {
+            break;
+        }
+        sum += &term;
+    }
+    precise(sum)
+}
+
+/// Series for atanh(x) = x + x^3/3 + x^5/5 + ..., for small |x|.
+fn atanh_series(x: &BigDecimal) -> BigDecimal {
+    let epsilon = epsilon();
+    let x_squared = precise(x.square());
+    let mut power = x.clone();
+    let mut sum = x.clone();
+    for n in (3u32..).step_by(2) {
+        power = precise(power * &x_squared);
+        let term = &power / n;
+        if term.abs() < epsilon {
+            break;
+        }
+        sum += term;
+    }
+    precise(sum)
+}
+
+fn ln(x: &BigDecimal, function: &str) -> Result<BigDecimal, CalcError> {
+    if !x.is_positive() {
+        return Err(CalcError::domain(function, "argument must be positive"));
+    }
+    if x.is_one() {
+        return Ok(BigDecimal::zero());
+    }
+
+    // Close to 1 the series converges directly and keeps the small result's
+    // relative precision
+    if (x - BigDecimal::one()).abs() < BigDecimal::new(BigInt::one(), 1) {
+        let z = precise((x - BigDecimal::one()) / (x + BigDecimal::one()));
+        return Ok(round_to(&(atanh_series(&z) * 2u32), WORKING_PRECISION));
+    }
+
+    // x = m * 10^k with m in [0.1, 1), then bring m close to 1 with square roots
+    // so the atanh series converges quickly
+    let k = magnitude(x);
+    let (digits, scale) = x.as_bigint_and_exponent();
+    let mut m = precise(BigDecimal::new(digits, scale + k));
+    let sqrt_context = context(WORKING_PRECISION + GUARD_DIGITS);
+    for _ in 0..4 {
+        m = precise(m.sqrt_with_context(&sqrt_context).expect("m is positive"));
+    }
+    let z = precise((&m - BigDecimal::one()) / (&m + BigDecimal::one()));
+    let ln_m = atanh_series(&z) * BigDecimal::from(32);
+    Ok(round_to(
+        &(ln_m + &*LN10 * BigDecimal::from(k)),
+        WORKING_PRECISION,
+    ))
+}
+
+/// Returns (sin x, cos x).
+fn sin_cos(x: &BigDecimal, function: &str) -> Result<(BigDecimal, BigDecimal), CalcError> {
+    if magnitude(x) > 20 {
+        return Err(CalcError::domain(
+            function,
+            "argument is too large for an accurate result",
+        ));
+    }
+
+    // Reduce to [-pi, pi]
+    let two_pi = PI.double();
+    let turns = (x / &two_pi).with_scale_round(0, RoundingMode::HalfEven);
+    let r = precise(x - turns * two_pi);
+
+    let epsilon = epsilon();
+    let r_squared = precise(r.square());
+    let mut sin = r.clone();
+    let mut cos = BigDecimal::one();
+    let mut sin_term = r;
+    let mut cos_term = BigDecimal::one();
+    for n in (2u32..).step_by(2) {
+        cos_term = precise(-cos_term * &r_squared / (n * (n - 1)));
+        sin_term = precise(-sin_term * &r_squared / (n * (n + 1)));
+        if cos_term.abs() < epsilon && sin_term.abs() < epsilon {
+            break;
+        }
+        cos += &cos_term;
+        sin += &sin_term;
+    }
+
+    Ok((
+        round_to(&round_tiny(sin), WORKING_PRECISION),
+        round_to(&round_tiny(cos), WORKING_PRECISION),
+    ))
+}
+
+/// Treats values lost in the guard digits as zero, so sin(pi) is 0.
+fn round_tiny(x: BigDecimal) -> BigDecimal {
+    if magnitude(&x) < -(WORKING_PRECISION as i64) {
+        BigDecimal::zero()
+    } else {
+        x
+    }
+}
+
+fn tan(x: &BigDecimal) -> Result<BigDecimal, CalcError> {
+    let (sin, cos) = sin_cos(x, "tan")?;
+    if cos.is_zero() {
+        return Err(CalcError::domain(
+            "tan",
+            "undefined at odd multiples of pi/2",
+        ));
+    }
+    Ok(round_to(&(sin / cos), WORKING_PRECISION))
+}
+
+fn asin(x: &BigDecimal) -> Result<BigDecimal, CalcError> {
+    let abs = x.abs();
+    if abs > BigDecimal::one() {
+        return Err(CalcError::domain(
+            "asin",
+            "argument must be between -1 and 1",
+        ));
+    }
+    if abs.is_one() {
+        let half_pi = round_to(&PI.half(), WORKING_PRECISION);
+        return Ok(if x.is_negative() { -half_pi } else { half_pi });
+    }
+    // asin(x) = atan(x / sqrt(1 - x^2))
+    let cos = precise(BigDecimal::one() - x.square())
+        .sqrt_with_context(&context(WORKING_PRECISION + GUARD_DIGITS))
+        .expect("1 - x^2 is positive");
+    Ok(atan(&precise(x / cos)))
+}
+
+fn atan(x: &BigDecimal) -> BigDecimal {
+    if x.is_zero() {
+        return BigDecimal::zero();
+    }
+    let abs = x.abs();
+    let result = if abs > BigDecimal::one() {
+        PI.half() - atan_reduced(&precise(BigDecimal::one() / abs))
+    } else {
+        atan_reduced(&abs)
+    };
+    let result = round_to(&result, WORKING_PRECISION);
+    if x.is_negative() {
+        -result
+    } else {
+        result
+    }
+}
+
+/// atan for 0 < x <= 1, halving the angle until the series converges quickly.
+fn atan_reduced(x: &BigDecimal) -> BigDecimal {
+    let sqrt_context = context(WORKING_PRECISION + GUARD_DIGITS);
+    let mut x = x.clone();
+    for _ in 0..3 {
+        // atan(x) = 2 atan(x / (1 + sqrt(1 + x^2)))
+        // Rounded first: sqrt loses digits on very long inputs
+        let root = precise(BigDecimal::one() + x.square())
+            .sqrt_with_context(&sqrt_context)
+            .expect("1 + x^2 is positive");
+        x = precise(&x / (BigDecimal::one() + root));
+    }
+    atan_series(&x) * BigDecimal::from(8)
+}
+
+/// Series for atan(x) = x - x^3/3 + x^5/5 - ..., for small |x|.
+fn atan_series(x: &BigDecimal) -> BigDecimal {
+    let epsilon = epsilon();
+    let x_squared = precise(x.square());
+    let mut power = x.clone();
+    let mut sum = x.clone();
+    for n in (3u32..).step_by(2) {
+        power = precise(-power * &x_squared);
+        let term = &power / n;
+        if term.abs() < epsilon {
+            break;
+        }
+        sum += term;
+    }
+    precise(sum)
+}
@@ -0,0 +1,112 @@
+use super::repo_map::CLONE_TIMEOUT;
+use super::{RiskLevel, ToolContext, ToolExecutor};
+use crate::repo::{run_cargo_tests_with_timeout, RepoCache, DEFAULT_TEST_TIMEOUT};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const MAX_TEST_TIMEOUT_SECS: u64 = 30 * 60;
+
+/// Runs `cargo test` in a repository and reports which tests failed and why.
+pub struct RunCargoTests {
+    cache: Arc<RepoCache>,
+}
+
+impl RunCargoTests {
+    pub fn new(cache: Arc<RepoCache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoTestArguments {
+    repository: String,
+    timeout_secs: Option<u64>,
+}
+
+#[async_trait]
+impl ToolExecutor for RunCargoTests {
+    fn name(&self) -> &str {
+        "run_cargo_tests"
+    }
+
+    fn description(&self) -> &str {
+        "Run cargo test in a Rust repository and report the failing tests and compile errors"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "repository": {
+                    "type": "string",
+                    "description": "GitHub owner/name, https:// git URL or configured repository name",
+                    "minLength": 1
+                },
+                "timeout_secs": {
+                    "type": "integer",
+                    "description": "Stop the run after this many seconds",
+                    "minimum": 1,
+                    "maximum": MAX_TEST_TIMEOUT_SECS
+                }
+            },
+            "required": ["repository"]
+        })
+    }
+
+    /// Past the longest run the arguments allow, leaving time to clone and
+    /// build.
+    fn timeout(&self) -> Option<Duration> {
+        Some(Duration::from_secs(MAX_TEST_TIMEOUT_SECS) + CLONE_TIMEOUT)
+    }
+
+    /// Building and running a repository's tests executes its code.
+    fn risk(&self) -> RiskLevel {
+        RiskLevel::SideEffecting
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["repository"].as_str() {
+            Some(repository) => format!("Running the tests of {}", repository),
+            None => "Running tests".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: CargoTestArguments = serde_json::from_value(arguments)?;
+        let repo_path = self.cache.checkout(&arguments.repository).await?;
+        let timeout = arguments
+            .timeout_secs
+            .map_or(DEFAULT_TEST_TIMEOUT, Duration::from_secs);
+        let report = run_cargo_tests_with_timeout(&repo_path, timeout).await?;
+
+        let failures: Vec<Value> = report
+            .failures()
+            .map(|test| {
+                json!({
+                    "target": test.target,
+                    "name": test.name,
+                    "status": test.status.to_string(),
+                    "failure": test.failure
+                })
+            })
+            .collect();
+        let compile_errors: Vec<&str> = report
+            .compile_errors()
+            .map(|diagnostic| diagnostic.rendered.as_str())
+            .collect();
+        Ok(json!({
+            "repository": arguments.repository,
+            "success": report.success(),
+            "summary": report.summary(),
+            "exit_code": report.exit_code,
+            "timed_out": report.timed_out,
+            "failures": failures,
+            "compile_errors": compile_errors
+        })
+        .to_string())
+    }
+}
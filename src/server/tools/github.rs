@@ -0,0 +1,482 @@
+use super::{RiskLevel, ToolContext, ToolExecutor};
+use crate::server::services::github_issue::{
+    GitHubIssue, GitHubService, IssueFilter, DEFAULT_LIST_LIMIT,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// Fetches a GitHub issue by number, with its discussion.
+pub struct ReadGitHubIssue {
+    github_service: Arc<GitHubService>,
+}
+
+impl ReadGitHubIssue {
+    pub fn new(github_service: Arc<GitHubService>) -> Self {
+        Self { github_service }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueArguments {
+    owner: String,
+    repo: String,
+    issue_number: i32,
+}
+
+#[async_trait]
+impl ToolExecutor for ReadGitHubIssue {
+    fn name(&self) -> &str {
+        "read_github_issue"
+    }
+
+    fn description(&self) -> &str {
+        "Read a GitHub issue by number, with its comments"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {
+                    "type": "string",
+                    "description": "The owner of the repository"
+                },
+                "repo": {
+                    "type": "string",
+                    "description": "The name of the repository"
+                },
+                "issue_number": {
+                    "type": "integer",
+                    "description": "The issue number",
+                    "minimum": 1
+                }
+            },
+            "required": ["owner", "repo", "issue_number"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match IssueArguments::deserialize(arguments) {
+            Ok(issue) => format!(
+                "Fetching GitHub issue #{} from {}/{}",
+                issue.issue_number, issue.owner, issue.repo
+            ),
+            Err(_) => "Fetching GitHub issue".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let IssueArguments {
+            owner,
+            repo,
+            issue_number,
+        } = serde_json::from_value(arguments)?;
+        let thread = self
+            .github_service
+            .get_issue_thread(&owner, &repo, issue_number)
+            .await?;
+        Ok(serde_json::to_string(&thread)?)
+    }
+}
+
+/// Posts a comment on a GitHub issue or pull request. Needs the user's
+/// approval for every call.
+pub struct PostGitHubComment {
+    github_service: Arc<GitHubService>,
+}
+
+impl PostGitHubComment {
+    pub fn new(github_service: Arc<GitHubService>) -> Self {
+        Self { github_service }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CommentArguments {
+    owner: String,
+    repo: String,
+    issue_number: i32,
+    body: String,
+}
+
+#[async_trait]
+impl ToolExecutor for PostGitHubComment {
+    fn name(&self) -> &str {
+        "post_github_comment"
+    }
+
+    fn description(&self) -> &str {
+        "Post a comment on a GitHub issue or pull request. The user is asked to approve each comment first."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {
+                    "type": "string",
+                    "description": "The owner of the repository"
+                },
+                "repo": {
+                    "type": "string",
+                    "description": "The name of the repository"
+                },
+                "issue_number": {
+                    "type": "integer",
+                    "description": "The issue or pull request number",
+                    "minimum": 1
+                },
+                "body": {
+                    "type": "string",
+                    "description": "The comment, in GitHub Markdown",
+                    "minLength": 1
+                }
+            },
+            "required": ["owner", "repo", "issue_number", "body"]
+        })
+    }
+
+    fn risk(&self) -> RiskLevel {
+        RiskLevel::SideEffecting
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match CommentArguments::deserialize(arguments) {
+            Ok(comment) => format!(
+                "Posting a comment on {}/{}#{}",
+                comment.owner, comment.repo, comment.issue_number
+            ),
+            Err(_) => "Posting a GitHub comment".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let CommentArguments {
+            owner,
+            repo,
+            issue_number,
+            body,
+        } = serde_json::from_value(arguments)?;
+        self.github_service
+            .post_comment(&owner, &repo, issue_number, &body)
+            .await?;
+        Ok(json!({
+            "posted": true,
+            "issue": format!("{}/{}#{}", owner, repo, issue_number)
+        })
+        .to_string())
+    }
+}
+
+/// Most issues a listing or search tool returns, to keep results readable.
+pub const MAX_TOOL_ISSUES: usize = 100;
+
+/// An issue in a listing, without its body.
+fn issue_summary(issue: &GitHubIssue) -> Value {
+    json!({
+        "number": issue.number,
+        "title": issue.title,
+        "state": issue.state,
+        "pull_request": issue.is_pull_request(),
+        "author": issue.user.as_ref().map(|user| &user.login),
+        "labels": issue.labels.iter().map(|label| &label.name).collect::<Vec<_>>(),
+        "comments": issue.comments,
+        "html_url": issue.html_url
+    })
+}
+
+/// Lists a repository's issues and pull requests, newest first.
+pub struct ListGitHubIssues {
+    github_service: Arc<GitHubService>,
+}
+
+impl ListGitHubIssues {
+    pub fn new(github_service: Arc<GitHubService>) -> Self {
+        Self { github_service }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListIssuesArguments {
+    owner: String,
+    repo: String,
+    state: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    limit: Option<usize>,
+}
+
+#[async_trait]
+impl ToolExecutor for ListGitHubIssues {
+    fn name(&self) -> &str {
+        "list_github_issues"
+    }
+
+    fn description(&self) -> &str {
+        "List a GitHub repository's issues and pull requests, newest first, optionally filtered by state and labels"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {
+                    "type": "string",
+                    "description": "The owner of the repository"
+                },
+                "repo": {
+                    "type": "string",
+                    "description": "The name of the repository"
+                },
+                "state": {
+                    "type": "string",
+                    "enum": ["open", "closed", "all"],
+                    "description": "Which issues to list (default: open)"
+                },
+                "labels": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Only issues with all of these labels"
+                },
+                "limit": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_TOOL_ISSUES,
+                    "description": "How many issues to list (default: 30)"
+                }
+            },
+            "required": ["owner", "repo"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match ListIssuesArguments::deserialize(arguments) {
+            Ok(list) => format!("Listing issues of {}/{}", list.owner, list.repo),
+            Err(_) => "Listing GitHub issues".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: ListIssuesArguments = serde_json::from_value(arguments)?;
+        let filter = IssueFilter {
+            state: arguments.state,
+            labels: arguments.labels,
+            limit: arguments.limit,
+            ..Default::default()
+        };
+        let issues = self
+            .github_service
+            .list_issues(&arguments.owner, &arguments.repo, &filter)
+            .await?;
+        Ok(Value::Array(issues.iter().map(issue_summary).collect()).to_string())
+    }
+}
+
+/// Searches issues and pull requests across GitHub.
+pub struct SearchGitHubIssues {
+    github_service: Arc<GitHubService>,
+}
+
+impl SearchGitHubIssues {
+    pub fn new(github_service: Arc<GitHubService>) -> Self {
+        Self { github_service }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchArguments {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[async_trait]
+impl ToolExecutor for SearchGitHubIssues {
+    fn name(&self) -> &str {
+        "search_github_issues"
+    }
+
+    fn description(&self) -> &str {
+        "Search GitHub issues and pull requests with GitHub search syntax, e.g. \"repo:owner/name is:issue is:open crash\""
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "The search query, with qualifiers such as repo:, is:, label: and author:",
+                    "minLength": 1
+                },
+                "limit": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_TOOL_ISSUES,
+                    "description": "How many matches to return (default: 30)"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["query"].as_str() {
+            Some(query) => format!("Searching GitHub for {}", query),
+            None => "Searching GitHub issues".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: SearchArguments = serde_json::from_value(arguments)?;
+        let search = self
+            .github_service
+            .search_issues(
+                &arguments.query,
+                arguments.limit.unwrap_or(DEFAULT_LIST_LIMIT),
+            )
+            .await?;
+        Ok(json!({
+            "total_count": search.total_count,
+            "incomplete_results": search.incomplete_results,
+            "items": search.items.iter().map(issue_summary).collect::<Vec<_>>()
+        })
+        .to_string())
+    }
+}
+
+/// Fetches a pull request with the files it changes and their patches.
+pub struct ReadPullRequest {
+    github_service: Arc<GitHubService>,
+}
+
+impl ReadPullRequest {
+    pub fn new(github_service: Arc<GitHubService>) -> Self {
+        Self { github_service }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestArguments {
+    owner: String,
+    repo: String,
+    pull_number: i32,
+}
+
+#[async_trait]
+impl ToolExecutor for ReadPullRequest {
+    fn name(&self) -> &str {
+        "read_pull_request"
+    }
+
+    fn description(&self) -> &str {
+        "Read a GitHub pull request by number, with the files it changes and their diffs"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {
+                    "type": "string",
+                    "description": "The owner of the repository"
+                },
+                "repo": {
+                    "type": "string",
+                    "description": "The name of the repository"
+                },
+                "pull_number": {
+                    "type": "integer",
+                    "description": "The pull request number",
+                    "minimum": 1
+                }
+            },
+            "required": ["owner", "repo", "pull_number"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match PullRequestArguments::deserialize(arguments) {
+            Ok(pull) => format!(
+                "Fetching pull request #{} from {}/{}",
+                pull.pull_number, pull.owner, pull.repo
+            ),
+            Err(_) => "Fetching pull request".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let PullRequestArguments {
+            owner,
+            repo,
+            pull_number,
+        } = serde_json::from_value(arguments)?;
+        let (pull_request, files) = tokio::try_join!(
+            self.github_service
+                .get_pull_request(&owner, &repo, pull_number),
+            self.github_service
+                .get_pull_request_files(&owner, &repo, pull_number)
+        )?;
+        Ok(json!({ "pull_request": pull_request, "files": files }).to_string())
+    }
+}
+
+/// Lists the labels defined in a repository.
+pub struct ListGitHubLabels {
+    github_service: Arc<GitHubService>,
+}
+
+impl ListGitHubLabels {
+    pub fn new(github_service: Arc<GitHubService>) -> Self {
+        Self { github_service }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryArguments {
+    owner: String,
+    repo: String,
+}
+
+#[async_trait]
+impl ToolExecutor for ListGitHubLabels {
+    fn name(&self) -> &str {
+        "list_github_labels"
+    }
+
+    fn description(&self) -> &str {
+        "List the labels defined in a GitHub repository"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {
+                    "type": "string",
+                    "description": "The owner of the repository"
+                },
+                "repo": {
+                    "type": "string",
+                    "description": "The name of the repository"
+                }
+            },
+            "required": ["owner", "repo"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match RepositoryArguments::deserialize(arguments) {
+            Ok(repository) => format!("Listing labels of {}/{}", repository.owner, repository.repo),
+            Err(_) => "Listing GitHub labels".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let RepositoryArguments { owner, repo } = serde_json::from_value(arguments)?;
+        let labels = self.github_service.list_labels(&owner, &repo).await?;
+        Ok(serde_json::to_string(&labels)?)
+    }
+}
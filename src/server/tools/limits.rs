@@ -0,0 +1,97 @@
+use std::env;
+use std::fmt;
+use std::time::Duration;
+use tracing::warn;
+
+/// How long a call may run unless its tool asks for longer.
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest result, in characters, sent back to the model.
+pub const DEFAULT_MAX_OUTPUT_CHARS: usize = 50_000;
+
+/// Bounds on every call run through a [`super::ToolRegistry`], so one slow or
+/// verbose tool can't stall a turn or fill the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolLimits {
+    pub timeout: Duration,
+    pub max_output_chars: usize,
+}
+
+impl Default for ToolLimits {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TOOL_TIMEOUT,
+            max_output_chars: DEFAULT_MAX_OUTPUT_CHARS,
+        }
+    }
+}
+
+impl ToolLimits {
+    /// The defaults, overridden by `TOOL_TIMEOUT_SECS` and
+    /// `TOOL_MAX_OUTPUT_CHARS`.
+    pub fn from_env() -> Self {
+        let mut limits = Self::default();
+        if let Some(secs) = positive_var("TOOL_TIMEOUT_SECS") {
+            limits.timeout = Duration::from_secs(secs as u64);
+        }
+        if let Some(chars) = positive_var("TOOL_MAX_OUTPUT_CHARS") {
+            limits.max_output_chars = chars;
+        }
+        limits
+    }
+}
+
+fn positive_var(name: &str) -> Option<usize> {
+    let value = env::var(name).ok()?;
+    match value.trim().parse() {
+        Ok(parsed) if parsed > 0 => Some(parsed),
+        _ => {
+            warn!("Ignoring {}={:?}: expected a positive number", name, value);
+            None
+        }
+    }
+}
+
+/// A call ran past its timeout and was dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolTimedOut {
+    pub tool: String,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for ToolTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} did not finish within {}s",
+            self.tool,
+            self.timeout.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for ToolTimedOut {}
+
+/// Cuts `output` down to about `max_chars` characters, keeping its start and
+/// end around a marker saying how much was left out.
+pub fn truncate_output(output: String, max_chars: usize) -> String {
+    let total = output.chars().count();
+    if total <= max_chars {
+        return output;
+    }
+    let head_chars = max_chars / 2;
+    let tail_chars = max_chars - head_chars;
+    let byte_at = |chars: usize| {
+        output
+            .char_indices()
+            .nth(chars)
+            .map_or(output.len(), |(index, _)| index)
+    };
+    let head_end = byte_at(head_chars);
+    let tail_start = byte_at(total - tail_chars);
+    format!(
+        "{}\n[... {} characters truncated ...]\n{}",
+        &output[..head_end],
+        total - head_chars - tail_chars,
+        &output[tail_start..]
+    )
+}
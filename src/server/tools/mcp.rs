@@ -0,0 +1,127 @@
+use super::{RiskLevel, ToolContext, ToolExecutor, ToolRegistry};
+use crate::server::mcp::{McpClient, McpConfig, ToolInfo};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// A tool of an MCP server, proxied over its connection.
+pub struct McpTool {
+    client: Arc<McpClient>,
+    info: ToolInfo,
+}
+
+impl McpTool {
+    pub fn new(client: Arc<McpClient>, info: ToolInfo) -> Self {
+        Self { client, info }
+    }
+}
+
+/// The MCP server reported that the call failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct McpToolFailed {
+    pub tool: String,
+    pub message: String,
+}
+
+impl fmt::Display for McpToolFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.tool, self.message)
+    }
+}
+
+impl std::error::Error for McpToolFailed {}
+
+#[async_trait]
+impl ToolExecutor for McpTool {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn description(&self) -> &str {
+        self.info.description.as_deref().unwrap_or_default()
+    }
+
+    fn parameters(&self) -> Value {
+        self.info.input_schema.clone()
+    }
+
+    /// Servers can't be trusted not to change things unless they say so, or
+    /// the server is configured as read-only.
+    fn risk(&self) -> RiskLevel {
+        let read_only_hint = self
+            .info
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.read_only_hint);
+        if self.client.config().read_only || read_only_hint == Some(true) {
+            RiskLevel::ReadOnly
+        } else {
+            RiskLevel::SideEffecting
+        }
+    }
+
+    fn status(&self, _arguments: &Value) -> String {
+        format!("Calling {} on {}", self.info.name, self.client.name())
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let result = self.client.call_tool(&self.info.name, arguments).await?;
+        if result.is_error {
+            return Err(McpToolFailed {
+                tool: self.info.name.clone(),
+                message: result.to_text(),
+            }
+            .into());
+        }
+        Ok(result.to_text())
+    }
+}
+
+/// Connects to `client`'s server and returns its tools.
+pub async fn mcp_tools(client: Arc<McpClient>) -> Result<ToolRegistry> {
+    let mut registry = ToolRegistry::new();
+    for info in client.list_tools().await? {
+        registry.register(McpTool::new(client.clone(), info));
+    }
+    Ok(registry)
+}
+
+/// The tools of every server in `config`. Servers that fail to start are
+/// logged and left out, so one broken server doesn't take the others down.
+pub async fn create_mcp_tools(config: &McpConfig) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    for (name, server) in &config.servers {
+        let tools = match McpClient::spawn(name, server).await {
+            Ok(client) => mcp_tools(client).await,
+            Err(e) => Err(e),
+        };
+        match tools {
+            Ok(tools) => {
+                info!("MCP server {} offers {:?}", name, tools.names());
+                for skipped in registry.merge(tools) {
+                    warn!("MCP tool {} from {} is already defined", skipped, name);
+                }
+            }
+            Err(e) => warn!("Leaving out MCP server {}: {:#}", name, e),
+        }
+    }
+    registry
+}
+
+/// [`create_mcp_tools`] for the config file named by `MCP_CONFIG`, or no tools
+/// when it isn't set.
+pub async fn mcp_tools_from_env() -> ToolRegistry {
+    let Ok(path) = std::env::var("MCP_CONFIG") else {
+        return ToolRegistry::new();
+    };
+    match McpConfig::load(&path) {
+        Ok(config) => create_mcp_tools(&config).await,
+        Err(e) => {
+            warn!("Not loading MCP tools: {:#}", e);
+            ToolRegistry::new()
+        }
+    }
+}
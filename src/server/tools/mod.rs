@@ -0,0 +1,306 @@
+pub mod approval;
+pub mod calculate;
+pub mod cargo_test;
+pub mod github;
+pub mod limits;
+pub mod mcp;
+pub mod repo_map;
+pub mod schema;
+pub mod workspace;
+
+use crate::server::services::deepseek::{ChatMessage, DeepSeekService, Tool, ToolCallResponse};
+use crate::server::services::github_issue::GitHubService;
+use crate::server::services::github_rate_limit::RateLimitExceeded;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+pub use approval::{Approval, ApprovalRequest, CallNotApproved, RiskLevel, ToolApprover};
+pub use calculate::{CalcError, Calculate};
+pub use cargo_test::RunCargoTests;
+pub use github::{
+    ListGitHubIssues, ListGitHubLabels, PostGitHubComment, ReadGitHubIssue, ReadPullRequest,
+    SearchGitHubIssues,
+};
+pub use limits::{truncate_output, ToolLimits, ToolTimedOut};
+pub use mcp::{create_mcp_tools, mcp_tools_from_env, McpTool, McpToolFailed};
+pub use repo_map::{GetRepoMap, RepoMap};
+pub use schema::{InvalidArguments, SchemaViolation};
+pub use workspace::{
+    create_workspace_tools, Grep, ListDir, ReadFile, Workspace, WorkspaceError, WriteFile,
+};
+
+/// A tool the model can call.
+#[async_trait]
+pub trait ToolExecutor: Send + Sync {
+    /// Name the model calls the tool by.
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// JSON Schema for the arguments object.
+    fn parameters(&self) -> Value;
+
+    /// Whether calls need the user's approval before they run.
+    fn risk(&self) -> RiskLevel {
+        RiskLevel::ReadOnly
+    }
+
+    /// How long calls may run, when it isn't the registry's
+    /// [`ToolLimits::timeout`].
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Short progress message shown while the call runs.
+    fn status(&self, _arguments: &Value) -> String {
+        format!("Running {}", self.name())
+    }
+
+    /// Runs the call and returns the content of the tool message sent back to
+    /// the model.
+    async fn execute(&self, arguments: Value, context: &ToolContext) -> Result<String>;
+}
+
+/// Where a tool call comes from.
+#[derive(Clone, Default)]
+pub struct ToolContext {
+    /// Conversation the call belongs to, when there is one.
+    pub conversation_id: Option<Uuid>,
+    /// Cancelled when the user stops the generation.
+    pub cancel: CancellationToken,
+    /// Asks the user about side-effecting calls. Without one they are denied.
+    pub approver: Option<Arc<dyn ToolApprover>>,
+}
+
+impl fmt::Debug for ToolContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolContext")
+            .field("conversation_id", &self.conversation_id)
+            .field("cancel", &self.cancel)
+            .field("approver", &self.approver.is_some())
+            .finish()
+    }
+}
+
+/// The model called a tool that isn't registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTool {
+    pub tool: String,
+}
+
+impl fmt::Display for UnknownTool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown tool: {}", self.tool)
+    }
+}
+
+impl std::error::Error for UnknownTool {}
+
+/// The tools offered to the model, looked up by name when it calls one.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn ToolExecutor>>,
+    limits: ToolLimits,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bounds every call's run time and result size by `limits`.
+    pub fn set_limits(&mut self, limits: ToolLimits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> ToolLimits {
+        self.limits
+    }
+
+    /// Adds `tool`, replacing any tool registered under the same name.
+    pub fn register(&mut self, tool: impl ToolExecutor + 'static) -> &mut Self {
+        self.tools.retain(|existing| existing.name() != tool.name());
+        self.tools.push(Arc::new(tool));
+        self
+    }
+
+    /// Adds the tools in `other` whose names aren't taken yet, and returns the
+    /// names of the ones left out.
+    pub fn merge(&mut self, other: ToolRegistry) -> Vec<String> {
+        let mut skipped = Vec::new();
+        for tool in other.tools {
+            if self.get(tool.name()).is_some() {
+                skipped.push(tool.name().to_string());
+            } else {
+                self.tools.push(tool);
+            }
+        }
+        skipped
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn ToolExecutor>> {
+        self.tools.iter().find(|tool| tool.name() == name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|tool| tool.name()).collect()
+    }
+
+    /// Tool definitions to send with a chat request.
+    pub fn definitions(&self) -> Vec<Tool> {
+        self.tools
+            .iter()
+            .map(|tool| {
+                DeepSeekService::create_tool(
+                    tool.name().to_string(),
+                    Some(tool.description().to_string()),
+                    tool.parameters(),
+                )
+            })
+            .collect()
+    }
+
+    /// Progress message for `call`.
+    pub fn status(&self, call: &ToolCallResponse) -> String {
+        match self.get(&call.function.name) {
+            Some(tool) => tool.status(&parse_arguments(call).unwrap_or(Value::Null)),
+            None => format!("Running {}", call.function.name),
+        }
+    }
+
+    /// Runs `call` with the registered tool of the same name. Arguments that
+    /// don't match the tool's schema fail with [`InvalidArguments`], and
+    /// side-effecting calls the user doesn't approve with [`CallNotApproved`],
+    /// without running the tool. Calls that outlast the timeout fail with
+    /// [`ToolTimedOut`], and results past the size limit are truncated.
+    pub async fn execute(&self, call: &ToolCallResponse, context: &ToolContext) -> Result<String> {
+        let tool = self.get(&call.function.name).ok_or_else(|| UnknownTool {
+            tool: call.function.name.clone(),
+        })?;
+        let arguments = parse_arguments(call)?;
+        let violations = schema::validate(&tool.parameters(), &arguments);
+        if !violations.is_empty() {
+            return Err(InvalidArguments {
+                tool: call.function.name.clone(),
+                violations,
+            }
+            .into());
+        }
+
+        if tool.risk() == RiskLevel::SideEffecting {
+            let request = ApprovalRequest {
+                tool: call.function.name.clone(),
+                description: tool.status(&arguments),
+                arguments: arguments.clone(),
+                risk: tool.risk(),
+            };
+            let approval = match &context.approver {
+                Some(approver) => approver.approve(&request).await,
+                None => Approval::Denied {
+                    reason: Some("nobody is available to approve it".to_string()),
+                },
+            };
+            if approval != Approval::Approved {
+                return Err(CallNotApproved {
+                    tool: request.tool,
+                    approval,
+                }
+                .into());
+            }
+        }
+
+        // Approval isn't timed, only the call itself
+        let timeout = tool.timeout().unwrap_or(self.limits.timeout);
+        let output = tokio::time::timeout(timeout, tool.execute(arguments, context))
+            .await
+            .map_err(|_| ToolTimedOut {
+                tool: call.function.name.clone(),
+                timeout,
+            })??;
+        Ok(truncate_output(output, self.limits.max_output_chars))
+    }
+
+    /// Runs `call` and wraps the result in the tool message answering it.
+    pub async fn tool_message(
+        &self,
+        call: &ToolCallResponse,
+        context: &ToolContext,
+    ) -> Result<ChatMessage> {
+        Ok(ChatMessage {
+            role: "tool".to_string(),
+            content: self.execute(call, context).await?,
+            tool_call_id: Some(call.id.clone()),
+            tool_calls: None,
+        })
+    }
+}
+
+/// Tool result content for a call that failed, so the model can correct
+/// itself or explain the problem: `{"error": ..., "kind": ...}`, plus details
+/// for some kinds.
+pub fn error_content(error: &anyhow::Error) -> String {
+    if let Some(invalid) = error.downcast_ref::<InvalidArguments>() {
+        return invalid.to_json().to_string();
+    }
+    if let Some(calc) = error.downcast_ref::<CalcError>() {
+        let mut content = json!({ "error": calc.to_string(), "kind": calc.kind() });
+        if let Some(position) = calc.position() {
+            content["position"] = position.into();
+        }
+        return content.to_string();
+    }
+    let kind = if let Some(not_approved) = error.downcast_ref::<CallNotApproved>() {
+        not_approved.kind()
+    } else if let Some(workspace) = error.downcast_ref::<WorkspaceError>() {
+        workspace.kind()
+    } else if error.is::<ToolTimedOut>() {
+        "timeout"
+    } else if error.is::<UnknownTool>() {
+        "unknown_tool"
+    } else if error.is::<McpToolFailed>() {
+        "tool_failed"
+    } else if error.is::<RateLimitExceeded>() {
+        "rate_limited"
+    } else {
+        "error"
+    };
+    json!({ "error": error.to_string(), "kind": kind }).to_string()
+}
+
+/// Models send an empty string for calls without arguments.
+fn parse_arguments(call: &ToolCallResponse) -> Result<Value, InvalidArguments> {
+    let arguments = call.function.arguments.trim();
+    if arguments.is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+    serde_json::from_str(arguments).map_err(|e| InvalidArguments {
+        tool: call.function.name.clone(),
+        violations: vec![SchemaViolation {
+            path: String::new(),
+            message: format!("are not valid JSON: {}", e),
+        }],
+    })
+}
+
+/// The tools available to the web chat and the CLIs, with the limits from
+/// [`ToolLimits::from_env`].
+pub fn create_tools(github_service: Arc<GitHubService>) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    registry
+        .set_limits(ToolLimits::from_env())
+        .register(ReadGitHubIssue::new(github_service.clone()))
+        .register(Calculate)
+        .register(PostGitHubComment::new(github_service.clone()))
+        .register(ListGitHubIssues::new(github_service.clone()))
+        .register(SearchGitHubIssues::new(github_service.clone()))
+        .register(ReadPullRequest::new(github_service.clone()))
+        .register(ListGitHubLabels::new(github_service));
+    registry
+}
@@ -0,0 +1,160 @@
+use super::{ToolContext, ToolExecutor, Workspace};
+use crate::repo::RepoCache;
+use crate::repomap::generate_repo_map_for_paths;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_MAP_CHARS: usize = 20_000;
+/// Below [`super::limits::DEFAULT_MAX_OUTPUT_CHARS`], so maps are budgeted
+/// by whole files rather than cut by the registry.
+pub const MAX_MAP_CHARS: usize = 40_000;
+/// How long the first call for a repository may take to clone it.
+pub const CLONE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Maps a repository's files, functions, traits and impls, so the model can
+/// answer questions about its structure.
+#[derive(Clone)]
+pub struct GetRepoMap {
+    cache: Arc<RepoCache>,
+    description: String,
+}
+
+/// A map cut down to a size budget.
+#[derive(Debug, Clone)]
+pub struct RepoMap {
+    pub map: String,
+    pub files: usize,
+    pub omitted_files: usize,
+}
+
+impl GetRepoMap {
+    pub fn new(cache: Arc<RepoCache>) -> Self {
+        let mut description = "Get a map of a repository's source files with the functions, traits and impls in each. Give a GitHub owner/name or an https:// git URL".to_string();
+        if !cache.local_repos().is_empty() {
+            let names: Vec<&str> = cache.local_repos().keys().map(String::as_str).collect();
+            description.push_str(&format!(", or one of: {}", names.join(", ")));
+        }
+        description.push('.');
+        Self { cache, description }
+    }
+
+    pub fn cache(&self) -> &Arc<RepoCache> {
+        &self.cache
+    }
+
+    /// Maps `paths` of `repository`, or all of it when `paths` is empty,
+    /// keeping whole files up to `max_chars`.
+    pub async fn generate(
+        &self,
+        repository: &str,
+        paths: Vec<String>,
+        max_chars: usize,
+    ) -> Result<RepoMap> {
+        let repo_path = self.cache.checkout(repository).await?;
+        let map = tokio::task::spawn_blocking(move || -> Result<String> {
+            let workspace = Workspace::new(&repo_path)?;
+            let paths = if paths.is_empty() {
+                vec![workspace.root().to_path_buf()]
+            } else {
+                paths
+                    .iter()
+                    .map(|path| workspace.resolve(path))
+                    .collect::<Result<Vec<PathBuf>, _>>()?
+            };
+            Ok(generate_repo_map_for_paths(workspace.root(), &paths, &[]))
+        })
+        .await??;
+
+        // Whole files only, in map order
+        let mut budgeted = RepoMap {
+            map: String::new(),
+            files: 0,
+            omitted_files: 0,
+        };
+        for file in map.split_inclusive("\n\n") {
+            if budgeted.omitted_files == 0 && budgeted.map.len() + file.len() <= max_chars {
+                budgeted.map.push_str(file);
+                budgeted.files += 1;
+            } else {
+                budgeted.omitted_files += 1;
+            }
+        }
+        Ok(budgeted)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoMapArguments {
+    repository: String,
+    #[serde(default)]
+    paths: Vec<String>,
+    max_chars: Option<usize>,
+}
+
+#[async_trait]
+impl ToolExecutor for GetRepoMap {
+    fn name(&self) -> &str {
+        "get_repo_map"
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "repository": {
+                    "type": "string",
+                    "description": "GitHub owner/name, https:// git URL or configured repository name",
+                    "minLength": 1
+                },
+                "paths": {
+                    "type": "array",
+                    "description": "Only map these files or directories, relative to the repository root",
+                    "items": {"type": "string"}
+                },
+                "max_chars": {
+                    "type": "integer",
+                    "description": "Size budget for the map; files past it are left out",
+                    "minimum": 1000,
+                    "maximum": MAX_MAP_CHARS
+                }
+            },
+            "required": ["repository"]
+        })
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        Some(CLONE_TIMEOUT)
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["repository"].as_str() {
+            Some(repository) => format!("Mapping {}", repository),
+            None => "Mapping a repository".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: RepoMapArguments = serde_json::from_value(arguments)?;
+        let max_chars = arguments.max_chars.unwrap_or(DEFAULT_MAP_CHARS);
+        let map = self
+            .generate(&arguments.repository, arguments.paths, max_chars)
+            .await?;
+        Ok(json!({
+            "repository": arguments.repository,
+            "map": map.map,
+            "files": map.files,
+            "omitted_files": map.omitted_files,
+            "truncated": map.omitted_files > 0
+        })
+        .to_string())
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// One way the arguments break the schema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending value, empty for the arguments object
+    /// itself.
+    pub path: String,
+    pub message: String,
+}
+
+/// A tool was called with arguments that don't match its declared schema.
+/// Sent back to the model as the tool result so it can correct the call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidArguments {
+    pub tool: String,
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl InvalidArguments {
+    /// The tool result content describing what to fix.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": self.to_string(),
+            "kind": "invalid_arguments",
+            "violations": self.violations,
+        })
+    }
+}
+
+impl fmt::Display for InvalidArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid arguments for {}: ", self.tool)?;
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            if violation.path.is_empty() {
+                write!(f, "{}", violation.message)?;
+            } else {
+                write!(f, "{} {}", violation.path, violation.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidArguments {}
+
+/// Checks `value` against the subset of JSON Schema tool definitions use:
+/// `type`, `required`, `properties`, `additionalProperties: false`, `enum`,
+/// `minimum`/`maximum` (and the exclusive forms), `minLength`/`maxLength`,
+/// `items` and `minItems`/`maxItems`. Other keywords are ignored.
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    check(schema, value, "", &mut violations);
+    violations
+}
+
+fn check(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    let mut violation = |message: String| {
+        violations.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+            violation(format!(
+                "must be {} but got {}",
+                types.join(" or "),
+                type_name(value)
+            ));
+            // Further keywords would only repeat the mismatch
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            violation(format!("must be one of {}", allowed.join(", ")));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+        if let Some(minimum) = bound("minimum").filter(|minimum| number < *minimum) {
+            violation(format!("must be at least {}", minimum));
+        }
+        if let Some(maximum) = bound("maximum").filter(|maximum| number > *maximum) {
+            violation(format!("must be at most {}", maximum));
+        }
+        if let Some(minimum) = bound("exclusiveMinimum").filter(|minimum| number <= *minimum) {
+            violation(format!("must be greater than {}", minimum));
+        }
+        if let Some(maximum) = bound("exclusiveMaximum").filter(|maximum| number >= *maximum) {
+            violation(format!("must be less than {}", maximum));
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        let length = text.chars().count() as u64;
+        let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_u64);
+        if let Some(min_length) = bound("minLength").filter(|min| length < *min) {
+            violation(format!("must be at least {} characters long", min_length));
+        }
+        if let Some(max_length) = bound("maxLength").filter(|max| length > *max) {
+            violation(format!("must be at most {} characters long", max_length));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        let length = items.len() as u64;
+        let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_u64);
+        if let Some(min_items) = bound("minItems").filter(|min| length < *min) {
+            violation(format!("must have at least {} items", min_items));
+        }
+        if let Some(max_items) = bound("maxItems").filter(|max| length > *max) {
+            violation(format!("must have at most {} items", max_items));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, &format!("{}/{}", path, i), violations);
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        check_object(schema, object, path, violations);
+    }
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+
+    for name in schema
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+    {
+        if object.get(name).is_none_or(Value::is_null) {
+            violations.push(SchemaViolation {
+                path: format!("{}/{}", path, name),
+                message: "is required".to_string(),
+            });
+        }
+    }
+
+    for (name, property) in object {
+        let property_path = format!("{}/{}", path, name);
+        match properties.and_then(|properties| properties.get(name)) {
+            // Optional fields may be sent as null
+            Some(_) if property.is_null() => {}
+            Some(property_schema) => check(property_schema, property, &property_path, violations),
+            None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                violations.push(SchemaViolation {
+                    path: property_path,
+                    message: "is not a known argument".to_string(),
+                });
+            }
+            None => {}
+        }
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        // Unknown types can't be checked
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -0,0 +1,735 @@
+use super::{InvalidArguments, SchemaViolation, ToolContext, ToolExecutor, ToolRegistry};
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Lines `read_file` returns when no range is given, and the most it returns
+/// for any range.
+pub const MAX_READ_LINES: usize = 400;
+/// Files larger than this are neither read nor searched.
+pub const MAX_FILE_BYTES: u64 = 1_000_000;
+pub const DEFAULT_GREP_MATCHES: usize = 50;
+pub const MAX_GREP_MATCHES: usize = 200;
+/// Directories `grep` doesn't descend into.
+const SKIPPED_DIRS: [&str; 3] = [".git", "target", "node_modules"];
+
+/// Why a workspace operation was refused or failed.
+#[derive(Debug)]
+pub enum WorkspaceError {
+    /// The path leads outside the workspace root, via `..`, an absolute path or
+    /// a symlink.
+    OutsideWorkspace {
+        path: String,
+    },
+    /// The path is inside a `.git` directory, whose config and hooks could
+    /// run commands on the next git operation.
+    GitDirectory {
+        path: String,
+    },
+    NotFound {
+        path: String,
+    },
+    NotAFile {
+        path: String,
+    },
+    NotADirectory {
+        path: String,
+    },
+    TooLarge {
+        path: String,
+        bytes: u64,
+    },
+    Binary {
+        path: String,
+    },
+    InvalidPattern(String),
+    /// The text to replace isn't in the file, or is there more than once.
+    EditMismatch {
+        path: String,
+        occurrences: usize,
+    },
+    Io(io::Error),
+}
+
+impl WorkspaceError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WorkspaceError::OutsideWorkspace { .. } => "outside_workspace",
+            WorkspaceError::GitDirectory { .. } => "git_directory",
+            WorkspaceError::NotFound { .. } => "not_found",
+            WorkspaceError::NotAFile { .. } => "not_a_file",
+            WorkspaceError::NotADirectory { .. } => "not_a_directory",
+            WorkspaceError::TooLarge { .. } => "too_large",
+            WorkspaceError::Binary { .. } => "binary",
+            WorkspaceError::InvalidPattern(_) => "invalid_pattern",
+            WorkspaceError::EditMismatch { .. } => "edit_mismatch",
+            WorkspaceError::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for WorkspaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkspaceError::OutsideWorkspace { path } => {
+                write!(f, "{} is outside the workspace", path)
+            }
+            WorkspaceError::GitDirectory { path } => {
+                write!(f, "{} is inside a .git directory", path)
+            }
+            WorkspaceError::NotFound { path } => write!(f, "{} does not exist", path),
+            WorkspaceError::NotAFile { path } => write!(f, "{} is not a file", path),
+            WorkspaceError::NotADirectory { path } => write!(f, "{} is not a directory", path),
+            WorkspaceError::TooLarge { path, bytes } => write!(
+                f,
+                "{} is {} bytes, over the {} byte limit",
+                path, bytes, MAX_FILE_BYTES
+            ),
+            WorkspaceError::Binary { path } => write!(f, "{} is a binary file", path),
+            WorkspaceError::InvalidPattern(message) => write!(f, "Invalid pattern: {}", message),
+            WorkspaceError::EditMismatch {
+                path,
+                occurrences: 0,
+            } => write!(f, "The text to replace was not found in {}", path),
+            WorkspaceError::EditMismatch { path, occurrences } => write!(
+                f,
+                "The text to replace appears {} times in {}; include more context so it is unique",
+                occurrences, path
+            ),
+            WorkspaceError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WorkspaceError {}
+
+impl From<io::Error> for WorkspaceError {
+    fn from(e: io::Error) -> Self {
+        WorkspaceError::Io(e)
+    }
+}
+
+/// Lines read from a file, numbered from 1.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileLines {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub total_lines: usize,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DirEntry {
+    pub name: String,
+    /// `file`, `dir` or `symlink`.
+    #[serde(rename = "type")]
+    pub entry_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GrepMatch {
+    pub path: String,
+    pub line: usize,
+    pub text: String,
+}
+
+/// A directory the model may read and edit, such as a cloned repository.
+/// Every path is relative to the root and may not lead outside it.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, WorkspaceError> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(WorkspaceError::NotADirectory {
+                path: root.display().to_string(),
+            });
+        }
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves `path` to a location inside the root, following symlinks.
+    /// The location itself need not exist yet, but its nearest existing
+    /// ancestor must resolve inside the root, and outside any `.git`
+    /// directory.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, WorkspaceError> {
+        let outside = || WorkspaceError::OutsideWorkspace {
+            path: path.to_string(),
+        };
+
+        let mut relative = PathBuf::new();
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(part) => relative.push(part),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !relative.pop() {
+                        return Err(outside());
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => return Err(outside()),
+            }
+        }
+
+        // Symlinks may point anywhere, so resolve the part that exists
+        let mut existing = self.root.join(&relative);
+        let mut missing = Vec::new();
+        while fs::symlink_metadata(&existing).is_err() {
+            let Some(name) = existing.file_name() else {
+                return Err(outside());
+            };
+            missing.push(name.to_os_string());
+            existing.pop();
+        }
+        let mut resolved = match existing.canonicalize() {
+            Ok(resolved) => resolved,
+            // A symlink to nowhere could be written through to anywhere
+            Err(_) => return Err(outside()),
+        };
+        if !resolved.starts_with(&self.root) {
+            return Err(outside());
+        }
+        resolved.extend(missing.iter().rev());
+        // Checked after following symlinks, so a link to .git is caught too
+        let in_git_dir = resolved
+            .strip_prefix(&self.root)
+            .is_ok_and(|relative| relative.components().any(|part| part.as_os_str() == ".git"));
+        if in_git_dir {
+            return Err(WorkspaceError::GitDirectory {
+                path: path.to_string(),
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// `path` relative to the root, for showing to the model.
+    fn display(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => path.display().to_string(),
+        }
+    }
+
+    fn read_text(&self, path: &str) -> Result<(PathBuf, String), WorkspaceError> {
+        let resolved = self.resolve(path)?;
+        let metadata = fs::metadata(&resolved).map_err(|_| WorkspaceError::NotFound {
+            path: path.to_string(),
+        })?;
+        if !metadata.is_file() {
+            return Err(WorkspaceError::NotAFile {
+                path: path.to_string(),
+            });
+        }
+        if metadata.len() > MAX_FILE_BYTES {
+            return Err(WorkspaceError::TooLarge {
+                path: path.to_string(),
+                bytes: metadata.len(),
+            });
+        }
+        let bytes = fs::read(&resolved)?;
+        match String::from_utf8(bytes) {
+            Ok(text) if !text.contains('\0') => Ok((resolved, text)),
+            _ => Err(WorkspaceError::Binary {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    /// Reads lines `start_line..=end_line`, at most [`MAX_READ_LINES`] of them.
+    pub fn read_lines(
+        &self,
+        path: &str,
+        start_line: Option<usize>,
+        end_line: Option<usize>,
+    ) -> Result<FileLines, WorkspaceError> {
+        let (resolved, text) = self.read_text(path)?;
+        let lines: Vec<&str> = text.lines().collect();
+        let start_line = start_line.unwrap_or(1).max(1);
+        let end_line = end_line
+            .unwrap_or(usize::MAX)
+            .min(start_line.saturating_add(MAX_READ_LINES - 1))
+            .min(lines.len());
+        let content = if start_line <= end_line {
+            lines[start_line - 1..end_line].join("\n")
+        } else {
+            String::new()
+        };
+        Ok(FileLines {
+            path: self.display(&resolved),
+            start_line,
+            end_line,
+            total_lines: lines.len(),
+            content,
+        })
+    }
+
+    /// Entries of a directory sorted by name, without `.git`.
+    pub fn list_dir(&self, path: &str) -> Result<Vec<DirEntry>, WorkspaceError> {
+        let resolved = self.resolve(path)?;
+        if !resolved.is_dir() {
+            return Err(if resolved.exists() {
+                WorkspaceError::NotADirectory {
+                    path: path.to_string(),
+                }
+            } else {
+                WorkspaceError::NotFound {
+                    path: path.to_string(),
+                }
+            });
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&resolved)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == ".git" {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            let (entry_type, size) = if file_type.is_symlink() {
+                ("symlink", None)
+            } else if file_type.is_dir() {
+                ("dir", None)
+            } else {
+                ("file", Some(entry.metadata()?.len()))
+            };
+            entries.push(DirEntry {
+                name,
+                entry_type,
+                size,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    /// Searches text files under `path` for lines matching `pattern`. Returns
+    /// at most `max_matches` matches and whether more were left out. Symlinks
+    /// are not followed.
+    pub fn grep(
+        &self,
+        pattern: &str,
+        path: &str,
+        case_insensitive: bool,
+        max_matches: usize,
+    ) -> Result<(Vec<GrepMatch>, bool), WorkspaceError> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .size_limit(1 << 20)
+            .build()
+            .map_err(|e| WorkspaceError::InvalidPattern(e.to_string()))?;
+        let resolved = self.resolve(path)?;
+        if !resolved.exists() {
+            return Err(WorkspaceError::NotFound {
+                path: path.to_string(),
+            });
+        }
+
+        let mut matches = Vec::new();
+        let mut pending = vec![resolved];
+        while let Some(current) = pending.pop() {
+            let metadata = fs::symlink_metadata(&current)?;
+            if metadata.is_dir() {
+                let mut children: Vec<PathBuf> = fs::read_dir(&current)?
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| {
+                        !SKIPPED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref())
+                    })
+                    .map(|entry| entry.path())
+                    .collect();
+                // Popped in name order
+                children.sort_by(|a, b| b.cmp(a));
+                pending.extend(children);
+                continue;
+            }
+            if !metadata.is_file() || metadata.len() > MAX_FILE_BYTES {
+                continue;
+            }
+            let Ok(text) = fs::read_to_string(&current) else {
+                continue;
+            };
+            for (i, line) in text.lines().enumerate() {
+                if regex.is_match(line) {
+                    if matches.len() == max_matches {
+                        return Ok((matches, true));
+                    }
+                    matches.push(GrepMatch {
+                        path: self.display(&current),
+                        line: i + 1,
+                        text: line.to_string(),
+                    });
+                }
+            }
+        }
+        Ok((matches, false))
+    }
+
+    /// Creates or overwrites a file, creating missing directories.
+    pub fn write_file(&self, path: &str, content: &str) -> Result<String, WorkspaceError> {
+        let resolved = self.resolve(path)?;
+        if resolved.is_dir() {
+            return Err(WorkspaceError::NotAFile {
+                path: path.to_string(),
+            });
+        }
+        if let Some(parent) = resolved.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&resolved, content)?;
+        Ok(self.display(&resolved))
+    }
+
+    /// Replaces the one occurrence of `old_text` in a file with `new_text`.
+    pub fn edit_file(
+        &self,
+        path: &str,
+        old_text: &str,
+        new_text: &str,
+    ) -> Result<String, WorkspaceError> {
+        let (resolved, text) = self.read_text(path)?;
+        let occurrences = if old_text.is_empty() {
+            0
+        } else {
+            text.matches(old_text).count()
+        };
+        if occurrences != 1 {
+            return Err(WorkspaceError::EditMismatch {
+                path: path.to_string(),
+                occurrences,
+            });
+        }
+        fs::write(&resolved, text.replacen(old_text, new_text, 1))?;
+        Ok(self.display(&resolved))
+    }
+}
+
+/// Runs blocking file system work off the async runtime.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, WorkspaceError> + Send + 'static,
+) -> Result<T> {
+    Ok(tokio::task::spawn_blocking(work).await??)
+}
+
+/// Reads a file in the workspace, optionally a range of lines.
+pub struct ReadFile {
+    workspace: Arc<Workspace>,
+}
+
+impl ReadFile {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadFileArguments {
+    path: String,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+}
+
+#[async_trait]
+impl ToolExecutor for ReadFile {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "Read a text file in the repository. Returns up to 400 lines; use start_line and end_line to page through longer files."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path relative to the repository root"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "description": "First line to read, counting from 1",
+                    "minimum": 1
+                },
+                "end_line": {
+                    "type": "integer",
+                    "description": "Last line to read, inclusive",
+                    "minimum": 1
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["path"].as_str() {
+            Some(path) => format!("Reading {}", path),
+            None => "Reading a file".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: ReadFileArguments = serde_json::from_value(arguments)?;
+        let workspace = self.workspace.clone();
+        let lines = blocking(move || {
+            workspace.read_lines(&arguments.path, arguments.start_line, arguments.end_line)
+        })
+        .await?;
+        Ok(serde_json::to_string(&lines)?)
+    }
+}
+
+/// Lists a directory in the workspace.
+pub struct ListDir {
+    workspace: Arc<Workspace>,
+}
+
+impl ListDir {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for ListDir {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+
+    fn description(&self) -> &str {
+        "List the files and directories in a repository directory"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Directory relative to the repository root; defaults to the root"
+                }
+            }
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        format!("Listing {}", arguments["path"].as_str().unwrap_or("."))
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let path = arguments["path"].as_str().unwrap_or(".").to_string();
+        let workspace = self.workspace.clone();
+        let entries = blocking(move || workspace.list_dir(&path)).await?;
+        Ok(json!({ "entries": entries }).to_string())
+    }
+}
+
+/// Regex search over the workspace's text files.
+pub struct Grep {
+    workspace: Arc<Workspace>,
+}
+
+impl Grep {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GrepArguments {
+    pattern: String,
+    path: Option<String>,
+    #[serde(default)]
+    case_insensitive: bool,
+    max_matches: Option<usize>,
+}
+
+#[async_trait]
+impl ToolExecutor for Grep {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn description(&self) -> &str {
+        "Search the repository's text files for lines matching a regular expression (Rust regex syntax)"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression to search for",
+                    "minLength": 1
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File or directory to search, relative to the repository root; defaults to the root"
+                },
+                "case_insensitive": {
+                    "type": "boolean"
+                },
+                "max_matches": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_GREP_MATCHES
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["pattern"].as_str() {
+            Some(pattern) => format!("Searching for {}", pattern),
+            None => "Searching".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: GrepArguments = serde_json::from_value(arguments)?;
+        let workspace = self.workspace.clone();
+        let (matches, truncated) = blocking(move || {
+            workspace.grep(
+                &arguments.pattern,
+                arguments.path.as_deref().unwrap_or("."),
+                arguments.case_insensitive,
+                arguments.max_matches.unwrap_or(DEFAULT_GREP_MATCHES),
+            )
+        })
+        .await?;
+        Ok(json!({ "matches": matches, "truncated": truncated }).to_string())
+    }
+}
+
+/// Writes or edits a file in the workspace. Changes stay inside the
+/// workspace, so calls don't need approval.
+pub struct WriteFile {
+    workspace: Arc<Workspace>,
+}
+
+impl WriteFile {
+    pub fn new(workspace: Arc<Workspace>) -> Self {
+        Self { workspace }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WriteFileArguments {
+    path: String,
+    content: Option<String>,
+    old_text: Option<String>,
+    new_text: Option<String>,
+}
+
+#[async_trait]
+impl ToolExecutor for WriteFile {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn description(&self) -> &str {
+        "Change a file in the repository. Either give content to create or overwrite the whole file, or give old_text and new_text to replace one exact, unique piece of the file."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path relative to the repository root"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The complete new file content"
+                },
+                "old_text": {
+                    "type": "string",
+                    "description": "Text to replace; must appear exactly once in the file",
+                    "minLength": 1
+                },
+                "new_text": {
+                    "type": "string",
+                    "description": "Replacement for old_text"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn status(&self, arguments: &Value) -> String {
+        match arguments["path"].as_str() {
+            Some(path) => format!("Editing {}", path),
+            None => "Editing a file".to_string(),
+        }
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let arguments: WriteFileArguments = serde_json::from_value(arguments)?;
+        let workspace = self.workspace.clone();
+        let (path, action) = match arguments {
+            WriteFileArguments {
+                path,
+                content: Some(content),
+                old_text: None,
+                new_text: None,
+            } => (
+                blocking(move || workspace.write_file(&path, &content)).await?,
+                "written",
+            ),
+            WriteFileArguments {
+                path,
+                content: None,
+                old_text: Some(old_text),
+                new_text: Some(new_text),
+            } => (
+                blocking(move || workspace.edit_file(&path, &old_text, &new_text)).await?,
+                "edited",
+            ),
+            _ => {
+                return Err(InvalidArguments {
+                    tool: self.name().to_string(),
+                    violations: vec![SchemaViolation {
+                        path: String::new(),
+                        message: "give either content, or old_text and new_text".to_string(),
+                    }],
+                }
+                .into())
+            }
+        };
+        Ok(json!({ "path": path, "result": action }).to_string())
+    }
+}
+
+/// Tools for exploring `workspace`, plus `write_file` when `writable`.
+pub fn create_workspace_tools(workspace: Arc<Workspace>, writable: bool) -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    registry
+        .register(ReadFile::new(workspace.clone()))
+        .register(ListDir::new(workspace.clone()))
+        .register(Grep::new(workspace.clone()));
+    if writable {
+        registry.register(WriteFile::new(workspace));
+    }
+    registry
+}
@@ -1,22 +1,176 @@
 use super::MessageHandler;
-use crate::server::services::github_issue::GitHubService;
+use crate::server::models::usage::CreateUsageRequest;
+use crate::server::services::agent::AgentEvent;
+use crate::server::services::deepseek::{self, GenerationOptions, Usage};
+use crate::server::tools::{Approval, ApprovalRequest, ToolApprover, ToolContext};
 use crate::server::ws::{transport::WebSocketState, types::ChatMessage};
 use async_trait::async_trait;
 use serde_json::json;
+use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Number of past messages sent to the model as context. Kept even so the
+/// window always starts on a user turn.
+const MAX_HISTORY_MESSAGES: usize = 40;
+
+/// How long a side-effecting call waits for the user to approve it.
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the handler remembers about one connection's chat.
+struct Conversation {
+    /// Identifies the conversation in the token usage ledger.
+    id: Uuid,
+    messages: Vec<deepseek::ChatMessage>,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+/// A call waiting for the user, by approval request id.
+struct PendingApproval {
+    conn_id: String,
+    reply: oneshot::Sender<Approval>,
+}
+
+type PendingApprovals = Arc<Mutex<HashMap<String, PendingApproval>>>;
 
 pub struct ChatHandler {
     ws_state: Arc<WebSocketState>,
-    github_service: Arc<GitHubService>,
+    conversations: Mutex<HashMap<String, Conversation>>,
+    /// Cancels the generation in flight on each connection.
+    generations: Mutex<HashMap<String, CancellationToken>>,
+    approvals: PendingApprovals,
+    approval_timeout: Duration,
 }
 
 impl ChatHandler {
-    pub fn new(ws_state: Arc<WebSocketState>, github_service: Arc<GitHubService>) -> Self {
+    pub fn new(ws_state: Arc<WebSocketState>) -> Self {
         Self {
             ws_state,
-            github_service,
+            conversations: Mutex::new(HashMap::new()),
+            generations: Mutex::new(HashMap::new()),
+            approvals: Arc::new(Mutex::new(HashMap::new())),
+            approval_timeout: APPROVAL_TIMEOUT,
+        }
+    }
+
+    pub fn with_approval_timeout(mut self, approval_timeout: Duration) -> Self {
+        self.approval_timeout = approval_timeout;
+        self
+    }
+
+    /// Answers the `approval_request` with `id`. Returns false when no such
+    /// request is waiting on `conn_id`, e.g. because it timed out.
+    pub async fn resolve_approval(&self, conn_id: &str, id: &str, approval: Approval) -> bool {
+        let mut approvals = self.approvals.lock().await;
+        match approvals.get(id) {
+            Some(pending) if pending.conn_id == conn_id => {}
+            _ => {
+                warn!("No approval request {} on {}", id, conn_id);
+                return false;
+            }
+        }
+        let pending = approvals.remove(id).expect("checked above");
+        info!("Approval request {} answered: {:?}", id, approval);
+        pending.reply.send(approval).is_ok()
+    }
+
+    /// Stops the generation in flight on `conn_id`, if any. The partial answer is
+    /// sent to the client with status `cancelled`.
+    pub async fn stop(&self, conn_id: &str) {
+        if let Some(cancel) = self.generations.lock().await.get(conn_id) {
+            info!("Stopping generation for {}", conn_id);
+            cancel.cancel();
+        }
+    }
+
+    async fn send_cancelled(
+        &self,
+        conn_id: &str,
+        partial_content: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let cancelled_json = json!({
+            "type": "chat",
+            "content": partial_content,
+            "sender": "ai",
+            "status": "cancelled"
+        });
+        self.ws_state
+            .send_to(conn_id, &cancelled_json.to_string())
+            .await
+    }
+
+    async fn history(&self, conn_id: &str) -> Vec<deepseek::ChatMessage> {
+        self.conversations
+            .lock()
+            .await
+            .get(conn_id)
+            .map(|conversation| conversation.messages.clone())
+            .unwrap_or_default()
+    }
+
+    /// Records a completed exchange so later messages on this connection see it.
+    async fn remember(&self, conn_id: &str, user_content: String, assistant_content: String) {
+        let mut conversations = self.conversations.lock().await;
+        let history = &mut conversations
+            .entry(conn_id.to_string())
+            .or_default()
+            .messages;
+        history.push(text_message("user", user_content));
+        history.push(text_message("assistant", assistant_content));
+
+        if history.len() > MAX_HISTORY_MESSAGES {
+            let excess = history.len() - MAX_HISTORY_MESSAGES;
+            history.drain(..excess);
+        }
+    }
+
+    async fn conversation_id(&self, conn_id: &str) -> Uuid {
+        self.conversations
+            .lock()
+            .await
+            .entry(conn_id.to_string())
+            .or_default()
+            .id
+    }
+
+    /// Adds `usage` to the ledger when the server has a database. Failures are
+    /// logged rather than failing the chat.
+    async fn record_usage(&self, conn_id: &str, usage: &Usage) {
+        let Some(chat_database) = self.ws_state.chat_database() else {
+            return;
+        };
+        if usage.total_tokens == 0 && usage.prompt_tokens == 0 {
+            return;
+        }
+
+        let conversation_id = self.conversation_id(conn_id).await;
+        let user_id = self
+            .ws_state
+            .get_user_id(conn_id)
+            .await
+            .map(|id| id.to_string());
+
+        let request = CreateUsageRequest {
+            user_id,
+            conversation_id,
+            source: "chat".to_string(),
+            usage: usage.clone(),
+        };
+        if let Err(e) = chat_database.record_usage(request).await {
+            warn!("Failed to record token usage: {}", e);
         }
     }
 
@@ -24,6 +178,7 @@ impl ChatHandler {
         &self,
         content: String,
         conn_id: &str,
+        cancel: &CancellationToken,
     ) -> Result<(), Box<dyn Error + Send + Sync>> {
         info!("Processing message: {}", content);
 
@@ -39,11 +194,14 @@ impl ChatHandler {
             .await?;
 
         // Get routing decision
-        let (decision, tool_calls) = self
-            .ws_state
-            .model_router
-            .route_message(content.clone())
-            .await?;
+        let Some(routing) = cancel
+            .run_until_cancelled(self.ws_state.model_router.route_message(content.clone()))
+            .await
+        else {
+            return self.send_cancelled(conn_id, "").await;
+        };
+        let (decision, tool_calls, routing_usage) = routing?;
+        self.record_usage(conn_id, &routing_usage).await;
 
         // Send routing status
         let routing_json = json!({
@@ -59,82 +217,68 @@ impl ChatHandler {
         if decision.needs_tool {
             // Handle tool execution
             if let Some(tool_calls) = tool_calls {
-                for tool_call in tool_calls {
-                    if tool_call.function.name == "read_github_issue" {
-                        // Parse tool call arguments
-                        let args: serde_json::Value =
-                            serde_json::from_str(&tool_call.function.arguments)?;
-                        let owner = args["owner"].as_str().unwrap_or("OpenAgentsInc");
-                        let repo = args["repo"].as_str().unwrap_or("openagents");
-                        let issue_number = args["issue_number"].as_i64().unwrap_or(0) as i32;
-
-                        // Send tool call status
-                        let tool_call_json = json!({
-                            "type": "chat",
-                            "content": format!("Fetching GitHub issue #{} from {}/{}", issue_number, owner, repo),
-                            "sender": "ai",
-                            "status": "tool_calls"
-                        });
-                        self.ws_state
-                            .send_to(conn_id, &tool_call_json.to_string())
-                            .await?;
+                let approver = WsApprover {
+                    ws_state: self.ws_state.clone(),
+                    approvals: self.approvals.clone(),
+                    conn_id: conn_id.to_string(),
+                    timeout: self.approval_timeout,
+                };
+                let context = ToolContext {
+                    conversation_id: Some(self.conversation_id(conn_id).await),
+                    cancel: cancel.clone(),
+                    approver: Some(Arc::new(approver)),
+                };
+                let mut messages = self.history(conn_id).await;
+                messages.push(text_message("user", content.clone()));
 
-                        // Fetch the issue
-                        let issue = self
-                            .github_service
-                            .get_issue(owner, repo, issue_number)
-                            .await?;
+                // Keep going until the model has what it needs, reporting each
+                // call as it starts
+                let (events_tx, mut events) = mpsc::unbounded_channel();
+                let agent = self
+                    .ws_state
+                    .model_router
+                    .agent(self.ws_state.tools().clone())
+                    .with_events(events_tx);
+                let run = async move { agent.run_tool_calls(messages, tool_calls, &context).await };
+                let forward = async {
+                    while let Some(event) = events.recv().await {
+                        if let AgentEvent::ToolCall { status, .. } = event {
+                            let tool_call_json = json!({
+                                "type": "chat",
+                                "content": status,
+                                "sender": "ai",
+                                "status": "tool_calls"
+                            });
+                            self.ws_state
+                                .send_to(conn_id, &tool_call_json.to_string())
+                                .await?;
+                        }
+                    }
+                    Ok::<_, Box<dyn Error + Send + Sync>>(())
+                };
 
-                        // Create messages for tool response
-                        let user_message = crate::server::services::deepseek::ChatMessage {
-                            role: "user".to_string(),
-                            content: content.clone(),
-                            tool_call_id: None,
-                            tool_calls: None,
-                        };
-
-                        let assistant_message =
-                            crate::server::services::deepseek::AssistantMessage {
-                                role: "assistant".to_string(),
-                                content: format!(
-                                    "Let me fetch GitHub issue #{} for you.",
-                                    issue_number
-                                ),
-                                tool_call_id: None,
-                                tool_calls: Some(vec![tool_call.clone()]),
-                            };
-
-                        let issue_message = crate::server::services::deepseek::ChatMessage {
-                            role: "tool".to_string(),
-                            content: serde_json::to_string(&issue)?,
-                            tool_call_id: Some(tool_call.id),
-                            tool_calls: None,
-                        };
-
-                        // Get final response with tool results
-                        let messages = vec![
-                            user_message,
-                            crate::server::services::deepseek::ChatMessage::from(assistant_message),
-                        ];
-
-                        let (final_content, _, _) = self
-                            .ws_state
-                            .model_router
-                            .handle_tool_response(messages, issue_message)
-                            .await?;
+                let Some((run, forwarded)) = cancel
+                    .run_until_cancelled(async { tokio::join!(run, forward) })
+                    .await
+                else {
+                    return self.send_cancelled(conn_id, "").await;
+                };
+                forwarded?;
+                let run = run?;
+                self.record_usage(conn_id, &run.usage).await;
 
-                        // Send final response
-                        let final_json = json!({
-                            "type": "chat",
-                            "content": final_content,
-                            "sender": "ai",
-                            "status": "complete"
-                        });
-                        self.ws_state
-                            .send_to(conn_id, &final_json.to_string())
-                            .await?;
-                    }
-                }
+                // Send final response
+                let final_json = json!({
+                    "type": "chat",
+                    "content": &run.content,
+                    "sender": "ai",
+                    "status": "complete"
+                });
+                self.ws_state
+                    .send_to(conn_id, &final_json.to_string())
+                    .await?;
+
+                self.remember(conn_id, content.clone(), run.content).await;
             } else {
                 // If no tool calls but tool was needed, send error
                 let error_json = json!({
@@ -148,11 +292,31 @@ impl ChatHandler {
                     .await?;
             }
         } else {
-            // Use regular chat stream for non-tool messages
-            let mut stream = self.ws_state.model_router.chat_stream(content).await;
+            // Use regular chat stream for non-tool messages, with the conversation so far
+            let mut messages = self.history(conn_id).await;
+            messages.push(text_message("user", content.clone()));
+
+            let mut stream = self
+                .ws_state
+                .model_router
+                .chat_stream(messages, GenerationOptions::reasoner())
+                .await;
             let mut full_response = String::new();
 
-            while let Some(update) = stream.recv().await {
+            loop {
+                let Some(update) = cancel.run_until_cancelled(stream.recv()).await else {
+                    // Dropping the stream aborts the request
+                    drop(stream);
+                    self.send_cancelled(conn_id, &full_response).await?;
+                    if !full_response.is_empty() {
+                        self.remember(conn_id, content, full_response).await;
+                    }
+                    break;
+                };
+                let Some(update) = update else {
+                    break;
+                };
+
                 match update {
                     crate::server::services::deepseek::StreamUpdate::Content(content) => {
                         full_response.push_str(&content);
@@ -177,16 +341,24 @@ impl ChatHandler {
                             .send_to(conn_id, &reasoning_json.to_string())
                             .await?;
                     }
+                    crate::server::services::deepseek::StreamUpdate::Usage(usage) => {
+                        self.record_usage(conn_id, &usage).await;
+                    }
+                    crate::server::services::deepseek::StreamUpdate::Error(e) => {
+                        return Err(Box::new(e));
+                    }
                     crate::server::services::deepseek::StreamUpdate::Done => {
                         let response_json = json!({
                             "type": "chat",
-                            "content": full_response,
+                            "content": &full_response,
                             "sender": "ai",
                             "status": "complete"
                         });
                         self.ws_state
                             .send_to(conn_id, &response_json.to_string())
                             .await?;
+
+                        self.remember(conn_id, content, full_response).await;
                         break;
                     }
                     _ => {}
@@ -210,7 +382,21 @@ impl MessageHandler for ChatHandler {
         info!("Handling chat message: {:?}", msg);
         match msg {
             ChatMessage::UserMessage { content } => {
-                match self.process_message(content, &conn_id).await {
+                let cancel = CancellationToken::new();
+                self.generations
+                    .lock()
+                    .await
+                    .insert(conn_id.clone(), cancel.clone());
+
+                let result = self.process_message(content, &conn_id, &cancel).await;
+                self.generations.lock().await.remove(&conn_id);
+                // Requests left over from a cancelled generation can't be answered
+                self.approvals
+                    .lock()
+                    .await
+                    .retain(|_, pending| pending.conn_id != conn_id);
+
+                match result {
                     Ok(_) => Ok(()),
                     Err(e) => {
                         error!("Error processing message: {}", e);
@@ -239,3 +425,72 @@ impl MessageHandler for ChatHandler {
         Ok(())
     }
 }
+
+/// Asks the client on one connection to approve side-effecting calls with an
+/// `approval_request` message, answered by `approve` or `deny`.
+struct WsApprover {
+    ws_state: Arc<WebSocketState>,
+    approvals: PendingApprovals,
+    conn_id: String,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl ToolApprover for WsApprover {
+    async fn approve(&self, request: &ApprovalRequest) -> Approval {
+        let id = Uuid::new_v4().to_string();
+        let (reply, answer) = oneshot::channel();
+        self.approvals.lock().await.insert(
+            id.clone(),
+            PendingApproval {
+                conn_id: self.conn_id.clone(),
+                reply,
+            },
+        );
+
+        let request_json = json!({
+            "type": "approval_request",
+            "id": &id,
+            "tool": &request.tool,
+            "arguments": &request.arguments,
+            "description": &request.description,
+            "risk": request.risk,
+            "timeout_secs": self.timeout.as_secs()
+        });
+        if let Err(e) = self
+            .ws_state
+            .send_to(&self.conn_id, &request_json.to_string())
+            .await
+        {
+            warn!("Failed to send approval request: {}", e);
+        }
+
+        let approval = match tokio::time::timeout(self.timeout, answer).await {
+            Ok(Ok(approval)) => approval,
+            // Dropped without an answer
+            Ok(Err(_)) => Approval::Denied { reason: None },
+            Err(_) => {
+                let timeout_json = json!({ "type": "approval_timeout", "id": &id });
+                if let Err(e) = self
+                    .ws_state
+                    .send_to(&self.conn_id, &timeout_json.to_string())
+                    .await
+                {
+                    warn!("Failed to send approval timeout: {}", e);
+                }
+                Approval::TimedOut
+            }
+        };
+        self.approvals.lock().await.remove(&id);
+        approval
+    }
+}
+
+fn text_message(role: &str, content: String) -> deepseek::ChatMessage {
+    deepseek::ChatMessage {
+        role: role.to_string(),
+        content,
+        tool_call_id: None,
+        tool_calls: None,
+    }
+}
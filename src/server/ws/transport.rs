@@ -10,38 +10,65 @@ use uuid::Uuid;
 
 use super::handlers::{chat::ChatHandler, MessageHandler};
 use super::types::{ChatMessage, ConnectionState, WebSocketError};
-use crate::server::services::{
-    deepseek::{DeepSeekService, Tool},
-    github_issue::GitHubService,
-    model_router::ModelRouter,
-};
+use crate::server::services::{model_router::ModelRouter, provider::ChatProvider, ChatDatabase};
+use crate::server::tools::{Approval, ToolRegistry};
 
 pub struct WebSocketState {
     connections: Arc<RwLock<HashMap<String, ConnectionState>>>,
     pub model_router: Arc<ModelRouter>,
-    github_service: Arc<GitHubService>,
+    tools: Arc<ToolRegistry>,
+    chat_database: Option<Arc<ChatDatabase>>,
 }
 
 impl WebSocketState {
     pub fn new(
-        tool_model: Arc<DeepSeekService>,
-        chat_model: Arc<DeepSeekService>,
-        github_service: Arc<GitHubService>,
-        tools: Vec<Tool>,
+        tool_model: Arc<dyn ChatProvider>,
+        chat_model: Arc<dyn ChatProvider>,
+        tools: ToolRegistry,
     ) -> Arc<Self> {
-        let model_router = Arc::new(ModelRouter::new(tool_model, chat_model, tools));
+        Self::build(tool_model, chat_model, tools, None)
+    }
+
+    /// Like [`new`](Self::new), but records token usage in `chat_database`.
+    pub fn with_chat_database(
+        tool_model: Arc<dyn ChatProvider>,
+        chat_model: Arc<dyn ChatProvider>,
+        tools: ToolRegistry,
+        chat_database: Arc<ChatDatabase>,
+    ) -> Arc<Self> {
+        Self::build(tool_model, chat_model, tools, Some(chat_database))
+    }
+
+    fn build(
+        tool_model: Arc<dyn ChatProvider>,
+        chat_model: Arc<dyn ChatProvider>,
+        tools: ToolRegistry,
+        chat_database: Option<Arc<ChatDatabase>>,
+    ) -> Arc<Self> {
+        let model_router = Arc::new(ModelRouter::new(
+            tool_model,
+            chat_model,
+            tools.definitions(),
+        ));
         Arc::new(Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
             model_router,
-            github_service,
+            tools: Arc::new(tools),
+            chat_database,
         })
     }
 
+    pub fn chat_database(&self) -> Option<&Arc<ChatDatabase>> {
+        self.chat_database.as_ref()
+    }
+
+    /// The tools the chat can call.
+    pub fn tools(&self) -> &Arc<ToolRegistry> {
+        &self.tools
+    }
+
     pub fn create_handlers(ws_state: Arc<WebSocketState>) -> Arc<ChatHandler> {
-        Arc::new(ChatHandler::new(
-            ws_state.clone(),
-            ws_state.github_service.clone(),
-        ))
+        Arc::new(ChatHandler::new(ws_state))
     }
 
     pub async fn validate_session(jar: &CookieJar) -> Result<i32, WebSocketError> {
@@ -98,6 +125,22 @@ impl WebSocketState {
             info!("Connection removed: {}", send_conn_id);
         });
 
+        // Chat messages are handled in order on their own task, so a stop message
+        // can interrupt the generation in flight
+        let (chat_tx, mut chat_rx) = mpsc::unbounded_channel::<ChatMessage>();
+        let worker_handler = chat_handler.clone();
+        let worker_conn_id = conn_id.clone();
+        let chat_task = tokio::spawn(async move {
+            while let Some(chat_msg) = chat_rx.recv().await {
+                if let Err(e) = worker_handler
+                    .handle_message(chat_msg, worker_conn_id.clone())
+                    .await
+                {
+                    error!("Error handling chat message: {}", e);
+                }
+            }
+        });
+
         // Handle incoming messages
         let receive_conn_id = conn_id.clone();
         let receive_task = tokio::spawn(async move {
@@ -118,12 +161,7 @@ impl WebSocketState {
                                     content: content_str.to_string(),
                                 };
                                 info!("Created chat message: {:?}", chat_msg);
-                                if let Err(e) = chat_handler
-                                    .handle_message(chat_msg, receive_conn_id.clone())
-                                    .await
-                                {
-                                    error!("Error handling chat message: {}", e);
-                                }
+                                let _ = chat_tx.send(chat_msg);
                             }
                         } else if let Some(message_type) = data.get("type") {
                             match message_type.as_str() {
@@ -134,15 +172,32 @@ impl WebSocketState {
                                             serde_json::from_value(message.clone())
                                         {
                                             info!("Parsed chat message: {:?}", chat_msg);
-                                            if let Err(e) = chat_handler
-                                                .handle_message(chat_msg, receive_conn_id.clone())
-                                                .await
-                                            {
-                                                error!("Error handling chat message: {}", e);
-                                            }
+                                            let _ = chat_tx.send(chat_msg);
                                         }
                                     }
                                 }
+                                Some("stop") => {
+                                    chat_handler.stop(&receive_conn_id).await;
+                                }
+                                Some(answer @ ("approve" | "deny")) => {
+                                    let Some(id) = data.get("id").and_then(|id| id.as_str()) else {
+                                        error!("Approval answer without an id");
+                                        continue;
+                                    };
+                                    let approval = if answer == "approve" {
+                                        Approval::Approved
+                                    } else {
+                                        Approval::Denied {
+                                            reason: data
+                                                .get("reason")
+                                                .and_then(|reason| reason.as_str())
+                                                .map(str::to_string),
+                                        }
+                                    };
+                                    chat_handler
+                                        .resolve_approval(&receive_conn_id, id, approval)
+                                        .await;
+                                }
                                 _ => {
                                     error!("Unknown message type");
                                 }
@@ -163,6 +218,9 @@ impl WebSocketState {
                 info!("Receive task completed for {}", final_conn_id);
             },
         }
+
+        // Drop any generation still in flight so a closed socket stops consuming tokens
+        chat_task.abort();
     }
 
     pub async fn broadcast(&self, msg: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
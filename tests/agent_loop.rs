@@ -0,0 +1,341 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use openagents::server::services::{
+    agent::{AgentEvent, AgentLoop, AgentStop, TimeBudgetExceeded},
+    deepseek::{ChatMessage, DeepSeekService},
+    github_issue::GitHubService,
+};
+use openagents::server::tools::{ReadGitHubIssue, ToolContext, ToolExecutor, ToolRegistry};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Barrier};
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+/// Only returns once the barrier is full, so it hangs unless calls run
+/// concurrently.
+struct Rendezvous {
+    barrier: Barrier,
+}
+
+#[async_trait]
+impl ToolExecutor for Rendezvous {
+    fn name(&self) -> &str {
+        "rendezvous"
+    }
+
+    fn description(&self) -> &str {
+        "Wait for the other calls"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({"type": "object", "properties": {"id": {"type": "integer"}}})
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        self.barrier.wait().await;
+        Ok(format!("arrived {}", arguments["id"]))
+    }
+}
+
+struct Sleep;
+
+#[async_trait]
+impl ToolExecutor for Sleep {
+    fn name(&self) -> &str {
+        "sleep"
+    }
+
+    fn description(&self) -> &str {
+        "Take a while"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({"type": "object", "properties": {}})
+    }
+
+    async fn execute(&self, _arguments: Value, _context: &ToolContext) -> Result<String> {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok("done".to_string())
+    }
+}
+
+fn user(content: &str) -> Vec<ChatMessage> {
+    vec![ChatMessage {
+        role: "user".to_string(),
+        content: content.to_string(),
+        tool_call_id: None,
+        tool_calls: None,
+    }]
+}
+
+fn tool_call_reply(calls: &[(&str, &str, Value)]) -> ResponseTemplate {
+    let tool_calls: Vec<Value> = calls
+        .iter()
+        .map(|(id, name, arguments)| {
+            json!({
+                "id": id,
+                "type": "function",
+                "function": {"name": name, "arguments": arguments.to_string()}
+            })
+        })
+        .collect();
+    ResponseTemplate::new(200).set_body_json(json!({
+        "choices": [{"message": {"content": "", "role": "assistant", "tool_calls": tool_calls}}],
+        "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+    }))
+}
+
+fn answer_reply(content: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "choices": [{"message": {"content": content, "role": "assistant"}}],
+        "usage": {"prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25}
+    }))
+}
+
+fn model(server: &MockServer) -> Arc<DeepSeekService> {
+    Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        server.uri(),
+    ))
+}
+
+async fn requests(server: &MockServer) -> Vec<Value> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_compares_issues_with_parallel_calls() {
+    let github = MockServer::start().await;
+    for (number, title) in [(12, "Add a tool registry"), (15, "Add an agent loop")] {
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/OpenAgentsInc/openagents/issues/{}",
+                number
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "number": number,
+                "title": title,
+                "body": null,
+                "state": "open",
+                "html_url": format!("https://github.com/OpenAgentsInc/openagents/issues/{}", number)
+            })))
+            .expect(1)
+            .mount(&github)
+            .await;
+    }
+
+    let deepseek = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(tool_call_reply(&[
+            (
+                "call_12",
+                "read_github_issue",
+                json!({"owner": "OpenAgentsInc", "repo": "openagents", "issue_number": 12}),
+            ),
+            (
+                "call_15",
+                "read_github_issue",
+                json!({"owner": "OpenAgentsInc", "repo": "openagents", "issue_number": 15}),
+            ),
+        ]))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&deepseek)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(answer_reply("#12 adds the registry, #15 the loop"))
+        .with_priority(2)
+        .mount(&deepseek)
+        .await;
+
+    let github_service = Arc::new(GitHubService::with_base_url(
+        "test_token".to_string(),
+        github.uri(),
+    ));
+    let mut tools = ToolRegistry::new();
+    tools.register(ReadGitHubIssue::new(github_service));
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let agent = AgentLoop::new(model(&deepseek), Arc::new(tools)).with_events(events_tx);
+
+    let run = agent
+        .run(user("Compare issues 12 and 15"), &ToolContext::default())
+        .await
+        .unwrap();
+    drop(agent);
+
+    assert_eq!(run.content, "#12 adds the registry, #15 the loop");
+    assert_eq!(run.stop, AgentStop::Finished);
+    assert_eq!(run.steps, 2);
+    assert_eq!(run.usage.total_tokens, 40);
+
+    // One assistant turn with both calls, then the results in call order
+    assert_eq!(run.messages.len(), 4);
+    assert_eq!(run.messages[1].tool_calls.as_ref().unwrap().len(), 2);
+    assert_eq!(run.messages[2].tool_call_id.as_deref(), Some("call_12"));
+    assert_eq!(run.messages[3].tool_call_id.as_deref(), Some("call_15"));
+    let issue: Value = serde_json::from_str(&run.messages[3].content).unwrap();
+    assert_eq!(issue["title"], "Add an agent loop");
+
+    let mut statuses = Vec::new();
+    while let Some(event) = events.recv().await {
+        if let AgentEvent::ToolCall { status, .. } = event {
+            statuses.push(status);
+        }
+    }
+    assert_eq!(
+        statuses,
+        vec![
+            "Fetching GitHub issue #12 from OpenAgentsInc/openagents",
+            "Fetching GitHub issue #15 from OpenAgentsInc/openagents"
+        ]
+    );
+
+    // The answer was asked for with both results
+    let requests = requests(&deepseek).await;
+    assert_eq!(requests.len(), 2);
+    let last_messages = requests[1]["messages"].as_array().unwrap();
+    assert_eq!(last_messages.len(), 4);
+    assert_eq!(last_messages[3]["role"], "tool");
+}
+
+#[tokio::test]
+async fn test_runs_calls_from_one_turn_concurrently() {
+    let deepseek = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(tool_call_reply(&[
+            ("call_1", "rendezvous", json!({"id": 1})),
+            ("call_2", "rendezvous", json!({"id": 2})),
+            ("call_3", "missing_tool", json!({})),
+        ]))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&deepseek)
+        .await;
+    // A second round of calls before the answer
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(tool_call_reply(&[
+            ("call_4", "rendezvous", json!({"id": 4})),
+            ("call_5", "rendezvous", json!({"id": 5})),
+        ]))
+        .up_to_n_times(1)
+        .with_priority(2)
+        .mount(&deepseek)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(answer_reply("Everyone arrived"))
+        .with_priority(3)
+        .mount(&deepseek)
+        .await;
+
+    let mut tools = ToolRegistry::new();
+    tools.register(Rendezvous {
+        barrier: Barrier::new(2),
+    });
+    let agent = AgentLoop::new(model(&deepseek), Arc::new(tools));
+
+    let run = tokio::time::timeout(
+        Duration::from_secs(5),
+        agent.run(user("Meet up twice"), &ToolContext::default()),
+    )
+    .await
+    .expect("calls should run concurrently")
+    .unwrap();
+
+    assert_eq!(run.content, "Everyone arrived");
+    assert_eq!(run.steps, 3);
+    let results: Vec<&str> = run
+        .messages
+        .iter()
+        .filter(|message| message.role == "tool")
+        .map(|message| message.content.as_str())
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            "arrived 1",
+            "arrived 2",
+            r#"{"error":"Unknown tool: missing_tool","kind":"unknown_tool"}"#,
+            "arrived 4",
+            "arrived 5"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_stops_at_max_steps() {
+    let deepseek = MockServer::start().await;
+    // Asked to answer without tools
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({"tool_choice": "none"})))
+        .respond_with(answer_reply("Here is what I found so far"))
+        .with_priority(1)
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(tool_call_reply(&[(
+            "call_1",
+            "rendezvous",
+            json!({"id": 1}),
+        )]))
+        .with_priority(2)
+        .expect(2)
+        .mount(&deepseek)
+        .await;
+
+    let mut tools = ToolRegistry::new();
+    tools.register(Rendezvous {
+        barrier: Barrier::new(1),
+    });
+    let agent = AgentLoop::new(model(&deepseek), Arc::new(tools)).with_max_steps(2);
+
+    let run = agent
+        .run(user("Keep going forever"), &ToolContext::default())
+        .await
+        .unwrap();
+    assert_eq!(run.content, "Here is what I found so far");
+    assert_eq!(run.stop, AgentStop::MaxSteps);
+    assert_eq!(run.steps, 3);
+    assert_eq!(run.usage.total_tokens, 55);
+}
+
+#[tokio::test]
+async fn test_enforces_time_budget() {
+    let deepseek = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(tool_call_reply(&[("call_1", "sleep", json!({}))]))
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+
+    let mut tools = ToolRegistry::new();
+    tools.register(Sleep);
+    let agent = AgentLoop::new(model(&deepseek), Arc::new(tools))
+        .with_time_budget(Duration::from_millis(200));
+
+    let error = agent
+        .run(user("Take your time"), &ToolContext::default())
+        .await
+        .unwrap_err();
+    let exceeded = error.downcast::<TimeBudgetExceeded>().unwrap();
+    assert_eq!(exceeded.steps, 1);
+    assert_eq!(exceeded.budget, Duration::from_millis(200));
+}
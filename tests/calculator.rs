@@ -0,0 +1,214 @@
+use openagents::server::tools::calculate::{evaluate, format_number, CalcError};
+use openagents::server::tools::{error_content, Calculate, ToolContext, ToolExecutor};
+use serde_json::{json, Value};
+
+fn calc(expression: &str) -> String {
+    match evaluate(expression) {
+        Ok(value) => format_number(&value),
+        Err(e) => panic!("{} failed: {}", expression, e),
+    }
+}
+
+#[test]
+fn test_arithmetic_and_precedence() {
+    let cases = [
+        ("1 + 2 * 3", "7"),
+        ("(1 + 2) * 3", "9"),
+        ("10 - 4 - 3", "3"),
+        ("2 ^ 3 ^ 2", "512"),
+        ("2 ** 10", "1024"),
+        ("-2 ^ 2", "-4"),
+        ("(-2) ^ 2", "4"),
+        ("2 ^ -2", "0.25"),
+        ("7 % 3", "1"),
+        ("-7.5 % 2", "-1.5"),
+        ("5!", "120"),
+        ("3! ^ 2", "36"),
+        ("0.1 + 0.2", "0.3"),
+        ("1 / 3", "0.3333333333333333333333333333333333333333"),
+        ("2 / 3", "0.6666666666666666666666666666666666666667"),
+        ("1.5e3 + 2E-1", "1500.2"),
+        ("6 × 7 − 2 ÷ 4", "41.5"),
+        ("+-+3", "-3"),
+    ];
+    for (expression, expected) in cases {
+        assert_eq!(calc(expression), expected, "{}", expression);
+    }
+}
+
+#[test]
+fn test_big_decimal_precision() {
+    assert_eq!(calc("2^64 - 1"), "18446744073709551615");
+    assert_eq!(
+        calc("2^200"),
+        "1606938044258990275541962092341162602522202993782792835301376"
+    );
+    assert_eq!(calc("0.1^20 + 1"), "1.00000000000000000001");
+    assert_eq!(
+        calc("12345678901234567890 * 98765432109876543210"),
+        "1219326311370217952237463801111263526900"
+    );
+    assert_eq!(calc("25!"), "15511210043330985984000000");
+    assert_eq!(
+        calc("10^50"),
+        "100000000000000000000000000000000000000000000000000"
+    );
+    assert_eq!(
+        calc("1 / 7^30"),
+        "4.436687086236305609334872212376898607041e-26"
+    );
+}
+
+#[test]
+fn test_functions_and_constants() {
+    let cases = [
+        ("pi", "3.141592653589793238462643383279502884197"),
+        ("e", "2.718281828459045235360287471352662497757"),
+        ("tau / 2 - pi", "0"),
+        ("sqrt(2)", "1.41421356237309504880168872420969807857"),
+        ("sqrt(144)", "12"),
+        ("cbrt(-27)", "-3"),
+        ("abs(-4.5)", "4.5"),
+        ("floor(-2.5)", "-3"),
+        ("ceil(2.1)", "3"),
+        ("trunc(-2.7)", "-2"),
+        ("round(2.5)", "3"),
+        ("round(3.14159, 2)", "3.14"),
+        ("ln(e)", "1"),
+        ("ln(10)", "2.302585092994045684017991454684364207601"),
+        ("exp(1) - e", "0"),
+        ("exp(-2)", "0.1353352832366126918939994949724844034076"),
+        ("log(1000)", "3"),
+        ("log10(0.001)", "-3"),
+        ("log2(1024)", "10"),
+        ("log(81, 3)", "4"),
+        ("sin(0)", "0"),
+        ("sin(pi)", "0"),
+        ("sin(pi / 6)", "0.5"),
+        ("cos(pi)", "-1"),
+        ("cos(pi / 3)", "0.5"),
+        ("tan(pi / 4)", "1"),
+        ("sin(100)", "-0.506365641109758793656557610459785432065"),
+        ("asin(1) * 2 - pi", "0"),
+        ("acos(0.5) * 3 - pi", "0"),
+        ("atan(1) * 4 - pi", "0"),
+        ("atan(-1e6)", "-1.570795326794896619564655024972884775432"),
+        ("2 ^ 0.5 - sqrt(2)", "0"),
+        ("pow(8, 1/3)", "2"),
+        ("min(3, -1, 2)", "-1"),
+        ("max(3, -1, 2)", "3"),
+    ];
+    for (expression, expected) in cases {
+        assert_eq!(calc(expression), expected, "{}", expression);
+    }
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(evaluate("1 / 0"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate("5 % 0"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate("0 ^ -1"), Err(CalcError::DivisionByZero));
+    assert_eq!(evaluate("10 ^ 10 ^ 10"), Err(CalcError::Overflow));
+    assert_eq!(evaluate("exp(1e6)"), Err(CalcError::Overflow));
+    assert_eq!(evaluate("100000!"), Err(CalcError::Overflow));
+
+    let error = evaluate("2 +* 3").unwrap_err();
+    assert_eq!(error.kind(), "syntax");
+    assert_eq!(error.position(), Some(3));
+
+    let error = evaluate("(1 + 2").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected ')' but the expression ended at position 6"
+    );
+
+    let error = evaluate("foo(2)").unwrap_err();
+    assert_eq!(error.kind(), "unknown_identifier");
+    assert_eq!(error.position(), Some(0));
+
+    let error = evaluate("2 $ 3").unwrap_err();
+    assert_eq!(error.to_string(), "Unexpected character '$' at position 2");
+
+    let error = evaluate("sqrt(1, 2)").unwrap_err();
+    assert_eq!(error.to_string(), "sqrt takes 1 argument(s) but got 2");
+
+    for (expression, kind) in [
+        ("sqrt(-1)", "domain"),
+        ("ln(0)", "domain"),
+        ("asin(2)", "domain"),
+        ("(-8) ^ 0.5", "domain"),
+        ("2.5!", "domain"),
+        ("tan(pi / 2)", "domain"),
+        ("1 2", "syntax"),
+        ("", "syntax"),
+        ("system(\"rm -rf /\")", "syntax"),
+    ] {
+        assert_eq!(
+            evaluate(expression).unwrap_err().kind(),
+            kind,
+            "{}",
+            expression
+        );
+    }
+
+    let nested = format!("{}1{}", "(".repeat(500), ")".repeat(500));
+    assert_eq!(evaluate(&nested).unwrap_err().kind(), "syntax");
+}
+
+#[test]
+fn test_magnitude_limits() {
+    assert_eq!(calc("10^100000"), "1e100000");
+    assert_eq!(calc("-10^100000"), "-1e100000");
+    assert_eq!(evaluate("10^100001"), Err(CalcError::Overflow));
+    assert_eq!(evaluate("10^100000 * 10"), Err(CalcError::Overflow));
+
+    let result = calc("exp(230258)");
+    let (mantissa, exponent) = result.split_once('e').unwrap();
+    assert_eq!(exponent, "99999");
+    let digits = mantissa.chars().filter(char::is_ascii_digit).count();
+    assert!(digits <= 40, "{}", result);
+}
+
+#[tokio::test]
+async fn test_calculate_tool_messages() {
+    let context = ToolContext::default();
+
+    let result: Value = serde_json::from_str(
+        &Calculate
+            .execute(json!({"expression": "sqrt(16) + 2^10"}), &context)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        json!({"expression": "sqrt(16) + 2^10", "result": "1028"})
+    );
+
+    // Failures are errors, which the registry reports with their kind
+    let error = Calculate
+        .execute(json!({"expression": "1 / (2 - 2)"}), &context)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<CalcError>(),
+        Some(&CalcError::DivisionByZero)
+    );
+    let content: Value = serde_json::from_str(&error_content(&error)).unwrap();
+    assert_eq!(
+        content,
+        json!({"error": "Division by zero", "kind": "division_by_zero"})
+    );
+    let error = Calculate
+        .execute(json!({"expression": "2 +* 3"}), &context)
+        .await
+        .unwrap_err();
+    let content: Value = serde_json::from_str(&error_content(&error)).unwrap();
+    assert_eq!(content["kind"], "syntax");
+    assert_eq!(content["position"], 3);
+
+    assert_eq!(
+        Calculate.status(&json!({"expression": "2 + 2"})),
+        "Calculating 2 + 2"
+    );
+}
@@ -0,0 +1,243 @@
+use openagents::server::services::{
+    cassette::{Cassette, CassetteMode, DEEPSEEK_UPSTREAM, GITHUB_UPSTREAM},
+    deepseek::{ChatCompletion, ChatMessage, DeepSeekService, StreamUpdate},
+    github_issue::GitHubService,
+    model_router::ModelRouter,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+async fn collect(mut rx: mpsc::Receiver<StreamUpdate>) -> String {
+    let mut content = String::new();
+    while let Some(update) = rx.recv().await {
+        match update {
+            StreamUpdate::Content(c) => content.push_str(&c),
+            StreamUpdate::Done => break,
+            _ => {}
+        }
+    }
+    content
+}
+
+/// Runs a chat, a stream and an issue read through `cassette`.
+async fn exercise(cassette: &Cassette) -> (String, String, String) {
+    let deepseek = DeepSeekService::with_base_url(
+        "secret_key".to_string(),
+        cassette.url_for(DEEPSEEK_UPSTREAM),
+    );
+    let github = GitHubService::with_base_url(
+        "secret_token".to_string(),
+        cassette.url_for(GITHUB_UPSTREAM),
+    );
+
+    let ChatCompletion { content: chat, .. } =
+        deepseek.chat("Hello".to_string(), false).await.unwrap();
+    let streamed = collect(
+        deepseek
+            .chat_stream("Stream please".to_string(), false)
+            .await,
+    )
+    .await;
+    let issue = github.get_issue("owner", "repo", 7).await.unwrap();
+
+    (chat, streamed, issue.title)
+}
+
+#[tokio::test]
+async fn test_record_then_replay() {
+    let upstream = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"content": "Hi there!", "role": "assistant"}}]
+        })))
+        .expect(1)
+        .mount(&upstream)
+        .await;
+
+    let sse = format!(
+        "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+        json!({"choices": [{"delta": {"content": "Streamed "}, "finish_reason": null}]}),
+        json!({"choices": [{"delta": {"content": "answer"}, "finish_reason": "stop"}]})
+    );
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(sse, "text/event-stream"))
+        .expect(1)
+        .mount(&upstream)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/7"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "number": 7,
+            "title": "Recorded issue",
+            "body": null,
+            "state": "open",
+            "html_url": "https://github.com/owner/repo/issues/7"
+        })))
+        .expect(1)
+        .mount(&upstream)
+        .await;
+
+    let fixture = std::env::temp_dir().join(format!("cassette-{}.json", Uuid::new_v4()));
+    let expected = (
+        "Hi there!".to_string(),
+        "Streamed answer".to_string(),
+        "Recorded issue".to_string(),
+    );
+
+    // Record against the upstream
+    let recorder = Cassette::start(
+        &fixture,
+        CassetteMode::Record,
+        &[
+            (DEEPSEEK_UPSTREAM, &upstream.uri()),
+            (GITHUB_UPSTREAM, &upstream.uri()),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(exercise(&recorder).await, expected);
+    assert_eq!(recorder.interactions().len(), 3);
+    drop(recorder);
+
+    let saved = std::fs::read_to_string(&fixture).unwrap();
+    assert!(
+        !saved.contains("secret_key"),
+        "Credentials must not be recorded"
+    );
+    assert!(
+        !saved.contains("secret_token"),
+        "Credentials must not be recorded"
+    );
+
+    // Replay with the upstream gone
+    drop(upstream);
+    let player = Cassette::start(
+        &fixture,
+        CassetteMode::Replay,
+        &[
+            (DEEPSEEK_UPSTREAM, "http://127.0.0.1:9"),
+            (GITHUB_UPSTREAM, "http://127.0.0.1:9"),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(exercise(&player).await, expected);
+
+    // Requests that were never recorded fail instead of reaching the network
+    let github =
+        GitHubService::with_base_url("secret_token".to_string(), player.url_for(GITHUB_UPSTREAM));
+    assert!(github.get_issue("owner", "repo", 8).await.is_err());
+
+    std::fs::remove_file(&fixture).ok();
+}
+
+/// Replays a routed GitHub tool call end to end. To refresh the fixture from the
+/// real APIs, run with `OPENAGENTS_CASSETTE_MODE=record`, `DEEPSEEK_API_KEY` and
+/// `GITHUB_TOKEN` set.
+#[tokio::test]
+async fn test_replay_chat_tool_flow() {
+    let mode = match std::env::var("OPENAGENTS_CASSETTE_MODE") {
+        Ok(mode) => mode.parse().unwrap(),
+        Err(_) => CassetteMode::Replay,
+    };
+    run_chat_tool_flow(
+        mode,
+        "https://api.deepseek.com/v1",
+        "https://api.github.com",
+    )
+    .await;
+}
+
+async fn run_chat_tool_flow(mode: CassetteMode, deepseek_url: &str, github_url: &str) {
+    let fixture = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/cassettes/chat_tool_flow.json"
+    );
+    let cassette = Cassette::start(
+        fixture,
+        mode,
+        &[
+            (DEEPSEEK_UPSTREAM, deepseek_url),
+            (GITHUB_UPSTREAM, github_url),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let api_key = std::env::var("DEEPSEEK_API_KEY").unwrap_or_else(|_| "replay".to_string());
+    let token = std::env::var("GITHUB_TOKEN").unwrap_or_else(|_| "replay".to_string());
+    let model = Arc::new(DeepSeekService::with_base_url(
+        api_key,
+        cassette.url_for(DEEPSEEK_UPSTREAM),
+    ));
+    let github = GitHubService::with_base_url(token, cassette.url_for(GITHUB_UPSTREAM));
+    let tools = vec![DeepSeekService::create_tool(
+        "read_github_issue".to_string(),
+        Some("Read a GitHub issue by number".to_string()),
+        json!({
+            "type": "object",
+            "properties": {
+                "owner": {"type": "string", "description": "The owner of the repository"},
+                "repo": {"type": "string", "description": "The name of the repository"},
+                "issue_number": {"type": "integer", "description": "The issue number"}
+            },
+            "required": ["owner", "repo", "issue_number"]
+        }),
+    )];
+    let router = ModelRouter::new(model.clone(), model, tools);
+
+    let message = "What is issue #1 in OpenAgentsInc/openagents about?".to_string();
+    let (decision, tool_calls, _) = router.route_message(message.clone()).await.unwrap();
+    assert!(decision.needs_tool);
+    let tool_call = tool_calls.expect("Should call a tool").remove(0);
+    assert_eq!(tool_call.function.name, "read_github_issue");
+
+    let args: serde_json::Value = serde_json::from_str(&tool_call.function.arguments).unwrap();
+    let issue = github
+        .get_issue(
+            args["owner"].as_str().unwrap(),
+            args["repo"].as_str().unwrap(),
+            args["issue_number"].as_i64().unwrap() as i32,
+        )
+        .await
+        .unwrap();
+    assert_eq!(issue.number, 1);
+
+    let messages = vec![
+        ChatMessage {
+            role: "user".to_string(),
+            content: message,
+            tool_call_id: None,
+            tool_calls: None,
+        },
+        ChatMessage {
+            role: "assistant".to_string(),
+            content: String::new(),
+            tool_call_id: None,
+            tool_calls: Some(vec![tool_call.clone()]),
+        },
+    ];
+    let tool_message = ChatMessage {
+        role: "tool".to_string(),
+        content: serde_json::to_string(&issue).unwrap(),
+        tool_call_id: Some(tool_call.id),
+        tool_calls: None,
+    };
+    let answer = router
+        .handle_tool_response(messages, tool_message)
+        .await
+        .unwrap();
+    assert!(answer.content.contains(&issue.title));
+}
@@ -1,12 +1,19 @@
 use dotenvy::dotenv;
 use openagents::server::{
-    models::chat::{CreateConversationRequest, CreateMessageRequest},
-    services::ChatDatabase,
+    models::{
+        chat::{CreateConversationRequest, CreateMessageRequest},
+        usage::{CreateUsageRequest, UsageTotals},
+    },
+    services::{
+        deepseek::{CompletionTokensDetails, Usage},
+        ChatDatabase,
+    },
 };
 use serde_json::json;
 use sqlx::PgPool;
 use tracing::{info, Level};
 use tracing_subscriber::fmt::format::FmtSpan;
+use uuid::Uuid;
 
 #[tokio::test]
 async fn test_chat_persistence() {
@@ -157,3 +164,73 @@ async fn test_chat_persistence() {
 
     info!("All tests passed!");
 }
+
+#[tokio::test]
+async fn test_token_usage_ledger() {
+    dotenv().ok();
+
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPool::connect(&database_url)
+        .await
+        .expect("Failed to connect to database");
+
+    sqlx::query!("DELETE FROM token_usage WHERE user_id = 'test_usage_user'")
+        .execute(&pool)
+        .await
+        .expect("Failed to clean up existing test data");
+
+    let chat_db = ChatDatabase::new(pool);
+    let conversation_id = Uuid::new_v4();
+
+    let record =
+        |prompt_tokens, completion_tokens, reasoning_tokens, cache_hit| CreateUsageRequest {
+            user_id: Some("test_usage_user".to_string()),
+            conversation_id,
+            source: "chat".to_string(),
+            usage: Usage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+                prompt_cache_hit_tokens: cache_hit,
+                prompt_cache_miss_tokens: prompt_tokens - cache_hit,
+                completion_tokens_details: Some(CompletionTokensDetails { reasoning_tokens }),
+            },
+        };
+
+    chat_db
+        .record_usage(record(100, 40, 10, 60))
+        .await
+        .expect("Failed to record usage");
+    chat_db
+        .record_usage(record(50, 20, 0, 50))
+        .await
+        .expect("Failed to record usage");
+
+    let expected = UsageTotals {
+        requests: 2,
+        prompt_tokens: 150,
+        completion_tokens: 60,
+        reasoning_tokens: 10,
+        prompt_cache_hit_tokens: 110,
+        prompt_cache_miss_tokens: 40,
+    };
+
+    let conversation_totals = chat_db
+        .get_conversation_usage(conversation_id)
+        .await
+        .expect("Failed to sum conversation usage");
+    assert_eq!(conversation_totals, expected);
+
+    let user_totals = chat_db
+        .get_user_usage("test_usage_user")
+        .await
+        .expect("Failed to sum user usage");
+    assert_eq!(user_totals, expected);
+
+    // Unknown conversations sum to zero rather than erroring
+    let empty = chat_db
+        .get_conversation_usage(Uuid::new_v4())
+        .await
+        .expect("Failed to sum usage for unknown conversation");
+    assert_eq!(empty, UsageTotals::default());
+}
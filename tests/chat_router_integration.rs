@@ -1,17 +1,15 @@
 use axum::extract::ws::Message;
 use dotenvy::dotenv;
-use openagents::server::services::{
-    deepseek::{DeepSeekService, Tool},
-    github_issue::GitHubService,
-};
+use openagents::server::services::{deepseek::DeepSeekService, github_issue::GitHubService};
+use openagents::server::tools::create_tools;
 use openagents::server::ws::handlers::MessageHandler;
 use openagents::server::ws::{handlers::chat::ChatHandler, transport::WebSocketState};
 use serde_json::json;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::Level;
-use tracing_subscriber;
 use wiremock::{
-    matchers::{header, method, path},
+    matchers::{body_partial_json, header, method, path},
     Mock, MockServer, ResponseTemplate,
 };
 
@@ -22,50 +20,6 @@ fn init_logging() {
         .try_init();
 }
 
-// Helper function to create test tools
-fn create_test_tools() -> Vec<Tool> {
-    vec![
-        // GitHub issue tool
-        DeepSeekService::create_tool(
-            "read_github_issue".to_string(),
-            Some("Read a GitHub issue by number".to_string()),
-            json!({
-                "type": "object",
-                "properties": {
-                    "owner": {
-                        "type": "string",
-                        "description": "The owner of the repository"
-                    },
-                    "repo": {
-                        "type": "string",
-                        "description": "The name of the repository"
-                    },
-                    "issue_number": {
-                        "type": "integer",
-                        "description": "The issue number"
-                    }
-                },
-                "required": ["owner", "repo", "issue_number"]
-            }),
-        ),
-        // Calculator tool
-        DeepSeekService::create_tool(
-            "calculate".to_string(),
-            Some("Perform a calculation".to_string()),
-            json!({
-                "type": "object",
-                "properties": {
-                    "expression": {
-                        "type": "string",
-                        "description": "The mathematical expression to evaluate"
-                    }
-                },
-                "required": ["expression"]
-            }),
-        ),
-    ]
-}
-
 #[tokio::test]
 async fn test_chat_router_integration() {
     // Initialize logging
@@ -139,16 +93,16 @@ async fn test_chat_router_integration() {
     );
 
     // Create tools
-    let tools = create_test_tools();
+    let tools = create_tools(github_service);
 
     // Create WebSocket state
-    let ws_state = WebSocketState::new(tool_model, chat_model, github_service.clone(), tools);
+    let ws_state = WebSocketState::new(tool_model, chat_model, tools);
 
     // Add test connection with test user_id
     let mut rx = ws_state.add_test_connection("test_conn", 1).await;
 
     // Create chat handler
-    let chat_handler = ChatHandler::new(ws_state.clone(), github_service.clone());
+    let chat_handler = ChatHandler::new(ws_state.clone());
 
     // Test message that should trigger GitHub tool
     let test_message = openagents::server::ws::types::ChatMessage::UserMessage {
@@ -235,16 +189,16 @@ async fn test_chat_router_streaming() {
     );
 
     // Create tools
-    let tools = create_test_tools();
+    let tools = create_tools(github_service);
 
     // Create WebSocket state
-    let ws_state = WebSocketState::new(tool_model, chat_model, github_service.clone(), tools);
+    let ws_state = WebSocketState::new(tool_model, chat_model, tools);
 
     // Add test connection with test user_id
     let mut rx = ws_state.add_test_connection("test_conn", 1).await;
 
     // Create chat handler
-    let chat_handler = ChatHandler::new(ws_state.clone(), github_service.clone());
+    let chat_handler = ChatHandler::new(ws_state.clone());
 
     // Test message that should use streaming
     let test_message = openagents::server::ws::types::ChatMessage::UserMessage {
@@ -266,3 +220,170 @@ async fn test_chat_router_streaming() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_chat_handler_streams_with_conversation_history() {
+    init_logging();
+
+    let mock_server = MockServer::start().await;
+
+    // Routing decisions are regular completions
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": json!({
+                        "needs_tool": false,
+                        "reasoning": "General chat message that doesn't require tools",
+                        "suggested_tool": null
+                    }).to_string(),
+                    "role": "assistant"
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // Answers are streamed
+    let sse = format!(
+        "data: {}\n\ndata: [DONE]\n\n",
+        json!({"choices": [{"delta": {"content": "Nice to meet you, Ada."}, "finish_reason": "stop"}]})
+    );
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(sse, "text/event-stream"))
+        .mount(&mock_server)
+        .await;
+
+    let tool_model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        mock_server.uri(),
+    ));
+    let chat_model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        mock_server.uri(),
+    ));
+    let github_service = Arc::new(
+        GitHubService::new(Some("test_token".to_string()))
+            .expect("Failed to create GitHub service"),
+    );
+
+    let ws_state = WebSocketState::new(tool_model, chat_model, create_tools(github_service));
+    let _rx = ws_state.add_test_connection("test_conn", 1).await;
+    let chat_handler = ChatHandler::new(ws_state.clone());
+
+    for content in ["My name is Ada.", "What is my name?"] {
+        let message = openagents::server::ws::types::ChatMessage::UserMessage {
+            content: content.to_string(),
+        };
+        chat_handler
+            .handle_message(message, "test_conn".to_string())
+            .await
+            .expect("Message handling should succeed");
+    }
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let streamed: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .filter(|body| body["stream"] == json!(true))
+        .collect();
+    assert_eq!(streamed.len(), 2);
+
+    // The second answer sees the first exchange
+    assert_eq!(
+        streamed[1]["messages"],
+        json!([
+            {"role": "user", "content": "My name is Ada."},
+            {"role": "assistant", "content": "Nice to meet you, Ada."},
+            {"role": "user", "content": "What is my name?"}
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_chat_handler_stop_cancels_generation() {
+    init_logging();
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": json!({
+                        "needs_tool": false,
+                        "reasoning": "General chat message that doesn't require tools",
+                        "suggested_tool": null
+                    }).to_string(),
+                    "role": "assistant"
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // A long generation that only starts answering after the test has stopped it
+    let sse = format!(
+        "data: {}\n\ndata: [DONE]\n\n",
+        json!({"choices": [{"delta": {"content": "Too late"}, "finish_reason": "stop"}]})
+    );
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_raw(sse, "text/event-stream")
+                .set_delay(Duration::from_secs(30)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        mock_server.uri(),
+    ));
+    let github_service = Arc::new(
+        GitHubService::new(Some("test_token".to_string()))
+            .expect("Failed to create GitHub service"),
+    );
+
+    let ws_state = WebSocketState::new(model.clone(), model, create_tools(github_service));
+    let mut rx = ws_state.add_test_connection("test_conn", 1).await;
+    let chat_handler = Arc::new(ChatHandler::new(ws_state.clone()));
+
+    let handler = chat_handler.clone();
+    let generation = tokio::spawn(async move {
+        let message = openagents::server::ws::types::ChatMessage::UserMessage {
+            content: "Write me a long essay".to_string(),
+        };
+        handler
+            .handle_message(message, "test_conn".to_string())
+            .await
+            .expect("Message handling should succeed");
+    });
+
+    // Wait until the answer is being generated, then stop it
+    while mock_server.received_requests().await.unwrap().len() < 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    chat_handler.stop("test_conn").await;
+
+    tokio::time::timeout(Duration::from_secs(5), generation)
+        .await
+        .expect("Generation should stop promptly")
+        .unwrap();
+
+    let mut statuses = Vec::new();
+    while let Ok(Message::Text(text)) = rx.try_recv() {
+        let data: serde_json::Value = serde_json::from_str(&text).unwrap();
+        statuses.push(data["status"].as_str().unwrap_or_default().to_string());
+    }
+    assert_eq!(statuses.last().map(String::as_str), Some("cancelled"));
+    assert!(!statuses.iter().any(|status| status == "complete"));
+}
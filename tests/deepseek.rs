@@ -1,7 +1,9 @@
-use openagents::server::services::deepseek::DeepSeekService;
+use openagents::server::services::deepseek::{
+    ChatCompletion, DeepSeekService, GenerationOptions, ResponseFormat,
+};
 use serde_json::json;
 use wiremock::{
-    matchers::{header, method, path},
+    matchers::{body_partial_json, header, method, path},
     Mock, MockServer, ResponseTemplate,
 };
 
@@ -29,7 +31,11 @@ async fn test_chat_basic() {
 
     let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
 
-    let (response, reasoning) = service.chat("Hello".to_string(), false).await.unwrap();
+    let ChatCompletion {
+        content: response,
+        reasoning,
+        ..
+    } = service.chat("Hello".to_string(), false).await.unwrap();
     assert_eq!(response, "Hello! How can I help you?");
     assert_eq!(reasoning, None);
 }
@@ -58,7 +64,11 @@ async fn test_chat_with_reasoning() {
 
     let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
 
-    let (response, reasoning) = service
+    let ChatCompletion {
+        content: response,
+        reasoning,
+        ..
+    } = service
         .chat("Compare 9.11 and 9.8".to_string(), true)
         .await
         .unwrap();
@@ -121,7 +131,12 @@ async fn test_chat_with_tools() {
         }),
     );
 
-    let (response, reasoning, tool_calls) = service
+    let ChatCompletion {
+        content: response,
+        reasoning,
+        tool_calls,
+        ..
+    } = service
         .chat_with_tools(
             "What's in issue #123?".to_string(),
             vec![get_issue_tool],
@@ -138,3 +153,122 @@ async fn test_chat_with_tools() {
     assert_eq!(tool_calls.len(), 1);
     assert_eq!(tool_calls[0].function.name, "get_github_issue");
 }
+
+#[tokio::test]
+async fn test_chat_with_generation_options() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "model": "deepseek-coder",
+            "temperature": 0.0,
+            "top_p": 0.9,
+            "max_tokens": 64,
+            "stop": ["\n\n"],
+            "seed": 42,
+            "response_format": {"type": "json_object"}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": "{\"ok\": true}",
+                    "role": "assistant"
+                }
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+
+    let options = GenerationOptions {
+        model: Some("deepseek-coder".to_string()),
+        top_p: Some(0.9),
+        max_tokens: Some(64),
+        stop: Some(vec!["\n\n".to_string()]),
+        seed: Some(42),
+        response_format: Some(ResponseFormat::JsonObject),
+        ..GenerationOptions::deterministic()
+    };
+
+    let ChatCompletion {
+        content: response, ..
+    } = service
+        .chat("Reply in JSON".to_string(), options)
+        .await
+        .unwrap();
+    assert_eq!(response, "{\"ok\": true}");
+}
+
+#[tokio::test]
+async fn test_chat_omits_unset_options() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": "Hi",
+                    "role": "assistant"
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    service.chat("Hello".to_string(), false).await.unwrap();
+
+    let requests = mock_server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+
+    assert_eq!(body["model"], "deepseek-chat");
+    assert!((body["temperature"].as_f64().unwrap() - 0.7).abs() < 1e-6);
+    for key in ["top_p", "stop", "seed", "response_format"] {
+        assert!(body.get(key).is_none(), "{} should not be sent", key);
+    }
+}
+
+#[tokio::test]
+async fn test_chat_returns_usage() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": "9.8 is greater",
+                    "reasoning_content": "Compare the decimals",
+                    "role": "assistant"
+                }
+            }],
+            "usage": {
+                "prompt_tokens": 120,
+                "completion_tokens": 80,
+                "total_tokens": 200,
+                "prompt_cache_hit_tokens": 100,
+                "prompt_cache_miss_tokens": 20,
+                "completion_tokens_details": {"reasoning_tokens": 64}
+            }
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let completion = service
+        .chat("Compare 9.11 and 9.8".to_string(), true)
+        .await
+        .unwrap();
+
+    let usage = completion.usage.expect("Usage should be parsed");
+    assert_eq!(usage.prompt_tokens, 120);
+    assert_eq!(usage.completion_tokens, 80);
+    assert_eq!(usage.total_tokens, 200);
+    assert_eq!(usage.prompt_cache_hit_tokens, 100);
+    assert_eq!(usage.prompt_cache_miss_tokens, 20);
+    assert_eq!(usage.reasoning_tokens(), 64);
+}
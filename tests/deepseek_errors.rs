@@ -0,0 +1,246 @@
+use openagents::server::services::deepseek::{
+    ChatCompletion, DeepSeekError, DeepSeekService, RetryPolicy, StreamUpdate,
+};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn completion(content: &str) -> serde_json::Value {
+    json!({
+        "choices": [{
+            "message": {
+                "content": content,
+                "role": "assistant"
+            }
+        }]
+    })
+}
+
+fn fast_retries(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_secs(5),
+    }
+}
+
+fn service(mock_server: &MockServer, retry_policy: RetryPolicy) -> DeepSeekService {
+    DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri())
+        .with_retry_policy(retry_policy)
+}
+
+#[tokio::test]
+async fn test_retries_transient_server_errors() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Recovered")))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let ChatCompletion {
+        content: response, ..
+    } = service(&mock_server, fast_retries(3))
+        .chat("Hello".to_string(), false)
+        .await
+        .unwrap();
+    assert_eq!(response, "Recovered");
+}
+
+#[tokio::test]
+async fn test_respects_retry_after() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("Retry-After", "1")
+                .set_body_json(json!({"error": {"message": "Rate limit reached"}})),
+        )
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Done waiting")))
+        .mount(&mock_server)
+        .await;
+
+    let started = Instant::now();
+    let ChatCompletion {
+        content: response, ..
+    } = service(&mock_server, fast_retries(2))
+        .chat("Hello".to_string(), false)
+        .await
+        .unwrap();
+
+    assert_eq!(response, "Done waiting");
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_long_retry_after_is_not_cut_short() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("Retry-After", "60")
+                .set_body_json(json!({"error": {"message": "Rate limit reached"}})),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let err = service(&mock_server, fast_retries(3))
+        .chat("Hello".to_string(), false)
+        .await
+        .unwrap_err();
+    match err {
+        DeepSeekError::RateLimited { retry_after, .. } => {
+            assert_eq!(retry_after, Some(Duration::from_secs(60)))
+        }
+        other => panic!("Expected a rate limit error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+
+    let err = service(&mock_server, fast_retries(3))
+        .chat("Hello".to_string(), false)
+        .await
+        .unwrap_err();
+
+    match err {
+        DeepSeekError::Server {
+            status, message, ..
+        } => {
+            assert_eq!(status, 500);
+            assert_eq!(message, "boom");
+        }
+        other => panic!("Expected a server error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_auth_errors_are_not_retried() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(401)
+                .set_body_json(json!({"error": {"message": "Authentication Fails"}})),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let err = service(&mock_server, fast_retries(3))
+        .chat("Hello".to_string(), false)
+        .await
+        .unwrap_err();
+
+    assert!(!err.is_retryable());
+    assert!(
+        matches!(&err, DeepSeekError::Auth { status: 401, message } if message == "Authentication Fails")
+    );
+}
+
+#[tokio::test]
+async fn test_malformed_body() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>gateway</html>"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let err = service(&mock_server, fast_retries(3))
+        .chat("Hello".to_string(), false)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, DeepSeekError::Malformed(_)));
+}
+
+#[tokio::test]
+async fn test_stream_retries_then_streams() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(502))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+
+    let body = format!(
+        "data: {}\n\ndata: [DONE]\n\n",
+        json!({"choices": [{"delta": {"content": "Streamed"}, "finish_reason": "stop"}]})
+    );
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .mount(&mock_server)
+        .await;
+
+    let mut rx = service(&mock_server, fast_retries(2))
+        .chat_stream("Hello".to_string(), false)
+        .await;
+
+    assert!(matches!(rx.recv().await, Some(StreamUpdate::Content(c)) if c == "Streamed"));
+    assert!(matches!(rx.recv().await, Some(StreamUpdate::Done)));
+}
+
+#[tokio::test]
+async fn test_stream_reports_errors() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(400)
+                .set_body_json(json!({"error": {"message": "Invalid model"}})),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut rx = service(&mock_server, fast_retries(3))
+        .chat_stream("Hello".to_string(), false)
+        .await;
+
+    assert!(matches!(
+        rx.recv().await,
+        Some(StreamUpdate::Error(DeepSeekError::Api { status: 400, .. }))
+    ));
+    assert!(matches!(rx.recv().await, Some(StreamUpdate::Done)));
+}
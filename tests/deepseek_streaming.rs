@@ -0,0 +1,322 @@
+use openagents::server::services::deepseek::{
+    ChatMessage, DeepSeekService, GenerationOptions, StreamUpdate, ToolChoice,
+};
+use serde_json::json;
+use tokio::sync::mpsc;
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn sse_body(chunks: &[serde_json::Value]) -> String {
+    let mut body = String::new();
+    for chunk in chunks {
+        body.push_str(&format!("data: {}\n\n", chunk));
+    }
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+fn delta(delta: serde_json::Value, finish_reason: Option<&str>) -> serde_json::Value {
+    json!({
+        "choices": [{
+            "index": 0,
+            "delta": delta,
+            "finish_reason": finish_reason
+        }]
+    })
+}
+
+async fn collect(mut rx: mpsc::Receiver<StreamUpdate>) -> Vec<StreamUpdate> {
+    let mut updates = Vec::new();
+    while let Some(update) = rx.recv().await {
+        let done = matches!(update, StreamUpdate::Done);
+        updates.push(update);
+        if done {
+            break;
+        }
+    }
+    updates
+}
+
+#[tokio::test]
+async fn test_stream_reassembles_tool_call_fragments() {
+    let mock_server = MockServer::start().await;
+
+    let body = sse_body(&[
+        delta(
+            json!({"role": "assistant", "content": "Let me check."}),
+            None,
+        ),
+        delta(
+            json!({"tool_calls": [{
+                "index": 0,
+                "id": "call_abc",
+                "type": "function",
+                "function": {"name": "read_github_issue", "arguments": ""}
+            }]}),
+            None,
+        ),
+        delta(
+            json!({"tool_calls": [{"index": 0, "function": {"arguments": "{\"owner\":\"OpenAg"}}]}),
+            None,
+        ),
+        delta(
+            json!({"tool_calls": [{"index": 0, "function": {"arguments": "entsInc\",\"repo\":\"openagents\","}}]}),
+            None,
+        ),
+        delta(
+            json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"issue_number\":595}"}}]}),
+            None,
+        ),
+        delta(json!({}), Some("tool_calls")),
+    ]);
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "stream": true,
+            "tools": [{"type": "function", "function": {"name": "read_github_issue"}}]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let tool = DeepSeekService::create_tool(
+        "read_github_issue".to_string(),
+        Some("Read a GitHub issue by number".to_string()),
+        json!({"type": "object", "properties": {}}),
+    );
+
+    let rx = service
+        .chat_stream_with_tools(
+            "Can you check issue #595?".to_string(),
+            vec![tool],
+            Some(ToolChoice::Auto("auto".to_string())),
+            false,
+        )
+        .await;
+    let updates = collect(rx).await;
+
+    let content: String = updates
+        .iter()
+        .filter_map(|u| match u {
+            StreamUpdate::Content(c) => Some(c.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(content, "Let me check.");
+
+    let tool_calls: Vec<_> = updates
+        .iter()
+        .filter_map(|u| match u {
+            StreamUpdate::ToolCalls(calls) => Some(calls.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(tool_calls.len(), 1, "Tool calls should be emitted once");
+    let calls = &tool_calls[0];
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].id, "call_abc");
+    assert_eq!(calls[0].tool_type, "function");
+    assert_eq!(calls[0].function.name, "read_github_issue");
+
+    let args: serde_json::Value = serde_json::from_str(&calls[0].function.arguments).unwrap();
+    assert_eq!(args["owner"], "OpenAgentsInc");
+    assert_eq!(args["repo"], "openagents");
+    assert_eq!(args["issue_number"], 595);
+
+    assert!(matches!(updates.last(), Some(StreamUpdate::Done)));
+    assert_eq!(
+        updates
+            .iter()
+            .filter(|u| matches!(u, StreamUpdate::Done))
+            .count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_stream_interleaved_parallel_tool_calls() {
+    let mock_server = MockServer::start().await;
+
+    let body = sse_body(&[
+        delta(
+            json!({"tool_calls": [
+                {"index": 0, "id": "call_1", "type": "function", "function": {"name": "calculate", "arguments": "{\"expr"}},
+                {"index": 1, "id": "call_2", "type": "function", "function": {"name": "calculate", "arguments": "{\"expr"}}
+            ]}),
+            None,
+        ),
+        delta(
+            json!({"tool_calls": [{"index": 1, "function": {"arguments": "ession\":\"3*3\"}"}}]}),
+            None,
+        ),
+        delta(
+            json!({"tool_calls": [{"index": 0, "function": {"arguments": "ession\":\"2+2\"}"}}]}),
+            Some("tool_calls"),
+        ),
+    ]);
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let tool = DeepSeekService::create_tool(
+        "calculate".to_string(),
+        None,
+        json!({"type": "object", "properties": {}}),
+    );
+
+    let rx = service
+        .chat_stream_with_tools("2+2 and 3*3".to_string(), vec![tool], None, false)
+        .await;
+    let updates = collect(rx).await;
+
+    let calls = updates
+        .iter()
+        .find_map(|u| match u {
+            StreamUpdate::ToolCalls(calls) => Some(calls.clone()),
+            _ => None,
+        })
+        .expect("Tool calls should be emitted");
+
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].id, "call_1");
+    assert_eq!(calls[0].function.arguments, "{\"expression\":\"2+2\"}");
+    assert_eq!(calls[1].id, "call_2");
+    assert_eq!(calls[1].function.arguments, "{\"expression\":\"3*3\"}");
+}
+
+#[tokio::test]
+async fn test_stream_reasoning_without_tools() {
+    let mock_server = MockServer::start().await;
+
+    let body = format!(
+        "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+        delta(json!({"reasoning_content": "Thinking..."}), None),
+        delta(json!({"content": "Hello!"}), Some("stop")),
+    );
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let updates = collect(service.chat_stream("Hi".to_string(), true).await).await;
+
+    assert!(matches!(&updates[0], StreamUpdate::Reasoning(r) if r == "Thinking..."));
+    assert!(matches!(&updates[1], StreamUpdate::Content(c) if c == "Hello!"));
+    assert!(matches!(updates[2], StreamUpdate::Done));
+    assert!(!updates
+        .iter()
+        .any(|u| matches!(u, StreamUpdate::ToolCalls(_))));
+}
+
+#[tokio::test]
+async fn test_stream_messages_sends_history_and_options() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "model": "deepseek-chat",
+            "stream": true,
+            "temperature": 0.2,
+            "max_tokens": 256,
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Hi, I'm Ada."},
+                {"role": "assistant", "content": "Hello Ada!"},
+                {"role": "user", "content": "Who am I?"}
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            sse_body(&[delta(json!({"content": "You're Ada."}), Some("stop"))]),
+            "text/event-stream",
+        ))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let message = |role: &str, content: &str| ChatMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+        tool_call_id: None,
+        tool_calls: None,
+    };
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let rx = service
+        .chat_stream_messages(
+            vec![
+                message("system", "Be brief."),
+                message("user", "Hi, I'm Ada."),
+                message("assistant", "Hello Ada!"),
+                message("user", "Who am I?"),
+            ],
+            None,
+            None,
+            GenerationOptions {
+                temperature: Some(0.2),
+                max_tokens: Some(256),
+                ..Default::default()
+            },
+        )
+        .await;
+    let updates = collect(rx).await;
+
+    assert!(matches!(&updates[0], StreamUpdate::Content(c) if c == "You're Ada."));
+    assert!(matches!(updates[1], StreamUpdate::Done));
+}
+
+#[tokio::test]
+async fn test_stream_reports_usage_before_done() {
+    let mock_server = MockServer::start().await;
+
+    let body = sse_body(&[
+        delta(json!({"content": "Hi!"}), Some("stop")),
+        json!({
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 12,
+                "completion_tokens": 3,
+                "total_tokens": 15,
+                "prompt_cache_hit_tokens": 0,
+                "prompt_cache_miss_tokens": 12
+            }
+        }),
+    ]);
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "stream": true,
+            "stream_options": {"include_usage": true}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let updates = collect(service.chat_stream("Hi".to_string(), false).await).await;
+
+    assert!(matches!(&updates[0], StreamUpdate::Content(c) if c == "Hi!"));
+    match &updates[1] {
+        StreamUpdate::Usage(usage) => {
+            assert_eq!(usage.prompt_tokens, 12);
+            assert_eq!(usage.completion_tokens, 3);
+            assert_eq!(usage.prompt_cache_miss_tokens, 12);
+        }
+        other => panic!("Expected usage, got {:?}", other),
+    }
+    assert!(matches!(updates[2], StreamUpdate::Done));
+}
@@ -0,0 +1,325 @@
+use git2::{Repository, Signature};
+use openagents::repo::{changed_files, head_commit_sha};
+use openagents::server::services::github_issue::{GitHubApiError, GitHubService};
+use openagents::server::services::github_types::{FileChange, NewPullRequest};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{body_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn service(github: &MockServer) -> GitHubService {
+    GitHubService::with_base_url("test_token".to_string(), github.uri())
+}
+
+#[tokio::test]
+async fn test_branch_commit_and_pull_request() {
+    let github = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/git/refs"))
+        .and(body_json(
+            json!({"ref": "refs/heads/solver/issue-7", "sha": "base-sha"}),
+        ))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "ref": "refs/heads/solver/issue-7",
+            "object": {"sha": "base-sha", "type": "commit"}
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    service(&github)
+        .set_branch("owner", "repo", "solver/issue-7", "base-sha")
+        .await
+        .unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/git/ref/heads/solver/issue-7"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ref": "refs/heads/solver/issue-7",
+            "object": {"sha": "base-sha", "type": "commit"}
+        })))
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/git/commits/base-sha"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "base-sha",
+            "tree": {"sha": "base-tree"}
+        })))
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/git/blobs"))
+        .and(body_json(json!({"content": "AP8=", "encoding": "base64"})))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"sha": "blob-sha"})))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/git/trees"))
+        .and(body_json(json!({
+            "base_tree": "base-tree",
+            "tree": [
+                {"path": "src/lib.rs", "mode": "100644", "type": "blob", "content": "pub fn fixed() {}\n"},
+                {"path": "assets/logo.bin", "mode": "100644", "type": "blob", "sha": "blob-sha"},
+                {"path": "scripts/release.sh", "mode": "100755", "type": "blob", "content": "#!/bin/sh\n"},
+                {"path": "src/old.rs", "mode": "100644", "type": "blob", "sha": null}
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"sha": "new-tree"})))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/git/commits"))
+        .and(body_json(json!({
+            "message": "Fix #7: Broken",
+            "tree": "new-tree",
+            "parents": ["base-sha"]
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"sha": "new-commit"})))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/owner/repo/git/refs/heads/solver/issue-7"))
+        .and(body_json(json!({"sha": "new-commit"})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ref": "refs/heads/solver/issue-7",
+            "object": {"sha": "new-commit", "type": "commit"}
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let changes = vec![
+        FileChange {
+            path: "src/lib.rs".to_string(),
+            mode: 0o100644,
+            content: Some(b"pub fn fixed() {}\n".to_vec()),
+        },
+        FileChange {
+            path: "assets/logo.bin".to_string(),
+            mode: 0o100644,
+            content: Some(vec![0x00, 0xff]),
+        },
+        FileChange {
+            path: "scripts/release.sh".to_string(),
+            mode: 0o100755,
+            content: Some(b"#!/bin/sh\n".to_vec()),
+        },
+        FileChange {
+            path: "src/old.rs".to_string(),
+            mode: 0o100644,
+            content: None,
+        },
+    ];
+    let sha = service(&github)
+        .commit_files(
+            "owner",
+            "repo",
+            "solver/issue-7",
+            "Fix #7: Broken",
+            &changes,
+        )
+        .await
+        .unwrap();
+    assert_eq!(sha, "new-commit");
+
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/pulls"))
+        .and(body_json(json!({
+            "title": "Fix #7: Broken",
+            "head": "solver/issue-7",
+            "base": "main",
+            "body": "Fixes #7\n\nFixed it.",
+            "draft": false
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "number": 8,
+            "title": "Fix #7: Broken",
+            "body": "Fixes #7\n\nFixed it.",
+            "state": "open",
+            "html_url": "https://github.com/owner/repo/pull/8",
+            "head": {"ref": "solver/issue-7", "sha": "new-commit"},
+            "base": {"ref": "main", "sha": "base-sha"}
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let pull_request = service(&github)
+        .create_pull_request(
+            "owner",
+            "repo",
+            &NewPullRequest {
+                title: "Fix #7: Broken".to_string(),
+                head: "solver/issue-7".to_string(),
+                base: "main".to_string(),
+                body: "Fixes #7\n\nFixed it.".to_string(),
+                draft: false,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(pull_request.number, 8);
+    assert_eq!(
+        pull_request.html_url,
+        "https://github.com/owner/repo/pull/8"
+    );
+}
+
+#[tokio::test]
+async fn test_set_branch_moves_an_existing_branch() {
+    let github = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/git/refs"))
+        .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+            "message": "Reference already exists"
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/owner/repo/git/refs/heads/solver/issue-7"))
+        .and(body_json(json!({"sha": "cloned-sha", "force": true})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ref": "refs/heads/solver/issue-7",
+            "object": {"sha": "cloned-sha", "type": "commit"}
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    service(&github)
+        .set_branch("owner", "repo", "solver/issue-7", "cloned-sha")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_failed_writes_are_errors() {
+    let github = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/git/refs"))
+        .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+            "message": "Reference already exists"
+        })))
+        .mount(&github)
+        .await;
+
+    let error = service(&github)
+        .create_branch("owner", "repo", "solver/issue-7", "base-sha")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "GitHub API request failed: 422 Unprocessable Entity: Reference already exists"
+    );
+    let error = error.downcast_ref::<GitHubApiError>().unwrap();
+    assert_eq!(error.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Validation failures say which fields were wrong
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/pulls"))
+        .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+            "message": "Validation Failed",
+            "errors": [
+                {"resource": "PullRequest", "field": "head", "code": "invalid"},
+                {"resource": "PullRequest", "code": "custom", "message": "No commits between main and solver/issue-7"}
+            ]
+        })))
+        .mount(&github)
+        .await;
+    let error = service(&github)
+        .create_pull_request(
+            "owner",
+            "repo",
+            &NewPullRequest {
+                title: "Fix #7: Broken".to_string(),
+                head: "solver/issue-7".to_string(),
+                base: "main".to_string(),
+                body: "Fixes #7".to_string(),
+                draft: false,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "GitHub API request failed: 422 Unprocessable Entity: Validation Failed (head invalid; No commits between main and solver/issue-7)"
+    );
+}
+
+#[test]
+fn test_changed_files() {
+    let dir = std::env::temp_dir().join(format!("openagents-changes-{}", Uuid::new_v4()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+    fs::write(dir.join("src/lib.rs"), "pub fn broken() {}\n").unwrap();
+    fs::write(dir.join("src/old.rs"), "pub fn old() {}\n").unwrap();
+
+    let repo = Repository::init(&dir).unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let commit = repo
+        .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+        .unwrap();
+    assert_eq!(head_commit_sha(&dir).unwrap(), commit.to_string());
+    assert!(changed_files(&dir).unwrap().is_empty());
+
+    fs::write(dir.join("src/lib.rs"), "pub fn fixed() {}\n").unwrap();
+    fs::remove_file(dir.join("src/old.rs")).unwrap();
+    fs::create_dir_all(dir.join("src/new")).unwrap();
+    fs::write(dir.join("src/new/mod.rs"), "pub fn new() {}\n").unwrap();
+    fs::create_dir_all(dir.join("target")).unwrap();
+    fs::write(dir.join("target/build.log"), "ignored\n").unwrap();
+    fs::write(dir.join("release.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(dir.join("release.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    std::os::unix::fs::symlink("src/lib.rs", dir.join("lib.rs")).unwrap();
+
+    let changes = changed_files(&dir).unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(
+        changes,
+        vec![
+            // A symlink's content is where it points
+            FileChange {
+                path: "lib.rs".to_string(),
+                mode: 0o120000,
+                content: Some(b"src/lib.rs".to_vec()),
+            },
+            FileChange {
+                path: "release.sh".to_string(),
+                mode: 0o100755,
+                content: Some(b"#!/bin/sh\n".to_vec()),
+            },
+            FileChange {
+                path: "src/lib.rs".to_string(),
+                mode: 0o100644,
+                content: Some(b"pub fn fixed() {}\n".to_vec()),
+            },
+            FileChange {
+                path: "src/new/mod.rs".to_string(),
+                mode: 0o100644,
+                content: Some(b"pub fn new() {}\n".to_vec()),
+            },
+            FileChange {
+                path: "src/old.rs".to_string(),
+                mode: 0o100644,
+                content: None,
+            },
+        ]
+    );
+}
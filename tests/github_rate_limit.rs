@@ -0,0 +1,306 @@
+use openagents::server::services::github_issue::GitHubService;
+use openagents::server::services::github_rate_limit::{RateLimit, RateLimitExceeded};
+use openagents::server::tools::error_content;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use wiremock::{
+    matchers::{header, header_exists, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn issue(number: i32) -> Value {
+    json!({
+        "number": number,
+        "title": "Crash on empty input",
+        "body": "It panics.",
+        "state": "open",
+        "html_url": format!("https://github.com/owner/repo/issues/{}", number)
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// A response carrying the `X-RateLimit-*` headers.
+fn with_rate_limit(response: ResponseTemplate, remaining: u32, reset: u64) -> ResponseTemplate {
+    response
+        .insert_header("x-ratelimit-limit", "5000")
+        .insert_header("x-ratelimit-remaining", remaining.to_string().as_str())
+        .insert_header("x-ratelimit-reset", reset.to_string().as_str())
+}
+
+#[tokio::test]
+async fn test_enterprise_base_url_and_rate_limit_tracking() {
+    let github = MockServer::start().await;
+    let reset = now() + 1800;
+    Mock::given(method("GET"))
+        .and(path("/api/v3/repos/owner/repo/issues/1"))
+        .respond_with(with_rate_limit(
+            ResponseTemplate::new(200).set_body_json(issue(1)),
+            4999,
+            reset,
+        ))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    // GitHub Enterprise Server serves the API under /api/v3
+    let service = GitHubService::with_base_url(
+        "test_token".to_string(),
+        format!("{}/api/v3/", github.uri()),
+    );
+    assert_eq!(service.base_url(), format!("{}/api/v3", github.uri()));
+    assert_eq!(service.rate_limit("core"), None);
+
+    let issue = service.get_issue("owner", "repo", 1).await.unwrap();
+    assert_eq!(issue.number, 1);
+    assert_eq!(
+        service.rate_limit("core"),
+        Some(RateLimit {
+            limit: 5000,
+            remaining: 4999,
+            reset
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_exhausted_quota_fails_with_a_typed_error() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .respond_with(with_rate_limit(
+            ResponseTemplate::new(403).set_body_json(json!({
+                "message": "API rate limit exceeded for user ID 1."
+            })),
+            0,
+            now() + 3600,
+        ))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri());
+    let error = service.get_issue("owner", "repo", 1).await.unwrap_err();
+    let exceeded = error.downcast_ref::<RateLimitExceeded>().unwrap();
+    assert!(!exceeded.secondary);
+    assert!(exceeded.retry_after > Duration::from_secs(3500));
+    let content: Value = serde_json::from_str(&error_content(&error)).unwrap();
+    assert_eq!(content["kind"], "rate_limited");
+
+    // The quota is known to be used up, so this one isn't sent
+    let error = service.get_issue("owner", "repo", 1).await.unwrap_err();
+    assert!(error.is::<RateLimitExceeded>());
+
+    // Forbidden for other reasons isn't a rate limit
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "message": "Resource not accessible by integration"
+        })))
+        .mount(&github)
+        .await;
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri());
+    let error = service.get_issue("owner", "repo", 1).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "GitHub API request failed: 403 Forbidden: Resource not accessible by integration"
+    );
+}
+
+#[tokio::test]
+async fn test_rate_limits_are_tracked_per_resource() {
+    let github = MockServer::start().await;
+    let reset = now() + 3600;
+    Mock::given(method("GET"))
+        .and(path("/search/issues"))
+        .respond_with(
+            with_rate_limit(
+                ResponseTemplate::new(403).set_body_json(json!({
+                    "message": "API rate limit exceeded for user ID 1."
+                })),
+                0,
+                reset,
+            )
+            .insert_header("x-ratelimit-resource", "search"),
+        )
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .respond_with(
+            with_rate_limit(
+                ResponseTemplate::new(200).set_body_json(issue(1)),
+                4999,
+                reset,
+            )
+            .insert_header("x-ratelimit-resource", "core"),
+        )
+        .expect(2)
+        .mount(&github)
+        .await;
+
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri());
+    let error = service.search_issues("crash", 10).await.unwrap_err();
+    assert!(error.is::<RateLimitExceeded>());
+    assert_eq!(service.rate_limit("search").unwrap().remaining, 0);
+
+    // An exhausted search quota leaves the core one alone
+    service.get_issue("owner", "repo", 1).await.unwrap();
+    assert_eq!(service.rate_limit("core").unwrap().remaining, 4999);
+
+    // And a core response doesn't hide the exhausted search quota
+    service.get_issue("owner", "repo", 1).await.unwrap();
+    let error = service.search_issues("crash", 10).await.unwrap_err();
+    assert!(error.is::<RateLimitExceeded>());
+}
+
+#[tokio::test]
+async fn test_waits_out_a_short_secondary_rate_limit() {
+    let github = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .respond_with(
+            ResponseTemplate::new(403)
+                .insert_header("retry-after", "1")
+                .set_body_json(json!({
+                    "message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."
+                })),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": 1})))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri());
+    let started = Instant::now();
+    service
+        .post_comment("owner", "repo", 1, "Hello")
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+
+    // Past the longest wait allowed, the error comes back instead
+    let github = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "120"))
+        .expect(1)
+        .mount(&github)
+        .await;
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri())
+        .with_max_rate_limit_wait(Duration::from_secs(5));
+    let error = service
+        .post_comment("owner", "repo", 1, "Hello")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<RateLimitExceeded>(),
+        Some(&RateLimitExceeded {
+            secondary: true,
+            retry_after: Duration::from_secs(120)
+        })
+    );
+    assert_eq!(
+        error.to_string(),
+        "GitHub secondary rate limit exceeded, retry in 120s"
+    );
+}
+
+#[tokio::test]
+async fn test_repeated_reads_are_conditional() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(2)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .set_body_json(issue(1)),
+        )
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issue(2)))
+        .expect(2)
+        .mount(&github)
+        .await;
+
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri());
+    let first = service.get_issue("owner", "repo", 1).await.unwrap();
+    // Clones share the cache
+    let second = service.clone().get_issue("owner", "repo", 1).await.unwrap();
+    let third = service.get_issue("owner", "repo", 1).await.unwrap();
+    assert_eq!(first.title, second.title);
+    assert_eq!(second.title, third.title);
+
+    // Without an ETag there is nothing to revalidate
+    service.get_issue("owner", "repo", 2).await.unwrap();
+    service.get_issue("owner", "repo", 2).await.unwrap();
+    let revalidated = github
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/repos/owner/repo/issues/2")
+        .filter(|request| request.headers.contains_key("if-none-match"))
+        .count();
+    assert_eq!(revalidated, 0);
+}
+
+#[tokio::test]
+async fn test_writes_are_not_conditional() {
+    let github = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .and(header_exists("if-none-match"))
+        .respond_with(ResponseTemplate::new(412))
+        .with_priority(1)
+        .expect(0)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .set_body_json(issue(1)),
+        )
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": 1})))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let service = GitHubService::with_base_url("test_token".to_string(), github.uri());
+    service.get_issue("owner", "repo", 1).await.unwrap();
+    service
+        .post_comment("owner", "repo", 1, "Hello")
+        .await
+        .unwrap();
+}
@@ -0,0 +1,356 @@
+use openagents::server::services::deepseek::{FunctionCallResponse, ToolCallResponse};
+use openagents::server::services::github_issue::{GitHubService, IssueFilter};
+use openagents::server::tools::{create_tools, ToolContext};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::{
+    matchers::{header, method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn service(github: &MockServer) -> GitHubService {
+    GitHubService::with_base_url("test_token".to_string(), github.uri())
+}
+
+fn issue(number: i32, comments: u32) -> Value {
+    json!({
+        "number": number,
+        "title": format!("Issue {}", number),
+        "body": format!("Body of {}", number),
+        "state": "open",
+        "html_url": format!("https://github.com/owner/repo/issues/{}", number),
+        "user": {"login": "alice", "id": 1},
+        "labels": [{"name": "bug", "color": "d73a4a", "id": 2}],
+        "comments": comments,
+        "created_at": "2024-05-01T10:00:00Z"
+    })
+}
+
+fn comment(id: u64, login: &str, body: &str) -> Value {
+    json!({
+        "id": id,
+        "user": {"login": login},
+        "body": body,
+        "created_at": format!("2024-05-0{}T12:00:00Z", id),
+        "html_url": format!("https://github.com/owner/repo/issues/1#issuecomment-{}", id)
+    })
+}
+
+/// A page whose `Link` header points at `next`.
+fn page(body: Value, next: Option<String>) -> ResponseTemplate {
+    let response = ResponseTemplate::new(200).set_body_json(body);
+    match next {
+        Some(next) => response.insert_header(
+            "link",
+            format!(r#"<{}>; rel="next", <{}>; rel="last""#, next, next).as_str(),
+        ),
+        None => response,
+    }
+}
+
+fn tool_call(name: &str, arguments: Value) -> ToolCallResponse {
+    ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_listings_follow_link_headers() {
+    let github = MockServer::start().await;
+    let second_page = format!("{}/repositories/1/issues?state=all&page=2", github.uri());
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues"))
+        .and(query_param("state", "all"))
+        .and(query_param("labels", "bug,ui"))
+        .and(query_param("per_page", "3"))
+        .respond_with(page(json!([issue(5, 0), issue(4, 0)]), Some(second_page)))
+        .expect(2)
+        .mount(&github)
+        .await;
+    let mut pull = issue(3, 0);
+    pull["pull_request"] = json!({"url": "https://api.github.com/repos/owner/repo/pulls/3"});
+    Mock::given(method("GET"))
+        .and(path("/repositories/1/issues"))
+        .and(query_param("page", "2"))
+        .respond_with(page(json!([pull, issue(2, 0)]), None))
+        .expect(2)
+        .mount(&github)
+        .await;
+
+    let filter = IssueFilter {
+        state: Some("all".to_string()),
+        labels: vec!["bug".to_string(), "ui".to_string()],
+        limit: Some(3),
+        ..Default::default()
+    };
+    let issues = service(&github)
+        .list_issues("owner", "repo", &filter)
+        .await
+        .unwrap();
+    let numbers: Vec<i32> = issues.iter().map(|issue| issue.number).collect();
+    assert_eq!(numbers, vec![5, 4, 3]);
+    assert!(!issues[0].is_pull_request());
+    assert!(issues[2].is_pull_request());
+    assert_eq!(issues[0].user.as_ref().unwrap().login, "alice");
+    assert_eq!(issues[0].labels[0].name, "bug");
+
+    // The tool lists them without their bodies
+    let tools = create_tools(Arc::new(service(&github)));
+    let listed = tools
+        .execute(
+            &tool_call(
+                "list_github_issues",
+                json!({"owner": "owner", "repo": "repo", "state": "all",
+                       "labels": ["bug", "ui"], "limit": 3}),
+            ),
+            &ToolContext::default(),
+        )
+        .await
+        .unwrap();
+    let listed: Value = serde_json::from_str(&listed).unwrap();
+    assert_eq!(
+        listed[2],
+        json!({
+            "number": 3,
+            "title": "Issue 3",
+            "state": "open",
+            "pull_request": true,
+            "author": "alice",
+            "labels": ["bug"],
+            "comments": 0,
+            "html_url": "https://github.com/owner/repo/issues/3"
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_issue_threads_include_the_discussion() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issue(1, 3)))
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issue(2, 0)))
+        .mount(&github)
+        .await;
+    let second_page = format!("{}/repos/owner/repo/issues/1/comments?page=2", github.uri());
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .and(query_param("per_page", "100"))
+        .respond_with(page(
+            json!([
+                comment(1, "bob", "Can reproduce"),
+                comment(2, "alice", "Fixed in #4?")
+            ]),
+            Some(second_page),
+        ))
+        .expect(2)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1/comments"))
+        .and(query_param("page", "2"))
+        .respond_with(page(json!([comment(3, "bob", "Yes")]), None))
+        .with_priority(1)
+        .expect(2)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/2/comments"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&github)
+        .await;
+
+    let github_service = service(&github);
+    let thread = github_service
+        .get_issue_thread("owner", "repo", 1)
+        .await
+        .unwrap();
+    assert_eq!(thread.discussion.len(), 3);
+    assert_eq!(
+        thread.for_prompt(),
+        "Issue #1: Issue 1\n\nBody of 1\n\nDiscussion:\
+         \n\n@bob (2024-05-01T12:00:00Z):\nCan reproduce\
+         \n\n@alice (2024-05-02T12:00:00Z):\nFixed in #4?\
+         \n\n@bob (2024-05-03T12:00:00Z):\nYes"
+    );
+    // Issues without comments take one request
+    let quiet = github_service
+        .get_issue_thread("owner", "repo", 2)
+        .await
+        .unwrap();
+    assert!(quiet.discussion.is_empty());
+
+    let tools = create_tools(Arc::new(github_service));
+    let read = tools
+        .execute(
+            &tool_call(
+                "read_github_issue",
+                json!({"owner": "owner", "repo": "repo", "issue_number": 1}),
+            ),
+            &ToolContext::default(),
+        )
+        .await
+        .unwrap();
+    let read: Value = serde_json::from_str(&read).unwrap();
+    assert_eq!(read["title"], "Issue 1");
+    assert_eq!(read["comments"], 3);
+    assert_eq!(read["discussion"][2]["body"], "Yes");
+}
+
+#[tokio::test]
+async fn test_timelines_pulls_labels_and_search() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/1/timeline"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"event": "labeled", "actor": {"login": "alice"},
+             "created_at": "2024-05-01T11:00:00Z", "label": {"name": "bug", "color": "d73a4a"}},
+            {"event": "commented", "user": {"login": "bob"}, "body": "Can reproduce",
+             "created_at": "2024-05-01T12:00:00Z"},
+            {"event": "cross-referenced", "source": {"type": "issue"}}
+        ])))
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/pulls/4"))
+        .and(header("accept", "application/vnd.github.diff"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("diff --git a/src/lib.rs b/src/lib.rs\n-    42\n+    43\n"),
+        )
+        .with_priority(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/pulls/4"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "number": 4,
+            "title": "Fix the answer",
+            "body": "Fixes #1",
+            "state": "open",
+            "html_url": "https://github.com/owner/repo/pull/4",
+            "user": {"login": "alice"},
+            "head": {"ref": "fix-answer", "sha": "abc123", "label": "alice:fix-answer"},
+            "base": {"ref": "main", "sha": "def456"},
+            "draft": false,
+            "merged": false,
+            "additions": 1,
+            "deletions": 1,
+            "changed_files": 1
+        })))
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/pulls/4/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "sha": "abc",
+            "filename": "src/lib.rs",
+            "status": "modified",
+            "additions": 1,
+            "deletions": 1,
+            "changes": 2,
+            "patch": "@@ -1 +1 @@\n-    42\n+    43"
+        }])))
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/labels"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"name": "bug", "color": "d73a4a", "description": "Something isn't working"}
+        ])))
+        .mount(&github)
+        .await;
+    let second_page = format!("{}/search/issues?q=crash&page=2", github.uri());
+    Mock::given(method("GET"))
+        .and(path("/search/issues"))
+        .and(query_param("q", "repo:owner/repo crash"))
+        .respond_with(page(
+            json!({"total_count": 40, "incomplete_results": false,
+                   "items": [issue(9, 1), issue(8, 0)]}),
+            Some(second_page),
+        ))
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/search/issues"))
+        .and(query_param("page", "2"))
+        .respond_with(page(
+            json!({"total_count": 40, "incomplete_results": true,
+                   "items": [issue(7, 0), issue(6, 0)]}),
+            None,
+        ))
+        .with_priority(1)
+        .mount(&github)
+        .await;
+
+    let github_service = service(&github);
+    let timeline = github_service
+        .get_issue_timeline("owner", "repo", 1)
+        .await
+        .unwrap();
+    assert_eq!(timeline.len(), 3);
+    assert_eq!(timeline[0].label.as_ref().unwrap().name, "bug");
+    assert_eq!(timeline[1].user.as_ref().unwrap().login, "bob");
+    assert_eq!(timeline[2].event.as_deref(), Some("cross-referenced"));
+
+    let pull = github_service
+        .get_pull_request("owner", "repo", 4)
+        .await
+        .unwrap();
+    assert_eq!(pull.head.branch, "fix-answer");
+    assert_eq!(pull.base.branch, "main");
+    let diff = github_service
+        .get_pull_request_diff("owner", "repo", 4)
+        .await
+        .unwrap();
+    assert!(diff.starts_with("diff --git a/src/lib.rs"));
+
+    let labels = github_service.list_labels("owner", "repo").await.unwrap();
+    assert_eq!(
+        labels[0].description.as_deref(),
+        Some("Something isn't working")
+    );
+
+    let search = github_service
+        .search_issues("repo:owner/repo crash", 3)
+        .await
+        .unwrap();
+    assert_eq!(search.total_count, 40);
+    assert!(search.incomplete_results);
+    let numbers: Vec<i32> = search.items.iter().map(|issue| issue.number).collect();
+    assert_eq!(numbers, vec![9, 8, 7]);
+
+    let missing = github_service
+        .get_pull_request("owner", "repo", 5)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        missing.to_string(),
+        "GitHub API request failed: 404 Not Found"
+    );
+
+    let tools = create_tools(Arc::new(github_service));
+    let read = tools
+        .execute(
+            &tool_call(
+                "read_pull_request",
+                json!({"owner": "owner", "repo": "repo", "pull_number": 4}),
+            ),
+            &ToolContext::default(),
+        )
+        .await
+        .unwrap();
+    let read: Value = serde_json::from_str(&read).unwrap();
+    assert_eq!(read["pull_request"]["title"], "Fix the answer");
+    assert_eq!(read["files"][0]["patch"], "@@ -1 +1 @@\n-    42\n+    43");
+}
@@ -0,0 +1,403 @@
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use bitcoin_hashes::{hmac, sha256, Hash, HashEngine};
+use openagents::repo::RepoCache;
+use openagents::server::handlers::{github_webhook, WebhookState};
+use openagents::server::services::deepseek::DeepSeekService;
+use openagents::server::services::github_issue::GitHubService;
+use openagents::server::services::github_webhook::{
+    verify_signature, AgentAction, AgentJob, AgentJobRunner, GitHubAgentRunner, WebhookConfig,
+};
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+use uuid::Uuid;
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const SECRET: &str = "webhook-secret";
+
+fn sign(body: &[u8]) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(SECRET.as_bytes());
+    engine.input(body);
+    format!(
+        "sha256={:x}",
+        hmac::Hmac::<sha256::Hash>::from_engine(engine)
+    )
+}
+
+fn app(capacity: usize) -> (Router, mpsc::Receiver<AgentJob>) {
+    let config = WebhookConfig::new(SECRET)
+        .with_label("agent:plan", AgentAction::Plan)
+        .with_label("agent:tests", AgentAction::SuggestTests)
+        .with_bot_login("openagents-bot");
+    let (jobs, queue) = mpsc::channel(capacity);
+    let app = Router::new()
+        .route("/webhooks/github", post(github_webhook))
+        .with_state(WebhookState::new(config, jobs));
+    (app, queue)
+}
+
+async fn deliver(
+    app: &Router,
+    event: &str,
+    delivery: &str,
+    payload: &Value,
+    signature: Option<String>,
+) -> (StatusCode, Value) {
+    let body = payload.to_string();
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/webhooks/github")
+        .header("content-type", "application/json")
+        .header("x-github-event", event)
+        .header("x-github-delivery", delivery);
+    if let Some(signature) = signature {
+        request = request.header("x-hub-signature-256", signature);
+    }
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+async fn deliver_signed(
+    app: &Router,
+    event: &str,
+    delivery: &str,
+    payload: &Value,
+) -> (StatusCode, Value) {
+    let signature = sign(payload.to_string().as_bytes());
+    deliver(app, event, delivery, payload, Some(signature)).await
+}
+
+fn repository() -> Value {
+    json!({
+        "name": "repo",
+        "full_name": "owner/repo",
+        "owner": {"login": "owner", "id": 1},
+        "default_branch": "main",
+        "html_url": "https://github.com/owner/repo"
+    })
+}
+
+fn issue(number: i32) -> Value {
+    json!({
+        "number": number,
+        "title": "Crash on empty input",
+        "body": "It panics.",
+        "state": "open",
+        "html_url": format!("https://github.com/owner/repo/issues/{}", number),
+        "user": {"login": "alice"}
+    })
+}
+
+fn labeled(number: i32, label: &str) -> Value {
+    json!({
+        "action": "labeled",
+        "issue": issue(number),
+        "label": {"name": label, "color": "ededed"},
+        "repository": repository(),
+        "sender": {"login": "alice"}
+    })
+}
+
+fn commented(number: i32, login: &str, body: &str) -> Value {
+    json!({
+        "action": "created",
+        "issue": issue(number),
+        "comment": {
+            "id": 10,
+            "user": {"login": login},
+            "body": body,
+            "author_association": "COLLABORATOR",
+            "created_at": "2024-05-01T12:00:00Z",
+            "html_url": format!("https://github.com/owner/repo/issues/{}#issuecomment-10", number)
+        },
+        "repository": repository(),
+        "sender": {"login": login}
+    })
+}
+
+fn job(action: AgentAction, number: i32, delivery: &str) -> AgentJob {
+    AgentJob {
+        action,
+        owner: "owner".to_string(),
+        repo: "repo".to_string(),
+        number,
+        delivery: delivery.to_string(),
+    }
+}
+
+#[test]
+fn test_verify_signature() {
+    // The example from GitHub's webhook documentation
+    let secret = b"It's a Secret to Everybody";
+    let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+    assert!(verify_signature(secret, b"Hello, World!", signature));
+    assert!(!verify_signature(secret, b"Hello, World?", signature));
+    assert!(!verify_signature(
+        b"another secret",
+        b"Hello, World!",
+        signature
+    ));
+    assert!(!verify_signature(
+        secret,
+        b"Hello, World!",
+        signature.trim_start_matches("sha256=")
+    ));
+    assert!(!verify_signature(secret, b"Hello, World!", "sha256="));
+}
+
+#[tokio::test]
+async fn test_webhook_queues_jobs() {
+    let (app, mut queue) = app(10);
+
+    let payload = labeled(7, "agent:plan");
+    let (status, body) = deliver(&app, "issues", "d-1", &payload, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body, json!({"error": "Invalid signature"}));
+    let (status, _) = deliver(&app, "issues", "d-1", &payload, Some(sign(b"{}"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = deliver_signed(&app, "issues", "d-1", &payload).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body, json!({"status": "queued", "jobs": 1}));
+    assert_eq!(queue.try_recv().unwrap(), job(AgentAction::Plan, 7, "d-1"));
+
+    // GitHub redelivers with the same ID
+    let (status, body) = deliver_signed(&app, "issues", "d-1", &payload).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"status": "duplicate"}));
+    assert!(queue.try_recv().is_err());
+
+    let (status, body) =
+        deliver_signed(&app, "issues", "d-2", &labeled(7, "good first issue")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"status": "ignored"}));
+
+    let comment = commented(
+        8,
+        "alice",
+        "@OpenAgents-Bot analyze this, and @openagents-bot tests please. Not @openagents-botany.",
+    );
+    let (status, body) = deliver_signed(&app, "issue_comment", "d-3", &comment).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body, json!({"status": "queued", "jobs": 2}));
+    assert_eq!(
+        queue.try_recv().unwrap(),
+        job(AgentAction::Analysis, 8, "d-3")
+    );
+    assert_eq!(
+        queue.try_recv().unwrap(),
+        job(AgentAction::SuggestTests, 8, "d-3")
+    );
+
+    // A bare mention asks for a plan
+    let comment = commented(8, "alice", "cc @openagents-bot");
+    deliver_signed(&app, "issue_comment", "d-4", &comment).await;
+    assert_eq!(queue.try_recv().unwrap(), job(AgentAction::Plan, 8, "d-4"));
+
+    // The bot's own comments never start jobs
+    let comment = commented(
+        8,
+        "openagents-bot",
+        "# Implementation Plan\n\n@openagents-bot",
+    );
+    let (_, body) = deliver_signed(&app, "issue_comment", "d-5", &comment).await;
+    assert_eq!(body, json!({"status": "ignored"}));
+
+    let pull_request = json!({
+        "action": "labeled",
+        "pull_request": {
+            "number": 9,
+            "title": "Fix the crash",
+            "body": null,
+            "state": "open",
+            "html_url": "https://github.com/owner/repo/pull/9",
+            "head": {"ref": "fix", "sha": "abc"},
+            "base": {"ref": "main", "sha": "def"}
+        },
+        "label": {"name": "agent:tests"},
+        "repository": repository(),
+        "sender": {"login": "alice"}
+    });
+    deliver_signed(&app, "pull_request", "d-6", &pull_request).await;
+    assert_eq!(
+        queue.try_recv().unwrap(),
+        job(AgentAction::SuggestTests, 9, "d-6")
+    );
+
+    let (status, body) =
+        deliver_signed(&app, "ping", "d-7", &json!({"zen": "Keep it simple."})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"status": "ignored"}));
+    assert!(queue.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_mentions_need_a_trusted_author() {
+    let config = WebhookConfig::new(SECRET)
+        .with_label("agent:plan", AgentAction::Plan)
+        .with_bot_login("openagents-bot")
+        .with_trusted_user("@Carol");
+    let (jobs, mut queue) = mpsc::channel(10);
+    let app = Router::new()
+        .route("/webhooks/github", post(github_webhook))
+        .with_state(WebhookState::new(config, jobs));
+
+    // Anyone can comment on a public repository
+    let mut comment = commented(8, "mallory", "@openagents-bot analyze");
+    comment["comment"]["author_association"] = json!("NONE");
+    let (status, body) = deliver_signed(&app, "issue_comment", "d-1", &comment).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"status": "ignored"}));
+    comment["comment"]
+        .as_object_mut()
+        .unwrap()
+        .remove("author_association");
+    let (_, body) = deliver_signed(&app, "issue_comment", "d-2", &comment).await;
+    assert_eq!(body, json!({"status": "ignored"}));
+
+    let mut opened = json!({
+        "action": "opened",
+        "issue": issue(9),
+        "repository": repository(),
+        "sender": {"login": "mallory"}
+    });
+    opened["issue"]["body"] = json!("@openagents-bot plan");
+    opened["issue"]["author_association"] = json!("CONTRIBUTOR");
+    let (_, body) = deliver_signed(&app, "issues", "d-3", &opened).await;
+    assert_eq!(body, json!({"status": "ignored"}));
+    assert!(queue.try_recv().is_err());
+
+    // Owners, members and collaborators, and the trusted users, can
+    opened["issue"]["author_association"] = json!("OWNER");
+    let (status, _) = deliver_signed(&app, "issues", "d-4", &opened).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(queue.try_recv().unwrap(), job(AgentAction::Plan, 9, "d-4"));
+
+    let mut comment = commented(8, "carol", "@openagents-bot analyze");
+    comment["comment"]["author_association"] = json!("NONE");
+    let (status, _) = deliver_signed(&app, "issue_comment", "d-5", &comment).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(
+        queue.try_recv().unwrap(),
+        job(AgentAction::Analysis, 8, "d-5")
+    );
+
+    // Labels take triage access to add, so they count whoever adds them
+    let (status, _) = deliver_signed(&app, "issues", "d-6", &labeled(10, "agent:plan")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(queue.try_recv().unwrap(), job(AgentAction::Plan, 10, "d-6"));
+}
+
+#[tokio::test]
+async fn test_full_queue_lets_github_redeliver() {
+    let (app, mut queue) = app(1);
+
+    let (status, _) = deliver_signed(&app, "issues", "d-1", &labeled(1, "agent:plan")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let payload = labeled(2, "agent:plan");
+    let (status, body) = deliver_signed(&app, "issues", "d-2", &payload).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, json!({"error": "The job queue is full"}));
+
+    queue.try_recv().unwrap();
+    let (status, _) = deliver_signed(&app, "issues", "d-2", &payload).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(queue.try_recv().unwrap(), job(AgentAction::Plan, 2, "d-2"));
+}
+
+/// A delivery asking for more jobs than there is room for queues none of
+/// them, so its redelivery doesn't run any twice.
+#[tokio::test]
+async fn test_deliveries_queue_all_their_jobs_or_none() {
+    let (app, mut queue) = app(2);
+    deliver_signed(&app, "issues", "d-1", &labeled(1, "agent:plan")).await;
+    let comment = commented(3, "alice", "@openagents-bot analyze, @openagents-bot tests");
+    let (status, _) = deliver_signed(&app, "issue_comment", "d-2", &comment).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(queue.try_recv().unwrap(), job(AgentAction::Plan, 1, "d-1"));
+    assert!(queue.try_recv().is_err());
+
+    let (status, _) = deliver_signed(&app, "issue_comment", "d-2", &comment).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(
+        queue.try_recv().unwrap(),
+        job(AgentAction::Analysis, 3, "d-2")
+    );
+    assert_eq!(
+        queue.try_recv().unwrap(),
+        job(AgentAction::SuggestTests, 3, "d-2")
+    );
+}
+
+#[tokio::test]
+async fn test_plan_job_comments_on_the_issue() {
+    let repo = std::env::temp_dir().join(format!("openagents-webhook-{}", Uuid::new_v4()));
+    fs::create_dir_all(repo.join("src")).unwrap();
+    fs::write(repo.join("src/lib.rs"), "pub fn parse(input: &str) {}\n").unwrap();
+
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/7"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(issue(7)))
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo/issues/7/comments"))
+        .and(body_partial_json(json!({
+            "body": "# Implementation Plan\n\nCheck for empty input in parse."
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": 1})))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let deepseek = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {
+                "content": "Check for empty input in parse.",
+                "role": "assistant"
+            }}]
+        })))
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+
+    let runner = GitHubAgentRunner::new(
+        Arc::new(GitHubService::with_base_url(
+            "test_token".to_string(),
+            github.uri(),
+        )),
+        Arc::new(DeepSeekService::with_base_url(
+            "test_key".to_string(),
+            deepseek.uri(),
+        )),
+        Arc::new(RepoCache::new(std::env::temp_dir()).with_local_repo("owner/repo", &repo)),
+    );
+    let result = runner.run(&job(AgentAction::Plan, 7, "d-1")).await;
+    let requests = deepseek.received_requests().await.unwrap();
+    let _ = fs::remove_dir_all(&repo);
+    result.unwrap();
+
+    let prompt: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let prompt = prompt["messages"][0]["content"].as_str().unwrap();
+    assert!(prompt.contains("Issue #7: Crash on empty input"));
+    assert!(prompt.contains("src/lib.rs:\n│fn parse"));
+}
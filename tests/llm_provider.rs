@@ -0,0 +1,145 @@
+use openagents::server::services::{
+    deepseek::{ChatCompletion, DeepSeekService, GenerationOptions},
+    model_router::ModelRouter,
+    ChatProvider, OpenAICompatService,
+};
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::{
+    matchers::{body_partial_json, header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn completion(content: &str) -> serde_json::Value {
+    json!({
+        "choices": [{
+            "message": {
+                "content": content,
+                "role": "assistant"
+            }
+        }]
+    })
+}
+
+#[tokio::test]
+async fn test_openai_compat_uses_configured_model() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(header("authorization", "Bearer local-key"))
+        .and(body_partial_json(json!({ "model": "llama3.1:8b" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Hi from llama")))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let provider = OpenAICompatService::new(
+        mock_server.uri(),
+        Some("local-key".to_string()),
+        "llama3.1:8b".to_string(),
+        None,
+    );
+
+    let ChatCompletion {
+        content: response, ..
+    } = provider
+        .chat("Hello".to_string(), GenerationOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(response, "Hi from llama");
+
+    // Without a dedicated reasoning model, reasoning requests use the regular model
+    let ChatCompletion {
+        content: response, ..
+    } = provider
+        .chat("Think hard".to_string(), GenerationOptions::reasoner())
+        .await
+        .unwrap();
+    assert_eq!(response, "Hi from llama");
+}
+
+#[tokio::test]
+async fn test_openai_compat_reasoner_model() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "model": "qwq:32b" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Reasoned answer")))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let provider = OpenAICompatService::new(
+        mock_server.uri(),
+        None,
+        "qwen2.5:7b".to_string(),
+        Some("qwq:32b".to_string()),
+    );
+
+    let ChatCompletion {
+        content: response, ..
+    } = provider
+        .chat("Think hard".to_string(), GenerationOptions::reasoner())
+        .await
+        .unwrap();
+    assert_eq!(response, "Reasoned answer");
+}
+
+#[tokio::test]
+async fn test_model_router_with_mixed_providers() {
+    let local_server = MockServer::start().await;
+    let deepseek_server = MockServer::start().await;
+
+    // Routing goes to the local tool model and is deterministic
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(
+            json!({ "model": "llama3.1:8b", "temperature": 0.0 }),
+        ))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(completion(
+                &json!({
+                    "needs_tool": false,
+                    "reasoning": "General chat message that doesn't require tools",
+                    "suggested_tool": null
+                })
+                .to_string(),
+            )),
+        )
+        .expect(1)
+        .mount(&local_server)
+        .await;
+
+    // Conversation goes to DeepSeek
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "model": "deepseek-chat" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Hello there!")))
+        .expect(1)
+        .mount(&deepseek_server)
+        .await;
+
+    let tool_model: Arc<dyn ChatProvider> = Arc::new(OpenAICompatService::new(
+        local_server.uri(),
+        None,
+        "llama3.1:8b".to_string(),
+        None,
+    ));
+    let chat_model: Arc<dyn ChatProvider> = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        deepseek_server.uri(),
+    ));
+
+    let router = ModelRouter::new(tool_model, chat_model, vec![]);
+
+    let (decision, tool_calls, _) = router.route_message("Hi!".to_string()).await.unwrap();
+    assert!(!decision.needs_tool);
+    assert!(tool_calls.is_none());
+
+    let ChatCompletion {
+        content: response, ..
+    } = router.chat("Hi!".to_string(), false).await.unwrap();
+    assert_eq!(response, "Hello there!");
+}
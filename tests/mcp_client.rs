@@ -0,0 +1,235 @@
+use openagents::server::mcp::{McpClient, McpConfig, McpServerConfig};
+use openagents::server::services::{
+    agent::AgentLoop,
+    deepseek::{ChatMessage, DeepSeekService, FunctionCallResponse, ToolCallResponse},
+};
+use openagents::server::tools::{
+    create_mcp_tools, error_content, Approval, ApprovalRequest, RiskLevel, ToolApprover,
+    ToolContext,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::{
+    matchers::{body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn echo_server() -> McpServerConfig {
+    McpServerConfig {
+        command: env!("CARGO_BIN_EXE_mcp-echo").to_string(),
+        ..Default::default()
+    }
+}
+
+fn call(name: &str, arguments: Value) -> ToolCallResponse {
+    ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+struct ApproveAll;
+
+#[async_trait::async_trait]
+impl ToolApprover for ApproveAll {
+    async fn approve(&self, _request: &ApprovalRequest) -> Approval {
+        Approval::Approved
+    }
+}
+
+#[tokio::test]
+async fn test_client_handshake_and_calls() {
+    let client = McpClient::spawn("echo", &echo_server()).await.unwrap();
+    assert_eq!(client.server_info()["name"], "mcp-echo");
+
+    // Both pages
+    let tools = client.list_tools().await.unwrap();
+    let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+    assert_eq!(names, vec!["echo", "fail"]);
+    assert_eq!(tools[0].input_schema["required"], json!(["text"]));
+
+    let echoed = client
+        .call_tool("echo", json!({"text": "hello"}))
+        .await
+        .unwrap();
+    assert!(!echoed.is_error);
+    assert_eq!(echoed.to_text(), "hello");
+
+    let error = client.call_tool("missing", json!({})).await.unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "MCP server echo failed tools/call: Unknown tool \"missing\" (code -32602)"
+    );
+
+    // Requests in flight at once get their own answers
+    let (a, b) = tokio::join!(
+        client.call_tool("echo", json!({"text": "a"})),
+        client.call_tool("echo", json!({"text": "b"}))
+    );
+    assert_eq!(a.unwrap().to_text(), "a");
+    assert_eq!(b.unwrap().to_text(), "b");
+}
+
+#[tokio::test]
+async fn test_servers_that_dont_answer() {
+    let exits = McpServerConfig {
+        command: "true".to_string(),
+        ..Default::default()
+    };
+    let error = McpClient::spawn("exits", &exits).await.unwrap_err();
+    assert_eq!(error.to_string(), "MCP server exits has exited");
+
+    let hangs = McpServerConfig {
+        command: "sleep".to_string(),
+        args: vec!["30".to_string()],
+        ..Default::default()
+    };
+    let error = McpClient::spawn_with_timeout("hangs", &hangs, Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "MCP server hangs did not answer initialize in time"
+    );
+}
+
+#[tokio::test]
+async fn test_mcp_tools_join_the_registry() {
+    let config: McpConfig = serde_json::from_value(json!({
+        "mcpServers": {
+            "echo": {"command": env!("CARGO_BIN_EXE_mcp-echo")},
+            "missing": {"command": "/nonexistent/mcp-server"}
+        }
+    }))
+    .unwrap();
+    let tools = create_mcp_tools(&config).await;
+    assert_eq!(tools.names(), vec!["echo", "fail"]);
+
+    // Only tools marked read-only skip approval
+    assert_eq!(tools.get("echo").unwrap().risk(), RiskLevel::ReadOnly);
+    assert_eq!(tools.get("fail").unwrap().risk(), RiskLevel::SideEffecting);
+    assert_eq!(
+        tools.get("fail").unwrap().status(&json!({})),
+        "Calling fail on echo"
+    );
+
+    assert_eq!(
+        tools
+            .execute(
+                &call("echo", json!({"text": "hi"})),
+                &ToolContext::default()
+            )
+            .await
+            .unwrap(),
+        "hi"
+    );
+    // Checked against the server's schema before it is called
+    let invalid = tools
+        .execute(&call("echo", json!({})), &ToolContext::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        invalid.to_string(),
+        "Invalid arguments for echo: /text is required"
+    );
+
+    let context = ToolContext {
+        approver: Some(Arc::new(ApproveAll)),
+        ..Default::default()
+    };
+    let failed = tools
+        .execute(&call("fail", json!({"message": "boom"})), &context)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        serde_json::from_str::<Value>(&error_content(&failed)).unwrap(),
+        json!({"error": "fail failed: boom", "kind": "tool_failed"})
+    );
+
+    let trusted: McpConfig = serde_json::from_value(json!({
+        "mcpServers": {
+            "echo": {"command": env!("CARGO_BIN_EXE_mcp-echo"), "read_only": true}
+        }
+    }))
+    .unwrap();
+    let tools = create_mcp_tools(&trusted).await;
+    assert_eq!(tools.get("fail").unwrap().risk(), RiskLevel::ReadOnly);
+}
+
+#[tokio::test]
+async fn test_agent_calls_mcp_tools() {
+    let config = McpConfig {
+        servers: [("echo".to_string(), echo_server())].into(),
+    };
+    let tools = Arc::new(create_mcp_tools(&config).await);
+
+    let deepseek = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_string_contains(r#""role":"tool""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"content": "It said ping", "role": "assistant"}}]
+        })))
+        .with_priority(1)
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_string_contains(r#""name":"echo""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": "",
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "echo", "arguments": "{\"text\": \"ping\"}"}
+                    }]
+                }
+            }]
+        })))
+        .with_priority(2)
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+
+    let model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        deepseek.uri(),
+    ));
+    let run = AgentLoop::new(model, tools)
+        .run(
+            vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Echo ping".to_string(),
+                tool_call_id: None,
+                tool_calls: None,
+            }],
+            &ToolContext::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(run.content, "It said ping");
+    assert_eq!(run.messages.last().unwrap().content, "ping");
+
+    // The server's schema went to the model as is
+    let requests = deepseek.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let echo = body["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|tool| tool["function"]["name"] == "echo")
+        .unwrap();
+    assert_eq!(
+        echo["function"]["parameters"]["properties"]["text"]["type"],
+        "string"
+    );
+}
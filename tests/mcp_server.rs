@@ -0,0 +1,290 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use openagents::server::mcp::protocol::{INVALID_PARAMS, INVALID_REQUEST, PARSE_ERROR};
+use openagents::server::mcp::{McpClient, McpServer, McpServerConfig};
+use openagents::server::tools::{Calculate, RiskLevel, ToolContext, ToolExecutor, ToolRegistry};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+/// A crate with one passing test. Removed when dropped.
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("openagents-mcp-server-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("crate/src")).unwrap();
+        fs::write(
+            dir.join("crate/Cargo.toml"),
+            "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("crate/src/lib.rs"),
+            "pub fn answer() -> u32 {\n    42\n}\n\n#[test]\nfn answers() {\n    assert_eq!(answer(), 42);\n}\n",
+        )
+        .unwrap();
+        Self { dir }
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test]
+async fn test_openagents_mcp_over_stdio() {
+    let fixture = Fixture::new();
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/repo/issues/7"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "number": 7,
+            "title": "Add an MCP server",
+            "body": "Expose the tools",
+            "state": "open",
+            "html_url": "https://github.com/owner/repo/issues/7"
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let server = McpServerConfig {
+        command: env!("CARGO_BIN_EXE_openagents").to_string(),
+        args: vec!["mcp".to_string()],
+        env: [
+            ("GITHUB_TOKEN", "test_token".to_string()),
+            ("GITHUB_API_URL", github.uri()),
+            (
+                "REPO_CACHE_DIR",
+                fixture.dir.join("cache").display().to_string(),
+            ),
+            (
+                "REPO_MAP_REPOS",
+                format!("fixture={}", fixture.dir.join("crate").display()),
+            ),
+            ("RUST_LOG", "off".to_string()),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect(),
+        ..Default::default()
+    };
+    let client = McpClient::spawn_with_timeout("openagents", &server, Duration::from_secs(300))
+        .await
+        .unwrap();
+    assert_eq!(client.server_info()["name"], "openagents");
+
+    let tools = client.list_tools().await.unwrap();
+    let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "read_github_issue",
+            "calculate",
+            "post_github_comment",
+            "list_github_issues",
+            "search_github_issues",
+            "read_pull_request",
+            "list_github_labels",
+            "get_repo_map",
+            "run_cargo_tests"
+        ]
+    );
+    let read_only = |name: &str| {
+        tools
+            .iter()
+            .find(|tool| tool.name == name)
+            .and_then(|tool| tool.annotations.as_ref()?.read_only_hint)
+    };
+    assert_eq!(read_only("calculate"), Some(true));
+    assert_eq!(read_only("post_github_comment"), Some(false));
+    assert_eq!(read_only("run_cargo_tests"), Some(false));
+
+    let issue = client
+        .call_tool(
+            "read_github_issue",
+            json!({"owner": "owner", "repo": "repo", "issue_number": 7}),
+        )
+        .await
+        .unwrap();
+    assert!(!issue.is_error);
+    let issue: Value = serde_json::from_str(&issue.to_text()).unwrap();
+    assert_eq!(issue["title"], "Add an MCP server");
+
+    let map = client
+        .call_tool("get_repo_map", json!({"repository": "fixture"}))
+        .await
+        .unwrap();
+    let map: Value = serde_json::from_str(&map.to_text()).unwrap();
+    assert!(map["map"].as_str().unwrap().contains("answer"));
+
+    let tests = client
+        .call_tool("run_cargo_tests", json!({"repository": "fixture"}))
+        .await
+        .unwrap();
+    let tests: Value = serde_json::from_str(&tests.to_text()).unwrap();
+    assert_eq!(tests["success"], true, "{}", tests);
+    assert!(tests["summary"]
+        .as_str()
+        .unwrap()
+        .starts_with("1 passed, 0 failed"));
+
+    // Repository maps are resources too
+    let resources = client.request("resources/list", json!({})).await.unwrap();
+    let uris: Vec<&str> = resources["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|resource| resource["uri"].as_str().unwrap())
+        .collect();
+    assert_eq!(uris, vec!["repomap://fixture", "repomap://workspace"]);
+    let templates = client
+        .request("resources/templates/list", json!({}))
+        .await
+        .unwrap();
+    assert_eq!(
+        templates["resourceTemplates"][0]["uriTemplate"],
+        "repomap://{owner}/{repo}"
+    );
+    let read = client
+        .request("resources/read", json!({"uri": "repomap://fixture"}))
+        .await
+        .unwrap();
+    assert_eq!(read["contents"][0]["uri"], "repomap://fixture");
+    assert!(read["contents"][0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("src/lib.rs"));
+    let error = client
+        .request("resources/read", json!({"uri": "https://example.com"}))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("Unknown resource"), "{}", error);
+}
+
+/// Waits until cancelled.
+struct Slow;
+
+#[async_trait]
+impl ToolExecutor for Slow {
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    fn description(&self) -> &str {
+        "Takes a long time"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({"type": "object", "properties": {}})
+    }
+
+    fn risk(&self) -> RiskLevel {
+        RiskLevel::SideEffecting
+    }
+
+    async fn execute(&self, _arguments: Value, _context: &ToolContext) -> Result<String> {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        Ok("done".to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_server_runs_requests_concurrently() {
+    let mut tools = ToolRegistry::new();
+    tools.register(Calculate).register(Slow);
+    let server = Arc::new(McpServer::new(tools));
+
+    let (client, server_end) = tokio::io::duplex(64 * 1024);
+    let (server_reader, server_writer) = tokio::io::split(server_end);
+    let serving = tokio::spawn(server.serve(server_reader, server_writer));
+    let (client_reader, mut client_writer) = tokio::io::split(client);
+    let mut responses = BufReader::new(client_reader).lines();
+
+    let mut send = Vec::new();
+    for message in [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "initialize", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "slow"}}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "tools/call",
+               "params": {"name": "calculate", "arguments": {"expression": "6 * 7"}}}),
+        json!({"jsonrpc": "2.0", "id": 5, "method": "tools/call",
+               "params": {"name": "calculate", "arguments": {}}}),
+        json!({"jsonrpc": "2.0", "id": 6, "method": "tools/call", "params": {"name": "missing"}}),
+    ] {
+        send.extend(format!("{}\n", message).into_bytes());
+    }
+    send.extend(b"not json\n");
+    client_writer.write_all(&send).await.unwrap();
+
+    let mut by_id = std::collections::BTreeMap::new();
+    for _ in 0..6 {
+        let line = tokio::time::timeout(Duration::from_secs(5), responses.next_line())
+            .await
+            .expect("a response")
+            .unwrap()
+            .unwrap();
+        let response: Value = serde_json::from_str(&line).unwrap();
+        by_id.insert(response["id"].to_string(), response);
+    }
+
+    assert_eq!(by_id["1"]["error"]["code"], INVALID_REQUEST);
+    assert_eq!(by_id["2"]["result"]["serverInfo"]["name"], "openagents");
+    assert!(by_id["2"]["result"]["capabilities"]
+        .get("resources")
+        .is_none());
+    // Answered while the slow call still runs
+    assert!(!by_id.contains_key("3"));
+    assert_eq!(
+        by_id["4"]["result"],
+        json!({
+            "content": [{"type": "text", "text": r#"{"expression":"6 * 7","result":"42"}"#}],
+            "isError": false
+        })
+    );
+    // Tool failures are results, unknown tools are protocol errors
+    assert_eq!(by_id["5"]["result"]["isError"], true);
+    let failure: Value =
+        serde_json::from_str(by_id["5"]["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        failure["error"],
+        "Invalid arguments for calculate: /expression is required"
+    );
+    assert_eq!(by_id["6"]["error"]["code"], INVALID_PARAMS);
+    assert_eq!(by_id["null"]["error"]["code"], PARSE_ERROR);
+
+    // Cancelled calls get no response, and the connection closes cleanly
+    client_writer
+        .write_all(
+            format!(
+                "{}\n",
+                json!({"jsonrpc": "2.0", "method": "notifications/cancelled",
+                       "params": {"requestId": 3}})
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    client_writer.shutdown().await.unwrap();
+    drop(client_writer);
+    tokio::time::timeout(Duration::from_secs(5), serving)
+        .await
+        .expect("the server stops")
+        .unwrap()
+        .unwrap();
+    assert!(responses.next_line().await.unwrap().is_none());
+}
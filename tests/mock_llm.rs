@@ -0,0 +1,144 @@
+use openagents::server::services::{
+    deepseek::{DeepSeekError, DeepSeekService, GenerationOptions, RetryPolicy, StreamUpdate},
+    mock_llm::{self, Scenario},
+    model_router::ModelRouter,
+};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn serve(scenario: Scenario) -> DeepSeekService {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, mock_llm::router(scenario))
+            .await
+            .unwrap();
+    });
+    DeepSeekService::with_base_url("test_key".to_string(), url)
+        .with_retry_policy(RetryPolicy::none())
+}
+
+fn scenario_file(extension: &str, content: &str) -> Scenario {
+    let path = std::env::temp_dir().join(format!("scenario-{}.{}", Uuid::new_v4(), extension));
+    std::fs::write(&path, content).unwrap();
+    let scenario = Scenario::load(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    scenario
+}
+
+#[tokio::test]
+async fn test_builtin_scenario_routes_issue_requests() {
+    let service = Arc::new(serve(Scenario::builtin()).await);
+    let tool = DeepSeekService::create_tool(
+        "read_github_issue".to_string(),
+        Some("Read a GitHub issue by number".to_string()),
+        json!({"type": "object", "properties": {}}),
+    );
+    let router = ModelRouter::new(service.clone(), service, vec![tool]);
+
+    let (decision, tool_calls, usage) = router
+        .route_message("Can you check issue #42?".to_string())
+        .await
+        .unwrap();
+    assert!(decision.needs_tool);
+    assert!(usage.total_tokens > 0);
+
+    let tool_calls = tool_calls.expect("Should call a tool");
+    assert_eq!(tool_calls[0].function.name, "read_github_issue");
+    let args: serde_json::Value = serde_json::from_str(&tool_calls[0].function.arguments).unwrap();
+    assert_eq!(args["issue_number"], 42);
+
+    let (decision, tool_calls, _) = router.route_message("Hi!".to_string()).await.unwrap();
+    assert!(!decision.needs_tool);
+    assert!(tool_calls.is_none());
+}
+
+#[tokio::test]
+async fn test_streams_reasoning_and_tool_calls() {
+    let scenario = scenario_file(
+        "json",
+        r#"{
+            "rules": [{
+                "match": "weather in (?P<city>\\w+)",
+                "reply": {
+                    "reasoning": "Need the forecast for ${city}",
+                    "tool_calls": [{"name": "get_weather", "arguments": {"units": "metric"}}]
+                }
+            }]
+        }"#,
+    );
+    let service = serve(scenario).await;
+    let tool = DeepSeekService::create_tool(
+        "get_weather".to_string(),
+        None,
+        json!({"type": "object", "properties": {}}),
+    );
+
+    let mut rx = service
+        .chat_stream_with_tools(
+            "What's the weather in Paris?".to_string(),
+            vec![tool],
+            None,
+            GenerationOptions::default(),
+        )
+        .await;
+
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+    let mut usage = None;
+    while let Some(update) = rx.recv().await {
+        match update {
+            StreamUpdate::Reasoning(r) => reasoning.push_str(&r),
+            StreamUpdate::ToolCalls(calls) => tool_calls = calls,
+            StreamUpdate::Usage(u) => usage = Some(u),
+            StreamUpdate::Done => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(reasoning, "Need the forecast for Paris");
+    assert_eq!(tool_calls.len(), 1);
+    assert_eq!(tool_calls[0].function.name, "get_weather");
+    assert_eq!(tool_calls[0].function.arguments, r#"{"units":"metric"}"#);
+    assert!(usage.expect("Usage should be streamed").reasoning_tokens() > 0);
+}
+
+#[tokio::test]
+async fn test_scripted_errors() {
+    let scenario = scenario_file(
+        "yaml",
+        r#"
+rules:
+  - match: "overloaded"
+    reply:
+      error:
+        status: 503
+        message: "Server is busy"
+        retry_after: 7
+default:
+  content: "Fine"
+"#,
+    );
+    let service = serve(scenario).await;
+
+    let error = service
+        .chat("Pretend you are overloaded".to_string(), false)
+        .await
+        .unwrap_err();
+    match error {
+        DeepSeekError::Server {
+            status,
+            retry_after,
+            message,
+        } => {
+            assert_eq!(status, 503);
+            assert_eq!(retry_after, Some(std::time::Duration::from_secs(7)));
+            assert_eq!(message, "Server is busy");
+        }
+        other => panic!("Expected server error, got {:?}", other),
+    }
+
+    let completion = service.chat("Hello".to_string(), false).await.unwrap();
+    assert_eq!(completion.content, "Fine");
+}
@@ -1,8 +1,9 @@
 use dotenvy::dotenv;
-use openagents::server::services::deepseek::{ChatMessage, DeepSeekService, ToolChoice};
+use openagents::server::services::deepseek::{
+    ChatCompletion, ChatMessage, DeepSeekService, ToolChoice,
+};
 use serde_json::json;
 use tracing::{info, Level};
-use tracing_subscriber;
 use wiremock::{
     matchers::{body_string_contains, header, method, path},
     Mock, MockServer, ResponseTemplate,
@@ -127,7 +128,9 @@ Remember: Only respond with a JSON object, do not use any tools, and do not add
             },
         ];
 
-        let (response, _, _) = service
+        let ChatCompletion {
+            content: response, ..
+        } = service
             .chat_with_tools_messages(
                 messages,
                 vec![dummy_tool.clone()],
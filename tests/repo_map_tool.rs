@@ -0,0 +1,267 @@
+use git2::{Repository, Signature};
+use openagents::repo::RepoCache;
+use openagents::server::services::deepseek::{FunctionCallResponse, ToolCallResponse};
+use openagents::server::tools::{error_content, GetRepoMap, ToolContext, ToolRegistry};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A committed repository to clone, and a cache directory. Removed when dropped.
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("openagents-repo-map-{}", Uuid::new_v4()));
+        let origin = dir.join("origin");
+        fs::create_dir_all(origin.join("src/server")).unwrap();
+        fs::write(
+            origin.join("src/lib.rs"),
+            "pub trait Agent {}\n\npub fn start() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            origin.join("src/server/mod.rs"),
+            "pub fn serve() {}\n\npub fn shutdown() {}\n",
+        )
+        .unwrap();
+        fs::write(origin.join("README.md"), "# Origin\n").unwrap();
+        commit_all(&origin);
+        Self { dir }
+    }
+
+    fn origin(&self) -> PathBuf {
+        self.dir.join("origin")
+    }
+
+    fn url(&self) -> String {
+        format!("file://{}", self.origin().display())
+    }
+
+    fn cache(&self) -> RepoCache {
+        RepoCache::new(self.dir.join("cache")).allow_file_urls()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn commit_all(path: &Path) {
+    let repo = Repository::init(path).unwrap();
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+        .unwrap();
+}
+
+async fn call(tools: &ToolRegistry, arguments: Value) -> Value {
+    let call = ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: "get_repo_map".to_string(),
+            arguments: arguments.to_string(),
+        },
+    };
+    let content = match tools.execute(&call, &ToolContext::default()).await {
+        Ok(content) => content,
+        Err(e) => error_content(&e),
+    };
+    serde_json::from_str(&content).unwrap()
+}
+
+#[test]
+fn test_clone_urls() {
+    let cache = RepoCache::new(std::env::temp_dir());
+    assert_eq!(
+        cache.clone_url("OpenAgentsInc/openagents").unwrap(),
+        "https://github.com/OpenAgentsInc/openagents"
+    );
+    assert_eq!(
+        cache
+            .clone_url("https://github.com/OpenAgentsInc/openagents.git")
+            .unwrap(),
+        "https://github.com/OpenAgentsInc/openagents.git"
+    );
+    for repository in [
+        "file:///etc",
+        "/etc",
+        "../openagents",
+        "http://example.com/repo.git",
+        "git@github.com:OpenAgentsInc/openagents.git",
+        "OpenAgentsInc/openagents/extra",
+        "OpenAgentsInc/..",
+        "https://gitlab.com/OpenAgentsInc/openagents.git",
+        "https://169.254.169.254/latest",
+        "https://github.com@example.com/repo.git",
+    ] {
+        assert!(
+            cache.clone_url(repository).is_err(),
+            "{} accepted",
+            repository
+        );
+    }
+
+    let cache = cache.with_clone_host("GitHub.Example.com");
+    assert_eq!(
+        cache
+            .clone_url("https://github.example.com/team/repo.git")
+            .unwrap(),
+        "https://github.example.com/team/repo.git"
+    );
+}
+
+#[tokio::test]
+async fn test_clones_are_cached() {
+    let fixture = Fixture::new();
+    let cache = fixture.cache();
+
+    let first = cache.checkout(&fixture.url()).await.unwrap();
+    assert!(first.join("src/lib.rs").is_file());
+    assert!(first.starts_with(fixture.dir.join("cache")));
+    fs::write(first.join("marker"), "").unwrap();
+
+    let second = cache.checkout(&fixture.url()).await.unwrap();
+    assert_eq!(second, first);
+    assert!(second.join("marker").exists(), "Should reuse the clone");
+
+    // Past the TTL the clone is replaced
+    let cache = fixture.cache().with_ttl(Duration::ZERO);
+    let fresh = cache.checkout(&fixture.url()).await.unwrap();
+    assert_eq!(fresh, first);
+    assert!(!fresh.join("marker").exists());
+
+    // A failed clone leaves nothing behind
+    let missing = format!("file://{}", fixture.dir.join("missing").display());
+    assert!(cache.checkout(&missing).await.is_err());
+    assert_eq!(
+        fs::read_dir(fixture.dir.join("cache")).unwrap().count(),
+        1,
+        "Only the good clone should be cached"
+    );
+
+    // Nor does one given up on, once it finishes in the background
+    let cache = fixture.cache().with_ttl(Duration::ZERO);
+    let cancelled = tokio::time::timeout(Duration::ZERO, cache.checkout(&fixture.url())).await;
+    assert!(cancelled.is_err());
+    for _ in 0..100 {
+        if fs::read_dir(fixture.dir.join("cache")).unwrap().count() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(fs::read_dir(fixture.dir.join("cache")).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_least_recently_used_clones_are_removed() {
+    let fixture = Fixture::new();
+    let other = fixture.dir.join("other");
+    fs::create_dir_all(&other).unwrap();
+    fs::write(other.join("lib.rs"), "pub fn other() {}\n").unwrap();
+    commit_all(&other);
+    let other_url = format!("file://{}", other.display());
+
+    let cache = fixture.cache().with_max_clones(1);
+    let first = cache.checkout(&fixture.url()).await.unwrap();
+    let second = cache.checkout(&other_url).await.unwrap();
+    assert!(!first.exists());
+    assert!(second.join("lib.rs").is_file());
+
+    // Checked out again, the first one is cloned afresh and the other goes
+    let first = cache.checkout(&fixture.url()).await.unwrap();
+    assert!(first.join("src/lib.rs").is_file());
+    assert!(!second.exists());
+}
+
+#[tokio::test]
+async fn test_get_repo_map() {
+    let fixture = Fixture::new();
+    let mut tools = ToolRegistry::new();
+    tools.register(GetRepoMap::new(Arc::new(
+        fixture.cache().with_local_repo("origin", fixture.origin()),
+    )));
+    assert!(tools
+        .get("get_repo_map")
+        .unwrap()
+        .description()
+        .ends_with("or one of: origin."));
+
+    let full = call(&tools, json!({"repository": fixture.url()})).await;
+    assert_eq!(
+        full,
+        json!({
+            "repository": fixture.url(),
+            "map": "src/lib.rs:\n│trait Agent\n│fn start\n\nsrc/server/mod.rs:\n│fn serve\n│fn shutdown\n\n",
+            "files": 2,
+            "omitted_files": 0,
+            "truncated": false
+        })
+    );
+
+    let filtered = call(
+        &tools,
+        json!({"repository": "origin", "paths": ["src/server"]}),
+    )
+    .await;
+    assert_eq!(
+        filtered["map"],
+        "src/server/mod.rs:\n│fn serve\n│fn shutdown\n\n"
+    );
+
+    // Whole files only
+    let budgeted = call(&tools, json!({"repository": "origin", "max_chars": 1000})).await;
+    assert_eq!(budgeted["files"], 2);
+    let mut tools = ToolRegistry::new();
+    let big = fixture.dir.join("big");
+    fs::create_dir_all(big.join("src")).unwrap();
+    for i in 0..40 {
+        fs::write(
+            big.join(format!("src/module_{:02}.rs", i)),
+            "pub fn a_function_with_a_fairly_long_name() {}\n",
+        )
+        .unwrap();
+    }
+    tools.register(GetRepoMap::new(Arc::new(
+        fixture.cache().with_local_repo("big", &big),
+    )));
+    let budgeted = call(&tools, json!({"repository": "big", "max_chars": 1000})).await;
+    let map = budgeted["map"].as_str().unwrap();
+    assert!(map.len() <= 1000);
+    assert!(map.ends_with("│fn a_function_with_a_fairly_long_name\n\n"));
+    assert_eq!(
+        budgeted["files"].as_u64().unwrap() + budgeted["omitted_files"].as_u64().unwrap(),
+        40
+    );
+    assert_eq!(budgeted["truncated"], true);
+
+    // Symlinks aren't followed, out of the repository or round in a loop
+    std::os::unix::fs::symlink(fixture.origin().join("src"), big.join("src/outside")).unwrap();
+    std::os::unix::fs::symlink(&big, big.join("src/loop")).unwrap();
+    let linked = call(&tools, json!({"repository": "big", "max_chars": 40000})).await;
+    assert_eq!(linked["files"], 40);
+    let map = linked["map"].as_str().unwrap();
+    assert!(!map.contains("src/outside/"));
+    assert!(!map.contains("src/loop/"));
+
+    assert_eq!(
+        call(&tools, json!({"repository": "big", "paths": ["../origin"]})).await,
+        json!({"error": "../origin is outside the workspace", "kind": "outside_workspace"})
+    );
+    assert!(call(&tools, json!({"repository": "/etc"})).await["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Unsupported repository"));
+}
@@ -45,7 +45,7 @@ fn create_test_service(mock_server: &MockServer, pool: PgPool) -> OIDCService {
 fn create_test_token(sub: &str) -> String {
     // Create a simple JWT token for testing
     let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
-    let claims = URL_SAFE_NO_PAD.encode(&format!(r#"{{"sub":"{}","iat":1516239022}}"#, sub));
+    let claims = URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"{}","iat":1516239022}}"#, sub));
     let signature = URL_SAFE_NO_PAD.encode("test_signature");
     format!("{}.{}.{}", header, claims, signature)
 }
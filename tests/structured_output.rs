@@ -0,0 +1,138 @@
+use openagents::server::services::deepseek::{ChatMessage, DeepSeekService, GenerationOptions};
+use openagents::server::services::structured::{extract_json, ChatJson, JsonOutputError};
+use serde::Deserialize;
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct FileRequest {
+    path: String,
+}
+
+fn completion(content: &str, total_tokens: u32) -> serde_json::Value {
+    json!({
+        "choices": [{
+            "message": {
+                "content": content,
+                "role": "assistant"
+            }
+        }],
+        "usage": {
+            "prompt_tokens": total_tokens - 5,
+            "completion_tokens": 5,
+            "total_tokens": total_tokens
+        }
+    })
+}
+
+fn prompt() -> Vec<ChatMessage> {
+    vec![ChatMessage {
+        role: "user".to_string(),
+        content: "Respond with a JSON object with a 'path' field.".to_string(),
+        tool_call_id: None,
+        tool_calls: None,
+    }]
+}
+
+#[tokio::test]
+async fn test_chat_json_strips_code_fences() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({
+            "response_format": {"type": "json_object"}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion(
+            "Here you go:\n```json\n{\"path\": \"src/main.rs\"}\n```",
+            20,
+        )))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let result = service
+        .chat_json::<FileRequest>(prompt(), GenerationOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(result.value.path, "src/main.rs");
+    assert_eq!(result.usage.total_tokens, 20);
+}
+
+#[tokio::test]
+async fn test_chat_json_repairs_invalid_output() {
+    let mock_server = MockServer::start().await;
+
+    // First reply is missing the required field
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(completion("{\"file\": \"src/lib.rs\"}", 20)),
+        )
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // The repair request carries the bad reply and the parse error
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_string_contains("missing field `path`"))
+        .and(body_string_contains("src/lib.rs"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(completion("{\"path\": \"src/lib.rs\"}", 30)),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let result = service
+        .chat_json::<FileRequest>(prompt(), GenerationOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(result.value.path, "src/lib.rs");
+    assert_eq!(result.usage.total_tokens, 50);
+}
+
+#[tokio::test]
+async fn test_chat_json_gives_up_after_repairs() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(completion("Sorry, no.", 10)))
+        .expect(3)
+        .mount(&mock_server)
+        .await;
+
+    let service = DeepSeekService::with_base_url("test_key".to_string(), mock_server.uri());
+    let error = service
+        .chat_json::<FileRequest>(prompt(), GenerationOptions::default())
+        .await
+        .unwrap_err()
+        .downcast::<JsonOutputError>()
+        .expect("Should fail with JsonOutputError");
+
+    assert_eq!(error.attempts, 3);
+    assert_eq!(error.content, "Sorry, no.");
+    assert_eq!(error.usage.total_tokens, 30);
+}
+
+#[test]
+fn test_extract_json() {
+    assert_eq!(extract_json("  {\"a\": 1}\n"), "{\"a\": 1}");
+    assert_eq!(extract_json("```json\n{\"a\": 1}\n```"), "{\"a\": 1}");
+    assert_eq!(extract_json("```\n[1, 2]\n```"), "[1, 2]");
+    assert_eq!(
+        extract_json("The file is {\"path\": \"a.rs\"}. Hope that helps!"),
+        "{\"path\": \"a.rs\"}"
+    );
+    assert_eq!(extract_json("no json here"), "no json here");
+}
@@ -0,0 +1,163 @@
+use openagents::repo::{run_cargo_tests, run_cargo_tests_with_timeout, TestStatus};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A throwaway crate. Removed when dropped.
+struct Crate {
+    dir: PathBuf,
+}
+
+impl Crate {
+    fn new(lib: &str, integration_test: Option<&str>) -> Self {
+        let dir = std::env::temp_dir().join(format!("openagents-test-runner-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )
+        .unwrap();
+        fs::write(dir.join("src/lib.rs"), lib).unwrap();
+        if let Some(test) = integration_test {
+            fs::create_dir_all(dir.join("tests")).unwrap();
+            fs::write(dir.join("tests/api.rs"), test).unwrap();
+        }
+        Self { dir }
+    }
+
+    fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Crate {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test]
+async fn test_report_has_each_test() {
+    let fixture = Crate::new(
+        r#"
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn adds() {
+        assert_eq!(super::add(1, 2), 3);
+    }
+
+    #[test]
+    fn adds_wrong() {
+        assert_eq!(super::add(1, 2), 4, "bad sum");
+    }
+
+    #[test]
+    #[ignore]
+    fn slow() {}
+}
+"#,
+        Some(
+            r#"
+#[test]
+fn from_outside() {
+    // Stderr noise must not get in the way
+    for _ in 0..10_000 {
+        eprintln!("a line of stderr output that would fill the pipe");
+    }
+    assert_eq!(fixture::add(2, 2), 4);
+}
+"#,
+        ),
+    );
+
+    let report = run_cargo_tests(fixture.path()).await.unwrap();
+    assert!(!report.success());
+    assert!(!report.timed_out);
+    assert_eq!(report.exit_code, Some(101));
+    assert!(report.compile_errors().next().is_none());
+    assert_eq!(report.count(TestStatus::Passed), 2);
+    assert_eq!(report.count(TestStatus::Failed), 1);
+    assert_eq!(report.count(TestStatus::Ignored), 1);
+
+    let outside = report
+        .tests
+        .iter()
+        .find(|test| test.name == "from_outside")
+        .unwrap();
+    assert_eq!(outside.target, "api");
+    assert_eq!(outside.status, TestStatus::Passed);
+    assert!(outside.duration.is_some());
+
+    let failures: Vec<_> = report.failures().collect();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].name, "tests::adds_wrong");
+    assert_eq!(failures[0].target, "fixture");
+    let message = failures[0].failure.as_deref().unwrap();
+    assert!(message.contains("bad sum"), "{}", message);
+
+    // Prompts only get the failures
+    let prompt = report.for_prompt();
+    assert!(prompt.starts_with("2 passed, 1 failed, 1 ignored in "));
+    assert!(prompt.contains("failed tests::adds_wrong (fixture)"));
+    assert!(prompt.contains("bad sum"));
+    assert!(!prompt.contains("from_outside"));
+}
+
+#[tokio::test]
+async fn test_compile_errors_are_diagnostics() {
+    let fixture = Crate::new("pub fn broken() -> i32 {\n    \"not a number\"\n}\n", None);
+
+    let report = run_cargo_tests(fixture.path()).await.unwrap();
+    assert!(!report.success());
+    assert!(report.tests.is_empty());
+    assert_ne!(report.exit_code, Some(0));
+    let errors: Vec<_> = report.compile_errors().collect();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].rendered.contains("mismatched types"));
+    assert!(errors[0].rendered.contains("src/lib.rs"));
+    assert!(report
+        .for_prompt()
+        .starts_with("0 passed, 0 failed, 0 ignored, 1 compile error(s)"));
+}
+
+#[tokio::test]
+async fn test_hanging_tests_time_out() {
+    let fixture = Crate::new(
+        r#"
+#[test]
+fn finishes() {}
+
+#[test]
+fn hangs() {
+    std::thread::sleep(std::time::Duration::from_secs(300));
+}
+"#,
+        None,
+    );
+    // Build first so the timeout only has to cover the run
+    let built = Command::new("cargo")
+        .args(["test", "--no-run", "--quiet"])
+        .current_dir(fixture.path())
+        .status()
+        .unwrap();
+    assert!(built.success());
+
+    let report = run_cargo_tests_with_timeout(fixture.path(), Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert!(report.timed_out);
+    assert!(!report.success());
+    assert!(report.duration < Duration::from_secs(30));
+    assert_eq!(report.count(TestStatus::Passed), 1);
+    let failures: Vec<_> = report.failures().collect();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].name, "hangs");
+    assert_eq!(failures[0].status, TestStatus::TimedOut);
+}
@@ -0,0 +1,334 @@
+use async_trait::async_trait;
+use axum::extract::ws::Message;
+use openagents::server::services::{
+    deepseek::{DeepSeekService, FunctionCallResponse, ToolCallResponse},
+    github_issue::GitHubService,
+};
+use openagents::server::tools::{
+    create_tools, error_content, Approval, ApprovalRequest, CallNotApproved, RiskLevel,
+    ToolApprover, ToolContext,
+};
+use openagents::server::ws::handlers::{chat::ChatHandler, MessageHandler};
+use openagents::server::ws::{transport::WebSocketState, types::ChatMessage};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use wiremock::{
+    matchers::{body_json, body_partial_json, body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+const COMMENT_ARGUMENTS: &str = r#"{"owner": "OpenAgentsInc", "repo": "openagents", "issue_number": 12, "body": "Fixed in #13"}"#;
+
+/// Gives the same answer to every request and remembers what was asked.
+struct FixedApprover {
+    approval: Approval,
+    requests: Mutex<Vec<ApprovalRequest>>,
+}
+
+#[async_trait]
+impl ToolApprover for FixedApprover {
+    async fn approve(&self, request: &ApprovalRequest) -> Approval {
+        self.requests.lock().unwrap().push(request.clone());
+        self.approval.clone()
+    }
+}
+
+fn comment_call() -> ToolCallResponse {
+    ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: "post_github_comment".to_string(),
+            arguments: COMMENT_ARGUMENTS.to_string(),
+        },
+    }
+}
+
+async fn mock_github(expected_comments: u64) -> MockServer {
+    let github = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/repos/OpenAgentsInc/openagents/issues/12/comments"))
+        .and(body_json(json!({"body": "Fixed in #13"})))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": 1})))
+        .expect(expected_comments)
+        .mount(&github)
+        .await;
+    github
+}
+
+fn github_service(github: &MockServer) -> Arc<GitHubService> {
+    Arc::new(GitHubService::with_base_url(
+        "test_token".to_string(),
+        github.uri(),
+    ))
+}
+
+#[tokio::test]
+async fn test_side_effecting_calls_need_approval() {
+    let github = mock_github(1).await;
+    let registry = create_tools(github_service(&github));
+    let comment_tool = registry.get("post_github_comment").unwrap();
+    assert_eq!(comment_tool.risk(), RiskLevel::SideEffecting);
+    assert_eq!(
+        registry.get("read_github_issue").unwrap().risk(),
+        RiskLevel::ReadOnly
+    );
+
+    // Nobody to ask
+    let error = registry
+        .execute(&comment_call(), &ToolContext::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The user denied the post_github_comment call: nobody is available to approve it"
+    );
+
+    let denier = Arc::new(FixedApprover {
+        approval: Approval::Denied {
+            reason: Some("wrong issue".to_string()),
+        },
+        requests: Mutex::new(Vec::new()),
+    });
+    let context = ToolContext {
+        approver: Some(denier.clone()),
+        ..Default::default()
+    };
+    let error = registry
+        .execute(&comment_call(), &context)
+        .await
+        .unwrap_err();
+    assert!(error.downcast_ref::<CallNotApproved>().is_some());
+    assert_eq!(
+        serde_json::from_str::<Value>(&error_content(&error)).unwrap(),
+        json!({
+            "error": "The user denied the post_github_comment call: wrong issue",
+            "kind": "denied"
+        })
+    );
+    let request = denier.requests.lock().unwrap()[0].clone();
+    assert_eq!(
+        request.description,
+        "Posting a comment on OpenAgentsInc/openagents#12"
+    );
+    assert_eq!(request.arguments["body"], "Fixed in #13");
+
+    let approver = Arc::new(FixedApprover {
+        approval: Approval::Approved,
+        requests: Mutex::new(Vec::new()),
+    });
+    let context = ToolContext {
+        approver: Some(approver.clone()),
+        ..Default::default()
+    };
+    let posted: Value =
+        serde_json::from_str(&registry.execute(&comment_call(), &context).await.unwrap()).unwrap();
+    assert_eq!(
+        posted,
+        json!({"posted": true, "issue": "OpenAgentsInc/openagents#12"})
+    );
+
+    // Read-only tools never ask
+    registry
+        .execute(
+            &ToolCallResponse {
+                function: FunctionCallResponse {
+                    name: "calculate".to_string(),
+                    arguments: r#"{"expression": "1 + 1"}"#.to_string(),
+                },
+                ..comment_call()
+            },
+            &context,
+        )
+        .await
+        .unwrap();
+    assert_eq!(approver.requests.lock().unwrap().len(), 1);
+}
+
+/// Sets up a chat whose model asks to post a comment, then answers with
+/// whatever tool result it got back.
+async fn mock_deepseek() -> MockServer {
+    let deepseek = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_string_contains(r#""role":"tool""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"content": "Done", "role": "assistant"}}]
+        })))
+        .with_priority(1)
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "tool_choice": "auto" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": "",
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "post_github_comment", "arguments": COMMENT_ARGUMENTS}
+                    }]
+                }
+            }]
+        })))
+        .with_priority(2)
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": json!({
+                        "needs_tool": true,
+                        "reasoning": "User wants to comment on an issue",
+                        "suggested_tool": "post_github_comment"
+                    }).to_string(),
+                    "role": "assistant"
+                }
+            }]
+        })))
+        .expect(1)
+        .mount(&deepseek)
+        .await;
+    deepseek
+}
+
+async fn next_message(rx: &mut mpsc::UnboundedReceiver<Message>, kind: &str) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for a message")
+            .expect("connection closed");
+        if let Message::Text(text) = message {
+            let message: Value = serde_json::from_str(&text).unwrap();
+            if message["type"] == kind {
+                return message;
+            }
+        }
+    }
+}
+
+/// Runs the chat with `answer` given to the approval request and returns the
+/// tool result the model got back.
+async fn chat_with_answer(answer: Option<Approval>, expected_comments: u64) -> Value {
+    let github = mock_github(expected_comments).await;
+    let deepseek = mock_deepseek().await;
+    let model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        deepseek.uri(),
+    ));
+    let ws_state = WebSocketState::new(model.clone(), model, create_tools(github_service(&github)));
+    let mut rx = ws_state.add_test_connection("test_conn", 1).await;
+    let chat_handler = Arc::new(
+        ChatHandler::new(ws_state.clone()).with_approval_timeout(Duration::from_millis(200)),
+    );
+
+    let handler = chat_handler.clone();
+    let chat = tokio::spawn(async move {
+        handler
+            .handle_message(
+                ChatMessage::UserMessage {
+                    content: "Comment on issue 12 that it was fixed in #13".to_string(),
+                },
+                "test_conn".to_string(),
+            )
+            .await
+            .unwrap();
+    });
+
+    let request = next_message(&mut rx, "approval_request").await;
+    assert_eq!(request["tool"], "post_github_comment");
+    assert_eq!(request["risk"], "side_effecting");
+    assert_eq!(
+        request["description"],
+        "Posting a comment on OpenAgentsInc/openagents#12"
+    );
+    assert_eq!(request["arguments"]["issue_number"], 12);
+    let id = request["id"].as_str().unwrap();
+
+    match answer {
+        Some(approval) => {
+            // Only the connection that was asked can answer
+            assert!(
+                !chat_handler
+                    .resolve_approval("other_conn", id, Approval::Approved)
+                    .await
+            );
+            assert!(
+                chat_handler
+                    .resolve_approval("test_conn", id, approval)
+                    .await
+            );
+        }
+        None => {
+            let timeout = next_message(&mut rx, "approval_timeout").await;
+            assert_eq!(timeout["id"], id);
+            assert!(
+                !chat_handler
+                    .resolve_approval("test_conn", id, Approval::Approved)
+                    .await
+            );
+        }
+    }
+
+    chat.await.unwrap();
+    let mut last = Value::Null;
+    while let Ok(Message::Text(text)) = rx.try_recv() {
+        last = serde_json::from_str(&text).unwrap();
+    }
+    assert_eq!(last["status"], "complete");
+    assert_eq!(last["content"], "Done");
+
+    let requests = deepseek.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let tool_message = body["messages"].as_array().unwrap().last().unwrap().clone();
+    assert_eq!(tool_message["tool_call_id"], "call_1");
+    serde_json::from_str(tool_message["content"].as_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_approved_call_runs() {
+    let result = chat_with_answer(Some(Approval::Approved), 1).await;
+    assert_eq!(
+        result,
+        json!({"posted": true, "issue": "OpenAgentsInc/openagents#12"})
+    );
+}
+
+#[tokio::test]
+async fn test_denied_call_goes_back_to_the_model() {
+    let result = chat_with_answer(
+        Some(Approval::Denied {
+            reason: Some("not yet".to_string()),
+        }),
+        0,
+    )
+    .await;
+    assert_eq!(
+        result,
+        json!({
+            "error": "The user denied the post_github_comment call: not yet",
+            "kind": "denied"
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_unanswered_approval_times_out() {
+    let result = chat_with_answer(None, 0).await;
+    assert_eq!(
+        result,
+        json!({
+            "error": "The user did not approve the post_github_comment call in time",
+            "kind": "approval_timeout"
+        })
+    );
+}
@@ -0,0 +1,260 @@
+use openagents::server::services::{
+    agent::AgentLoop,
+    deepseek::{ChatMessage, DeepSeekService, FunctionCallResponse, ToolCallResponse},
+    github_issue::GitHubService,
+};
+use openagents::server::tools::{
+    create_tools, error_content, schema::validate, InvalidArguments, ReadGitHubIssue,
+    SchemaViolation, ToolContext, ToolRegistry,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn tool_call(name: &str, arguments: &str) -> ToolCallResponse {
+    ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+fn messages(violations: &[SchemaViolation]) -> Vec<String> {
+    violations
+        .iter()
+        .map(|violation| format!("{} {}", violation.path, violation.message))
+        .collect()
+}
+
+#[test]
+fn test_schema_validation() {
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "owner": {"type": "string", "minLength": 1},
+            "state": {"type": "string", "enum": ["open", "closed"]},
+            "per_page": {"type": "integer", "minimum": 1, "maximum": 100},
+            "ratio": {"type": "number", "exclusiveMaximum": 1},
+            "labels": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+            "filter": {
+                "type": "object",
+                "properties": {"draft": {"type": "boolean"}},
+                "required": ["draft"],
+                "additionalProperties": false
+            }
+        },
+        "required": ["owner"]
+    });
+
+    let valid = json!({
+        "owner": "OpenAgentsInc",
+        "state": "open",
+        "per_page": 100,
+        "ratio": 0.5,
+        "labels": ["bug"],
+        "filter": {"draft": false},
+        "unlisted": "allowed at the top level"
+    });
+    assert!(validate(&schema, &valid).is_empty());
+    // Optional fields may be null
+    assert!(validate(&schema, &json!({"owner": "me", "state": null})).is_empty());
+
+    let invalid = json!({
+        "state": "merged",
+        "per_page": 0,
+        "ratio": 1,
+        "labels": ["bug", 7, "docs"],
+        "filter": {"drafts": true}
+    });
+    assert_eq!(
+        messages(&validate(&schema, &invalid)),
+        vec![
+            "/owner is required",
+            "/filter/draft is required",
+            "/filter/drafts is not a known argument",
+            "/labels must have at most 2 items",
+            "/labels/1 must be string but got integer",
+            "/per_page must be at least 1",
+            "/ratio must be less than 1",
+            "/state must be one of \"open\", \"closed\"",
+        ]
+    );
+
+    assert_eq!(
+        messages(&validate(&schema, &json!({"owner": "", "per_page": "10"}))),
+        vec![
+            "/owner must be at least 1 characters long",
+            "/per_page must be integer but got string",
+        ]
+    );
+    assert_eq!(
+        messages(&validate(&schema, &json!({"owner": "me", "per_page": 2.5}))),
+        vec!["/per_page must be integer but got number"]
+    );
+    assert_eq!(
+        messages(&validate(&schema, &json!(["owner"]))),
+        vec![" must be object but got array"]
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_arguments_never_reach_the_tool() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&github)
+        .await;
+    let github_service = Arc::new(GitHubService::with_base_url(
+        "test_token".to_string(),
+        github.uri(),
+    ));
+    let registry = create_tools(github_service);
+    let context = ToolContext::default();
+
+    for (arguments, expected) in [
+        (
+            r#"{"owner": "OpenAgentsInc", "repo": "openagents"}"#,
+            "Invalid arguments for read_github_issue: /issue_number is required",
+        ),
+        (
+            r#"{"owner": "OpenAgentsInc", "repo": "openagents", "issue_number": 0}"#,
+            "Invalid arguments for read_github_issue: /issue_number must be at least 1",
+        ),
+        (
+            r#"{"owner": 42, "repo": "openagents", "issue_number": "12"}"#,
+            "Invalid arguments for read_github_issue: /issue_number must be integer but got string; /owner must be string but got integer",
+        ),
+    ] {
+        let error = registry
+            .execute(&tool_call("read_github_issue", arguments), &context)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), expected);
+        assert!(error.downcast_ref::<InvalidArguments>().is_some());
+    }
+
+    let call = tool_call("read_github_issue", r#"{"owner": "OpenAgentsInc""#);
+    assert_eq!(registry.status(&call), "Fetching GitHub issue");
+    let error = registry.execute(&call, &context).await.unwrap_err();
+    let content: Value = serde_json::from_str(&error_content(&error)).unwrap();
+    assert_eq!(content["kind"], "invalid_arguments");
+    assert_eq!(content["violations"][0]["path"], "");
+    assert!(content["violations"][0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("are not valid JSON"));
+
+    let error = registry
+        .execute(
+            &tool_call("calculate", r#"{"expression": ["1 + 1"]}"#),
+            &context,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        serde_json::from_str::<Value>(&error_content(&error)).unwrap(),
+        json!({
+            "error": "Invalid arguments for calculate: /expression must be string but got array",
+            "kind": "invalid_arguments",
+            "violations": [{"path": "/expression", "message": "must be string but got array"}]
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_model_corrects_rejected_call() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/OpenAgentsInc/openagents/issues/12"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "number": 12,
+            "title": "Validate tool arguments",
+            "body": null,
+            "state": "open",
+            "html_url": "https://github.com/OpenAgentsInc/openagents/issues/12"
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let deepseek = MockServer::start().await;
+    for (priority, arguments) in [
+        (1, json!({"owner": "OpenAgentsInc", "repo": "openagents"})),
+        (
+            2,
+            json!({"owner": "OpenAgentsInc", "repo": "openagents", "issue_number": 12}),
+        ),
+    ] {
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "choices": [{
+                    "message": {
+                        "content": "",
+                        "role": "assistant",
+                        "tool_calls": [{
+                            "id": format!("call_{}", priority),
+                            "type": "function",
+                            "function": {"name": "read_github_issue", "arguments": arguments.to_string()}
+                        }]
+                    }
+                }]
+            })))
+            .up_to_n_times(1)
+            .with_priority(priority)
+            .mount(&deepseek)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"content": "Issue #12 is about validation", "role": "assistant"}}]
+        })))
+        .with_priority(3)
+        .mount(&deepseek)
+        .await;
+
+    let github_service = Arc::new(GitHubService::with_base_url(
+        "test_token".to_string(),
+        github.uri(),
+    ));
+    let mut tools = ToolRegistry::new();
+    tools.register(ReadGitHubIssue::new(github_service));
+    let model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        deepseek.uri(),
+    ));
+    let agent = AgentLoop::new(model, Arc::new(tools));
+
+    let run = agent
+        .run(
+            vec![ChatMessage {
+                role: "user".to_string(),
+                content: "What is issue 12 about?".to_string(),
+                tool_call_id: None,
+                tool_calls: None,
+            }],
+            &ToolContext::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(run.content, "Issue #12 is about validation");
+    assert_eq!(run.steps, 3);
+
+    // The rejected call was answered with what to fix
+    let rejection: Value = serde_json::from_str(&run.messages[2].content).unwrap();
+    assert_eq!(rejection["kind"], "invalid_arguments");
+    assert_eq!(
+        rejection["violations"],
+        json!([{"path": "/issue_number", "message": "is required"}])
+    );
+    let issue: Value = serde_json::from_str(&run.messages[4].content).unwrap();
+    assert_eq!(issue["number"], 12);
+}
@@ -0,0 +1,362 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::extract::ws::Message;
+use openagents::server::services::{
+    deepseek::{DeepSeekService, FunctionCallResponse, ToolCallResponse},
+    github_issue::GitHubService,
+};
+use openagents::server::tools::{
+    create_tools, error_content, ReadGitHubIssue, ToolContext, ToolExecutor, ToolLimits,
+    ToolRegistry, ToolTimedOut,
+};
+use openagents::server::ws::handlers::{chat::ChatHandler, MessageHandler};
+use openagents::server::ws::{transport::WebSocketState, types::ChatMessage};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use wiremock::{
+    matchers::{body_partial_json, body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+struct Echo;
+
+#[async_trait]
+impl ToolExecutor for Echo {
+    fn name(&self) -> &str {
+        "echo"
+    }
+
+    fn description(&self) -> &str {
+        "Repeat the given text"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+            "required": ["text"]
+        })
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        Ok(arguments["text"].as_str().unwrap_or("nothing").to_string())
+    }
+}
+
+/// Sleeps for `millis`, within its own timeout when it has one.
+struct Sleep {
+    timeout: Option<Duration>,
+}
+
+#[async_trait]
+impl ToolExecutor for Sleep {
+    fn name(&self) -> &str {
+        if self.timeout.is_some() {
+            "patient_sleep"
+        } else {
+            "sleep"
+        }
+    }
+
+    fn description(&self) -> &str {
+        "Sleep for a while"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "millis": { "type": "integer" } },
+            "required": ["millis"]
+        })
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    async fn execute(&self, arguments: Value, _context: &ToolContext) -> Result<String> {
+        let millis = arguments["millis"].as_u64().unwrap_or_default();
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        Ok("awake".to_string())
+    }
+}
+
+fn tool_call(name: &str, arguments: &str) -> ToolCallResponse {
+    ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_registry_dispatches_by_name() {
+    let github = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/OpenAgentsInc/openagents/issues/12"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "number": 12,
+            "title": "Add a tool registry",
+            "body": null,
+            "state": "open",
+            "html_url": "https://github.com/OpenAgentsInc/openagents/issues/12"
+        })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let github_service = Arc::new(GitHubService::with_base_url(
+        "test_token".to_string(),
+        github.uri(),
+    ));
+    let mut registry = ToolRegistry::new();
+    registry
+        .register(ReadGitHubIssue::new(github_service))
+        .register(Echo);
+
+    assert_eq!(registry.names(), vec!["read_github_issue", "echo"]);
+    let definitions = registry.definitions();
+    assert_eq!(definitions[1].function.name, "echo");
+    assert_eq!(
+        definitions[1].function.parameters["required"],
+        json!(["text"])
+    );
+
+    let context = ToolContext::default();
+    let echoed = registry
+        .execute(&tool_call("echo", r#"{"text": "hi"}"#), &context)
+        .await
+        .unwrap();
+    assert_eq!(echoed, "hi");
+
+    // Calls without arguments get an empty object, which lacks the required text
+    let missing = registry
+        .execute(&tool_call("echo", ""), &context)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        missing.to_string(),
+        "Invalid arguments for echo: /text is required"
+    );
+
+    let call = tool_call(
+        "read_github_issue",
+        r#"{"owner": "OpenAgentsInc", "repo": "openagents", "issue_number": 12}"#,
+    );
+    assert_eq!(
+        registry.status(&call),
+        "Fetching GitHub issue #12 from OpenAgentsInc/openagents"
+    );
+    let message = registry.tool_message(&call, &context).await.unwrap();
+    assert_eq!(message.role, "tool");
+    assert_eq!(message.tool_call_id.as_deref(), Some("call_1"));
+    let issue: Value = serde_json::from_str(&message.content).unwrap();
+    assert_eq!(issue["title"], "Add a tool registry");
+
+    let unknown = registry
+        .execute(&tool_call("get_github_issue", "{}"), &context)
+        .await
+        .unwrap_err();
+    assert_eq!(unknown.to_string(), "Unknown tool: get_github_issue");
+    assert!(registry
+        .execute(&tool_call("echo", "{not json"), &context)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_calls_are_bounded_by_the_limits() {
+    let mut registry = ToolRegistry::new();
+    assert_eq!(registry.limits(), ToolLimits::default());
+    registry
+        .set_limits(ToolLimits {
+            timeout: Duration::from_millis(100),
+            max_output_chars: 20,
+        })
+        .register(Echo)
+        .register(Sleep { timeout: None })
+        .register(Sleep {
+            timeout: Some(Duration::from_secs(5)),
+        });
+    let context = ToolContext::default();
+
+    // Short results are untouched, long ones keep their start and end
+    let short = registry
+        .execute(&tool_call("echo", r#"{"text": "hello"}"#), &context)
+        .await
+        .unwrap();
+    assert_eq!(short, "hello");
+    let text = format!("{}{}{}", "<".repeat(10), "é".repeat(100), ">".repeat(10));
+    let long = registry
+        .execute(
+            &tool_call("echo", &json!({ "text": text }).to_string()),
+            &context,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        long,
+        "<<<<<<<<<<\n[... 100 characters truncated ...]\n>>>>>>>>>>"
+    );
+
+    // Slow calls fail instead of holding up the turn
+    let slow = registry
+        .execute(&tool_call("sleep", r#"{"millis": 5000}"#), &context)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        slow.downcast_ref::<ToolTimedOut>(),
+        Some(&ToolTimedOut {
+            tool: "sleep".to_string(),
+            timeout: Duration::from_millis(100),
+        })
+    );
+    assert_eq!(
+        serde_json::from_str::<Value>(&error_content(&slow)).unwrap(),
+        json!({"error": "sleep did not finish within 0.1s", "kind": "timeout"})
+    );
+    // Tools that need longer say so
+    let patient = registry
+        .execute(&tool_call("patient_sleep", r#"{"millis": 300}"#), &context)
+        .await
+        .unwrap();
+    assert_eq!(patient, "awake");
+
+    // Every failure has a kind the model can react to
+    let unknown = registry
+        .execute(&tool_call("missing", "{}"), &context)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        serde_json::from_str::<Value>(&error_content(&unknown)).unwrap(),
+        json!({"error": "Unknown tool: missing", "kind": "unknown_tool"})
+    );
+    assert_eq!(
+        serde_json::from_str::<Value>(&error_content(&anyhow::anyhow!("GitHub is down"))).unwrap(),
+        json!({"error": "GitHub is down", "kind": "error"})
+    );
+}
+
+#[tokio::test]
+async fn test_registering_replaces_tools_with_the_same_name() {
+    let github_service = Arc::new(GitHubService::with_base_url(
+        "test_token".to_string(),
+        "http://127.0.0.1:9".to_string(),
+    ));
+    let mut registry = create_tools(github_service);
+    registry.register(Echo).register(Echo);
+    assert_eq!(
+        registry.names(),
+        vec![
+            "read_github_issue",
+            "calculate",
+            "post_github_comment",
+            "list_github_issues",
+            "search_github_issues",
+            "read_pull_request",
+            "list_github_labels",
+            "echo"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_chat_handler_runs_registered_tools() {
+    let mock_server = MockServer::start().await;
+
+    // Answer once the tool result is in
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_string_contains(r#""role":"tool""#))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"content": "The tool said hello", "role": "assistant"}}]
+        })))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .and(body_partial_json(json!({ "tool_choice": "auto" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": "",
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "echo", "arguments": "{\"text\": \"hello\"}"}
+                    }]
+                }
+            }]
+        })))
+        .with_priority(2)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "message": {
+                    "content": json!({
+                        "needs_tool": true,
+                        "reasoning": "User wants an echo",
+                        "suggested_tool": "echo"
+                    }).to_string(),
+                    "role": "assistant"
+                }
+            }]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let model = Arc::new(DeepSeekService::with_base_url(
+        "test_key".to_string(),
+        mock_server.uri(),
+    ));
+    let mut tools = ToolRegistry::new();
+    tools.register(Echo);
+
+    let ws_state = WebSocketState::new(model.clone(), model, tools);
+    let mut rx = ws_state.add_test_connection("test_conn", 1).await;
+    let chat_handler = ChatHandler::new(ws_state.clone());
+
+    chat_handler
+        .handle_message(
+            ChatMessage::UserMessage {
+                content: "Echo hello".to_string(),
+            },
+            "test_conn".to_string(),
+        )
+        .await
+        .unwrap();
+
+    let mut sent = Vec::new();
+    while let Ok(Message::Text(text)) = rx.try_recv() {
+        sent.push(serde_json::from_str::<Value>(&text).unwrap());
+    }
+    assert!(sent
+        .iter()
+        .any(|m| m["status"] == "tool_calls" && m["content"] == "Running echo"));
+    let last = sent.last().unwrap();
+    assert_eq!(last["status"], "complete");
+    assert_eq!(last["content"], "The tool said hello");
+
+    // The tool result went back to the model
+    let requests = mock_server.received_requests().await.unwrap();
+    let body: Value = serde_json::from_slice(&requests.last().unwrap().body).unwrap();
+    let tool_message = body["messages"].as_array().unwrap().last().unwrap().clone();
+    assert_eq!(
+        tool_message,
+        json!({"role": "tool", "content": "hello", "tool_call_id": "call_1"})
+    );
+}
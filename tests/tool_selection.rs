@@ -1,8 +1,9 @@
 use dotenvy::dotenv;
-use openagents::server::services::deepseek::{ChatMessage, DeepSeekService, ToolChoice};
+use openagents::server::services::deepseek::{
+    ChatCompletion, ChatMessage, DeepSeekService, ToolChoice,
+};
 use serde_json::json;
 use tracing::{info, Level};
-use tracing_subscriber;
 use wiremock::{
     matchers::{body_string_contains, header, method, path},
     Mock, MockServer, ResponseTemplate,
@@ -137,7 +138,7 @@ async fn test_tool_selection() {
             .await;
 
         // Create messages with system context
-        let _messages = vec![
+        let _messages = [
             ChatMessage {
                 role: "system".to_string(),
                 content: "You are a helpful assistant that reads GitHub issues. When referring to the repository, always use 'OpenAgentsInc' as the owner and 'openagents' (lowercase) as the repository name.".to_string(),
@@ -152,7 +153,11 @@ async fn test_tool_selection() {
             },
         ];
 
-        let (response, _, tool_calls) = service
+        let ChatCompletion {
+            content: response,
+            tool_calls,
+            ..
+        } = service
             .chat_with_tools(
                 input.to_string(),
                 vec![read_issue_tool.clone()],
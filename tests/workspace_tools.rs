@@ -0,0 +1,343 @@
+use openagents::server::services::deepseek::{FunctionCallResponse, ToolCallResponse};
+use openagents::server::tools::{
+    create_workspace_tools, error_content, ToolContext, ToolRegistry, Workspace, WorkspaceError,
+};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// A workspace with a small repository in it, next to a secret it must not
+/// reach. Removed when dropped.
+struct Fixture {
+    dir: PathBuf,
+    workspace: Arc<Workspace>,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("openagents-workspace-{}", Uuid::new_v4()));
+        let root = dir.join("repo");
+        fs::create_dir_all(root.join("src/server")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    println!(\"hello\");\n    run();\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/server/mod.rs"),
+            "pub fn run() {}\npub fn stop() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("target/build.rs"), "pub fn run() {}\n").unwrap();
+        fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+        fs::write(dir.join("secret.txt"), "hunter2\n").unwrap();
+
+        let workspace = Arc::new(Workspace::new(&root).unwrap());
+        Self { dir, workspace }
+    }
+
+    fn tools(&self) -> ToolRegistry {
+        create_workspace_tools(self.workspace.clone(), true)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+async fn call(tools: &ToolRegistry, name: &str, arguments: Value) -> Value {
+    let call = ToolCallResponse {
+        id: "call_1".to_string(),
+        tool_type: "function".to_string(),
+        function: FunctionCallResponse {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        },
+    };
+    let content = match tools.execute(&call, &ToolContext::default()).await {
+        Ok(content) => content,
+        Err(e) => error_content(&e),
+    };
+    serde_json::from_str(&content).unwrap()
+}
+
+#[tokio::test]
+async fn test_paths_cannot_leave_the_workspace() {
+    let fixture = Fixture::new();
+    let root = fixture.workspace.root().to_path_buf();
+    std::os::unix::fs::symlink(fixture.dir.join("secret.txt"), root.join("secret")).unwrap();
+    std::os::unix::fs::symlink(&fixture.dir, root.join("parent")).unwrap();
+    std::os::unix::fs::symlink(root.join("src"), root.join("code")).unwrap();
+
+    for path in [
+        "../secret.txt",
+        "src/../../secret.txt",
+        "/etc/passwd",
+        "secret",
+        "parent/secret.txt",
+        "parent/new.txt",
+    ] {
+        assert!(
+            matches!(
+                fixture.workspace.resolve(path),
+                Err(WorkspaceError::OutsideWorkspace { .. })
+            ),
+            "{} resolved",
+            path
+        );
+    }
+
+    // Going up and back down, and symlinks within the workspace, are fine
+    assert_eq!(
+        fixture.workspace.resolve("src/../src/main.rs").unwrap(),
+        root.join("src/main.rs")
+    );
+    assert_eq!(
+        fixture.workspace.resolve("code/main.rs").unwrap(),
+        root.join("src/main.rs")
+    );
+    assert_eq!(
+        fixture.workspace.resolve("docs/new.md").unwrap(),
+        root.join("docs/new.md")
+    );
+
+    let tools = fixture.tools();
+    assert_eq!(
+        call(&tools, "read_file", json!({"path": "secret"})).await,
+        json!({"error": "secret is outside the workspace", "kind": "outside_workspace"})
+    );
+    assert_eq!(
+        call(
+            &tools,
+            "write_file",
+            json!({"path": "parent/secret.txt", "content": "changed"})
+        )
+        .await["kind"],
+        "outside_workspace"
+    );
+    assert_eq!(
+        fs::read_to_string(fixture.dir.join("secret.txt")).unwrap(),
+        "hunter2\n"
+    );
+}
+
+#[tokio::test]
+async fn test_git_directory_is_off_limits() {
+    let fixture = Fixture::new();
+    let root = fixture.workspace.root().to_path_buf();
+    fs::create_dir_all(root.join(".git/hooks")).unwrap();
+    fs::write(root.join(".git/config"), "[core]\n").unwrap();
+    std::os::unix::fs::symlink(root.join(".git"), root.join("meta")).unwrap();
+
+    for path in [
+        ".git",
+        ".git/config",
+        ".git/hooks/pre-commit",
+        "src/../.git/config",
+        "meta/config",
+        "vendor/.git/config",
+    ] {
+        assert!(
+            matches!(
+                fixture.workspace.resolve(path),
+                Err(WorkspaceError::GitDirectory { .. })
+            ),
+            "{} resolved",
+            path
+        );
+    }
+    // Only a component named exactly .git counts
+    assert_eq!(
+        fixture.workspace.resolve(".gitignore").unwrap(),
+        root.join(".gitignore")
+    );
+
+    let tools = fixture.tools();
+    assert_eq!(
+        call(&tools, "read_file", json!({"path": ".git/config"})).await,
+        json!({"error": ".git/config is inside a .git directory", "kind": "git_directory"})
+    );
+    assert_eq!(
+        call(
+            &tools,
+            "write_file",
+            json!({"path": ".git/hooks/pre-commit", "content": "#!/bin/sh\ncurl evil\n"})
+        )
+        .await["kind"],
+        "git_directory"
+    );
+    assert!(!root.join(".git/hooks/pre-commit").exists());
+}
+
+#[tokio::test]
+async fn test_read_file_and_list_dir() {
+    let fixture = Fixture::new();
+    let tools = fixture.tools();
+    assert_eq!(
+        tools.names(),
+        vec!["read_file", "list_dir", "grep", "write_file"]
+    );
+
+    assert_eq!(
+        call(
+            &tools,
+            "read_file",
+            json!({"path": "src/main.rs", "start_line": 2, "end_line": 3})
+        )
+        .await,
+        json!({
+            "path": "src/main.rs",
+            "start_line": 2,
+            "end_line": 3,
+            "total_lines": 4,
+            "content": "    println!(\"hello\");\n    run();"
+        })
+    );
+    let whole = call(&tools, "read_file", json!({"path": "./src/server/mod.rs"})).await;
+    assert_eq!(whole["content"], "pub fn run() {}\npub fn stop() {}");
+    assert_eq!(whole["end_line"], 2);
+
+    assert_eq!(
+        call(&tools, "read_file", json!({"path": "logo.png"})).await["kind"],
+        "binary"
+    );
+    assert_eq!(
+        call(&tools, "read_file", json!({"path": "src"})).await["kind"],
+        "not_a_file"
+    );
+    assert_eq!(
+        call(&tools, "read_file", json!({"path": "missing.rs"})).await["kind"],
+        "not_found"
+    );
+    assert_eq!(
+        call(
+            &tools,
+            "read_file",
+            json!({"path": "src/main.rs", "start_line": 0})
+        )
+        .await["kind"],
+        "invalid_arguments"
+    );
+
+    assert_eq!(
+        call(&tools, "list_dir", json!({})).await,
+        json!({"entries": [
+            {"name": "logo.png", "type": "file", "size": 10},
+            {"name": "src", "type": "dir"},
+            {"name": "target", "type": "dir"}
+        ]})
+    );
+    assert_eq!(
+        call(&tools, "list_dir", json!({"path": "src/main.rs"})).await["kind"],
+        "not_a_directory"
+    );
+}
+
+#[tokio::test]
+async fn test_grep() {
+    let fixture = Fixture::new();
+    let tools = fixture.tools();
+
+    // Skips target/ and binary files, in path order
+    assert_eq!(
+        call(&tools, "grep", json!({"pattern": r"\brun\(\)"})).await,
+        json!({
+            "matches": [
+                {"path": "src/main.rs", "line": 3, "text": "    run();"},
+                {"path": "src/server/mod.rs", "line": 1, "text": "pub fn run() {}"}
+            ],
+            "truncated": false
+        })
+    );
+    assert_eq!(
+        call(
+            &tools,
+            "grep",
+            json!({"pattern": "PUB FN", "path": "src/server", "case_insensitive": true, "max_matches": 1})
+        )
+        .await,
+        json!({
+            "matches": [{"path": "src/server/mod.rs", "line": 1, "text": "pub fn run() {}"}],
+            "truncated": true
+        })
+    );
+    assert_eq!(
+        call(&tools, "grep", json!({"pattern": "("})).await["kind"],
+        "invalid_pattern"
+    );
+}
+
+#[tokio::test]
+async fn test_write_file() {
+    let fixture = Fixture::new();
+    let root = fixture.workspace.root().to_path_buf();
+    let tools = fixture.tools();
+
+    assert_eq!(
+        call(
+            &tools,
+            "write_file",
+            json!({"path": "docs/notes.md", "content": "# Notes\n"})
+        )
+        .await,
+        json!({"path": "docs/notes.md", "result": "written"})
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("docs/notes.md")).unwrap(),
+        "# Notes\n"
+    );
+
+    assert_eq!(
+        call(
+            &tools,
+            "write_file",
+            json!({"path": "src/server/mod.rs", "old_text": "pub fn stop() {}", "new_text": "pub fn stop() -> bool {\n    true\n}"})
+        )
+        .await,
+        json!({"path": "src/server/mod.rs", "result": "edited"})
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("src/server/mod.rs")).unwrap(),
+        "pub fn run() {}\npub fn stop() -> bool {\n    true\n}\n"
+    );
+
+    assert_eq!(
+        call(
+            &tools,
+            "write_file",
+            json!({"path": "src/server/mod.rs", "old_text": "pub fn", "new_text": "fn"})
+        )
+        .await,
+        json!({
+            "error": "The text to replace appears 2 times in src/server/mod.rs; include more context so it is unique",
+            "kind": "edit_mismatch"
+        })
+    );
+    assert_eq!(
+        call(
+            &tools,
+            "write_file",
+            json!({"path": "src/main.rs", "old_text": "goodbye", "new_text": "hello"})
+        )
+        .await["kind"],
+        "edit_mismatch"
+    );
+    assert_eq!(
+        call(&tools, "write_file", json!({"path": "src/main.rs"})).await,
+        json!({
+            "error": "Invalid arguments for write_file: give either content, or old_text and new_text",
+            "kind": "invalid_arguments",
+            "violations": [{"path": "", "message": "give either content, or old_text and new_text"}]
+        })
+    );
+
+    // The exploring tools alone can't change anything
+    let read_only = create_workspace_tools(fixture.workspace.clone(), false);
+    assert!(read_only.get("write_file").is_none());
+}